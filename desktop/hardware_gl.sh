@@ -6,4 +6,4 @@ if [[ "$2" == "--debug" ]]; then
     FEATURES="$FEATURES,debug"
 fi
 
-cargo run --release --no-default-features --features "$FEATURES" "$1"
\ No newline at end of file
+cargo run --release --no-default-features --features "$FEATURES" -- "$@"
\ No newline at end of file
@@ -10,8 +10,13 @@ use renderer_metal::renderer::Renderer;
 use renderer_opengl::renderer::Renderer;
 use rsx_redux::cpu::bus::spu::NUM_SAMPLES;
 use rsx_redux::cpu::CPU;
-use rsx_redux::cpu::bus::gpu::{GPU, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rsx_redux::cpu::crash;
+use rsx_redux::cpu::logger::{self, Channel, Level};
+use rsx_redux::cpu::bus::gpu::{GPU, SCREEN_HEIGHT, SCREEN_WIDTH, dump::GpuDump};
 use rsx_redux::cpu::bus::peripherals::memory_card::MEMORY_SIZE;
+use rsx_redux::log;
+#[cfg(feature = "perf_counters")]
+use rsx_redux::cpu::perf::SUBSYSTEMS;
 use sdl2::GameControllerSubsystem;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::controller::{Axis, Button};
@@ -29,12 +34,17 @@ use std::fs::{self, File, OpenOptions};
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const BUTTON_UP: usize = 4;
 const BUTTON_RIGHT: usize = 5;
 const BUTTON_DOWN: usize = 6;
 const BUTTON_LEFT: usize = 7;
 
+// the perf overlay shows averages over this many frames
+#[cfg(feature = "perf_counters")]
+const PERF_OVERLAY_FRAMES: u32 = 60;
+
 pub struct PsxAudioCallback {
     pub audio_buffer: VecDeque<i16>,
 }
@@ -103,6 +113,12 @@ pub struct Frontend {
     button_map: HashMap<Button, usize>,
     button_map2: HashMap<Axis, usize>,
     key_map: HashMap<Keycode, usize>,
+    pub coverage_path: Option<PathBuf>,
+    pub profile_path: Option<PathBuf>,
+    #[cfg(feature = "perf_counters")]
+    perf_overlay: bool,
+    #[cfg(feature = "perf_counters")]
+    perf_frames: u32,
 }
 
 impl Frontend {
@@ -260,6 +276,12 @@ impl Frontend {
             controller_id: None,
             retry_attempts: 0,
             key_map,
+            coverage_path: None,
+            profile_path: None,
+            #[cfg(feature = "perf_counters")]
+            perf_overlay: true,
+            #[cfg(feature = "perf_counters")]
+            perf_frames: 0,
             #[cfg(feature = "hardware_gpu_opengl")]
             window,
         }
@@ -270,6 +292,43 @@ impl Frontend {
         self.window.gl_swap_window();
     }
 
+    #[cfg(feature = "perf_counters")]
+    fn set_title(&mut self, title: &str) {
+        #[cfg(feature = "hardware_gpu_metal")]
+        self._window.set_title(title).unwrap();
+        #[cfg(feature = "hardware_gpu_opengl")]
+        self.window.set_title(title).unwrap();
+        #[cfg(feature = "software_gpu")]
+        self.canvas.window_mut().set_title(title).unwrap();
+    }
+
+    // there's no text rendering, so the overlay goes in the window title, ie "cpu 9.1ms gpu 2.3ms spu 1.0ms"
+    #[cfg(feature = "perf_counters")]
+    pub fn update_perf_overlay(&mut self, cpu: &mut CPU) {
+        self.perf_frames += 1;
+
+        if self.perf_frames < PERF_OVERLAY_FRAMES {
+            return;
+        }
+
+        let mut title = "RSX-redux".to_string();
+
+        if self.perf_overlay {
+            for subsystem in SUBSYSTEMS {
+                let time = cpu.bus.perf.counter(subsystem).time / self.perf_frames;
+
+                if !time.is_zero() {
+                    title += &format!(" {subsystem} {:.1}ms", time.as_secs_f64() * 1000.0);
+                }
+            }
+        }
+
+        self.set_title(&title);
+
+        cpu.bus.perf.reset();
+        self.perf_frames = 0;
+    }
+
     pub fn get_memory_card_path() -> Option<PathBuf> {
         if let Some(mut memory_path) = data_dir() {
             memory_path.push("RSX-redux");
@@ -303,12 +362,7 @@ impl Frontend {
         None
     }
 
-    fn get_quick_state_path(cpu: &CPU) -> PathBuf {
-        #[cfg(feature = "software_gpu")]
-        let filename = "quick_save_sw.state";
-        #[cfg(any(feature = "hardware_gpu_metal", feature = "hardware_gpu_opengl"))]
-        let filename = "quick_save_hw.state";
-
+    fn get_game_dir(cpu: &CPU) -> PathBuf {
         let game_path = Path::new(&cpu.game_path);
 
         let game_path_str = game_path
@@ -326,13 +380,157 @@ impl Frontend {
         dir.push("RSX-redux");
         dir.push(game_name);
 
-        fs::create_dir_all(&dir).expect("Couldn't create save state directory");
+        fs::create_dir_all(&dir).expect("Couldn't create game data directory");
+
+        dir
+    }
+
+    fn get_quick_state_path(cpu: &CPU) -> PathBuf {
+        #[cfg(feature = "software_gpu")]
+        let filename = "quick_save_sw.state";
+        #[cfg(any(feature = "hardware_gpu_metal", feature = "hardware_gpu_opengl"))]
+        let filename = "quick_save_hw.state";
+
+        let mut dir = Self::get_game_dir(cpu);
 
         dir.push(filename);
 
         dir
     }
 
+    fn get_cheats_path(cpu: &CPU) -> PathBuf {
+        let mut dir = Self::get_game_dir(cpu);
+
+        dir.push("cheats.json");
+
+        dir
+    }
+
+    pub fn load_cheats(cpu: &mut CPU) {
+        if let Ok(json) = fs::read_to_string(Self::get_cheats_path(cpu)) {
+            match cpu.bus.cheats.load_json(&json) {
+                Ok(()) => println!("loaded {} cheat(s)", cpu.bus.cheats.cheats().len()),
+                Err(e) => log!(Cpu, Warn, "couldn't load cheats: {e}"),
+            }
+        }
+    }
+
+    pub fn save_cheats(cpu: &CPU) {
+        fs::write(Self::get_cheats_path(cpu), cpu.bus.cheats.to_json()).unwrap();
+    }
+
+    // lcov when there are symbols to map addresses to source, otherwise a list of basic blocks
+    fn save_coverage(cpu: &CPU, coverage_path: &Path) {
+        if let Some(coverage) = cpu.coverage() {
+            let output = match coverage.to_lcov(&cpu.symbols) {
+                Ok(lcov) => lcov,
+                Err(_) => coverage.to_string(),
+            };
+
+            fs::write(coverage_path, output).unwrap();
+        }
+    }
+
+    fn save_machine_state(cpu: &CPU) {
+        let mut path = Self::get_game_dir(cpu);
+
+        path.push("machine_state.json");
+
+        fs::write(&path, cpu.inspect_json()).unwrap();
+
+        println!("saved the machine state to {}", path.display());
+    }
+
+    // F12 writes VRAM as is, which rsx-vram can read no matter which renderer the build uses
+    fn save_vram_dump(&self, cpu: &CPU) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let mut path = Self::get_game_dir(cpu);
+
+        path.push("vram_dumps");
+
+        fs::create_dir_all(&path).unwrap();
+
+        path.push(format!("{timestamp}.bin"));
+
+        match fs::write(&path, self.get_vram(&cpu.bus.gpu)) {
+            Ok(()) => println!("saved a VRAM dump to {}", path.display()),
+            Err(e) => log!(Gpu, Warn, "couldn't save the VRAM dump: {e}"),
+        }
+    }
+
+    // goes into the game's directory, under crashes/<unix time>
+    pub fn write_crash_bundle(cpu: &mut CPU) {
+        let message = crash::take_panic_message().unwrap_or_else(|| "unknown panic".to_string());
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let mut dir = Self::get_game_dir(cpu);
+
+        dir.push("crashes");
+        dir.push(timestamp.to_string());
+
+        match cpu.write_crash_bundle(&dir, &message) {
+            Ok(()) => println!("wrote a crash report to {}", dir.display()),
+            Err(e) => log!(Cpu, Warn, "couldn't write a crash report: {e}"),
+        }
+    }
+
+    // VRAM as 16-bit pixels, which the hardware renderers have to read back from their textures
+    #[cfg_attr(not(feature = "software_gpu"), allow(unused_variables))]
+    pub fn get_vram(&self, gpu: &GPU) -> Vec<u8> {
+        #[cfg(feature = "software_gpu")]
+        let vram = gpu.vram.to_vec();
+        #[cfg(feature = "hardware_gpu_metal")]
+        let (vram, _) = self.renderer.get_vram_textures();
+        #[cfg(feature = "hardware_gpu_opengl")]
+        let (_, vram) = self.renderer.get_vram_textures();
+
+        vram
+    }
+
+    // F11 starts recording a GPU dump and stops it again, dumps go in the game's directory under gpu_dumps
+    fn toggle_gpu_dump(cpu: &mut CPU, get_vram: impl FnOnce(&GPU) -> Vec<u8>) {
+        if let Some(dump) = cpu.bus.gpu.stop_dump() {
+            Self::save_gpu_dump(cpu, dump);
+
+            return;
+        }
+
+        let vram = get_vram(&cpu.bus.gpu);
+
+        match cpu.bus.gpu.start_dump(vram) {
+            Ok(()) => println!("recording a GPU dump, press F11 again to stop"),
+            Err(e) => log!(Gpu, Warn, "couldn't start a GPU dump: {e}"),
+        }
+    }
+
+    fn save_gpu_dump(cpu: &CPU, dump: GpuDump) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let mut path = Self::get_game_dir(cpu);
+
+        path.push("gpu_dumps");
+
+        fs::create_dir_all(&path).unwrap();
+
+        path.push(format!("{timestamp}.gpudump"));
+
+        match fs::write(&path, dump.to_bytes()) {
+            Ok(()) => println!("saved a GPU dump of {} frame(s) to {}", dump.frames(), path.display()),
+            Err(e) => log!(Gpu, Warn, "couldn't save the GPU dump: {e}"),
+        }
+    }
+
     fn load_quick_state_inner(cpu: &mut CPU, after_load: impl FnOnce(&mut CPU)) {
         let quick_save_path = Self::get_quick_state_path(cpu);
 
@@ -431,12 +629,39 @@ impl Frontend {
         });
     }
 
-    pub fn handle_events(&mut self, cpu: &mut CPU) {
+    // saves whatever was being recorded before exiting
+    pub fn exit(&self, cpu: &mut CPU, code: i32) -> ! {
+        cpu.stop_trace().unwrap();
+        if let Some(coverage_path) = &self.coverage_path {
+            Self::save_coverage(cpu, coverage_path);
+        }
+        if let Some(profile_path) = &self.profile_path
+            && let Some(profiler) = cpu.profiler()
+        {
+            fs::write(profile_path, profiler.to_folded(&cpu.symbols)).unwrap();
+        }
+        if let Some(dump) = cpu.bus.gpu.stop_dump() {
+            Self::save_gpu_dump(cpu, dump);
+        }
+        exit(code);
+    }
+
+    // all a GPU dump player needs, nothing it does can reach the console
+    pub fn handle_quit_events(&mut self) {
         for event in self.event_pump.poll_iter() {
+            if let Event::Quit { .. } = event {
+                exit(0);
+            }
+        }
+    }
+
+    pub fn handle_events(&mut self, cpu: &mut CPU) {
+        // collected first so that the hotkeys can still borrow the frontend
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+
+        for event in events {
             match event {
-                Event::Quit { .. } => {
-                    exit(0);
-                }
+                Event::Quit { .. } => self.exit(cpu, 0),
                 Event::KeyDown { keycode, .. } => {
                     if let Some(keycode) = keycode {
                         if let Some(index) = self.key_map.get(&keycode) {
@@ -444,10 +669,14 @@ impl Frontend {
                         } else {
                             let digital_mode = cpu.bus.peripherals.controller.digital_mode;
                             match keycode {
-                                #[cfg(feature = "debug")]
                                 Keycode::G => {
-                                    cpu.debug_on = !cpu.debug_on;
-                                    println!("setting debug on to {}", cpu.debug_on);
+                                    let level = if logger::level(Channel::Cpu) == Some(Level::Trace) {
+                                        Level::Warn
+                                    } else {
+                                        Level::Trace
+                                    };
+                                    logger::set_level(Channel::Cpu, Some(level));
+                                    println!("set the cpu log level to {level}");
                                 }
                                 Keycode::E => {
                                     if !cpu.bus.peripherals.controller.digital_mode_locked {
@@ -463,6 +692,22 @@ impl Frontend {
                                 Keycode::F => {
                                     cpu.bus.gpu.debug_on = !cpu.bus.gpu.debug_on;
                                 }
+                                Keycode::C => {
+                                    cpu.bus.cartridge.toggle_switch();
+                                    println!(
+                                        "set cartridge switch to {}",
+                                        cpu.bus.cartridge.switch_on
+                                    );
+                                }
+                                #[cfg(feature = "perf_counters")]
+                                Keycode::F9 => {
+                                    self.perf_overlay = !self.perf_overlay;
+                                }
+                                Keycode::F10 => Self::save_machine_state(cpu),
+                                Keycode::F11 => {
+                                    Self::toggle_gpu_dump(cpu, |gpu| self.get_vram(gpu))
+                                }
+                                Keycode::F12 => self.save_vram_dump(cpu),
                                 Keycode::F5 => {
                                     #[cfg(feature = "software_gpu")]
                                     Self::create_quick_state(cpu);
@@ -472,6 +717,11 @@ impl Frontend {
                                     Self::create_quick_state(&mut self.renderer, cpu);
                                 }
                                 Keycode::F7 => {
+                                    // a dump can't go on past a jump to another point in time
+                                    if let Some(dump) = cpu.bus.gpu.stop_dump() {
+                                        Self::save_gpu_dump(cpu, dump);
+                                    }
+
                                     #[cfg(feature = "software_gpu")]
                                     Self::load_quick_state(cpu);
                                     #[cfg(feature = "hardware_gpu_metal")]
@@ -0,0 +1,53 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use rsx_redux::{cpu::exe::Executable, log};
+
+// polls the exe's modification time, which is cheap enough to do once a frame
+pub struct ExeWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    pub keep_ram: bool,
+}
+
+impl ExeWatcher {
+    pub fn new(path: PathBuf, keep_ram: bool) -> Self {
+        let modified = Self::modified_time(&path);
+
+        Self {
+            path,
+            modified,
+            keep_ram,
+        }
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    // returns the new exe once the file has changed
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let modified = Self::modified_time(&self.path);
+
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+
+        self.modified = modified;
+
+        let exe_bytes = fs::read(&self.path).ok()?;
+
+        // most likely the linker is still writing it, finishing will change the time again
+        if let Err(e) = Executable::parse(&exe_bytes) {
+            log!(Cpu, Warn, "not reloading {}: {e}", self.path.display());
+            return None;
+        }
+
+        Some(exe_bytes)
+    }
+}
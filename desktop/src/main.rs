@@ -1,28 +1,130 @@
 use std::{
     env,
     fs::{self, File},
-    path::Path,
+    io::{BufWriter, Read},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
 };
 
 use frontend::Frontend;
+use hot_reload::ExeWatcher;
 use memmap2::Mmap;
 #[cfg(feature = "hardware_gpu_metal")]
 use objc2_core_foundation::CGSize;
-use rsx_redux::cpu::CPU;
+use rsx_redux::cpu::{
+    CPU,
+    bus::{
+        cartridge::CartridgeType,
+        gpu::{
+            dump::{GpuDump, GpuPlayer},
+            vram_viewer::VramViewer,
+        },
+    },
+    crash,
+    exe::{self, ExeFormat, Executable},
+    logger,
+    perf::Subsystem,
+    profiler,
+};
+use rsx_redux::log;
 
 pub mod frontend;
+pub mod hot_reload;
 
 // TODO: fix using unsafe for type coersion (ie reading a u16 from a byte array) to use std::ptr::read_unaligned
 
+fn get_option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.as_str())
+}
+
+// plays a GPU dump back without the BIOS or the game, --frames writes VRAM as a PNG after every
+// frame so renderers can be compared
+fn play_gpu_dump(dump_path: &Path, frames_dir: Option<&Path>) {
+    let dump = fs::read(dump_path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| GpuDump::from_bytes(&data))
+        .unwrap_or_else(|e| panic!("couldn't load {}: {e}", dump_path.display()));
+
+    if let Some(dir) = frames_dir {
+        fs::create_dir_all(dir).unwrap();
+    }
+
+    // nothing but the GPU gets to run
+    let mut cpu = CPU::new(None, dump_path.to_string_lossy().to_string());
+    let mut frontend = Frontend::new(&cpu.bus.gpu);
+    let mut player = GpuPlayer::new(dump);
+
+    #[cfg(feature = "hardware_gpu_metal")]
+    frontend.renderer.metal_layer.setDrawableSize(CGSize::new(
+        cpu.bus.gpu.display_width as f64,
+        cpu.bus.gpu.display_height as f64,
+    ));
+
+    println!("playing a GPU dump of {} frame(s)", player.frames());
+
+    let mut frame = 0;
+
+    loop {
+        if player.play_frame(&mut cpu.bus.gpu) {
+            #[cfg(feature = "hardware_gpu_metal")]
+            frontend.renderer.process(&mut cpu.bus.gpu);
+            #[cfg(feature = "hardware_gpu_opengl")]
+            frontend.renderer.process(&mut cpu.bus.gpu);
+
+            if let Some(dir) = frames_dir {
+                let vram = frontend.get_vram(&cpu.bus.gpu);
+                let path = dir.join(format!("frame_{frame:05}.png"));
+                let output = BufWriter::new(File::create(path).unwrap());
+
+                let result = VramViewer::new(&vram).and_then(|viewer| viewer.full().write_png(output));
+
+                if let Err(e) = result {
+                    log!(Gpu, Warn, "couldn't write frame {frame}: {e}");
+                }
+            }
+
+            frame += 1;
+
+            if player.is_finished() {
+                println!("finished playing the GPU dump");
+            }
+        }
+
+        #[cfg(feature = "hardware_gpu_metal")]
+        frontend.renderer.present(&mut cpu.bus.gpu);
+        #[cfg(feature = "hardware_gpu_opengl")]
+        {
+            frontend.renderer.present(&mut cpu.bus.gpu);
+            frontend.end_frame();
+        }
+        #[cfg(feature = "software_gpu")]
+        frontend.render(&mut cpu.bus.gpu);
+
+        cpu.bus.gpu.cap_fps();
+
+        frontend.handle_quit_events();
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    crash::install_panic_hook();
+
     if args.len() < 2 {
         panic!("syntax: ./psx-redux <path_to_game/exe>");
     }
 
+    // ie `--log info,cdrom=debug,kernel=off`, the G key switches the cpu channel to trace and back
+    if let Some(filters) = get_option(&args, "--log") {
+        logger::apply_filters(filters).expect("invalid log filters");
+    }
+
     let file_path = Path::new(&args[1]);
-    let bios = fs::read("SCPH1001.bin").unwrap();
 
     let file_extension = file_path
         .extension()
@@ -30,10 +132,36 @@ fn main() {
         .to_str()
         .unwrap_or_default();
 
+    if file_extension == "gpudump" {
+        play_gpu_dump(file_path, get_option(&args, "--frames").map(Path::new));
+
+        return;
+    }
+
+    let bios = fs::read("SCPH1001.bin").unwrap();
+
+    // executables are recognized by their magic rather than the extension, ELF files often don't have one
+    let mut header = [0; 8];
+    let header_len = File::open(file_path).unwrap().read(&mut header).unwrap();
+
+    let exe_format = exe::detect_format(&header[..header_len]);
+
     let mut cpu = match file_extension {
-        "exe" => {
+        _ if exe_format.is_some() => {
             let exe_bytes = fs::read(&args[1]).unwrap();
-            CPU::new(Some(exe_bytes), "".to_string())
+
+            if let Err(e) = Executable::parse(&exe_bytes) {
+                panic!("couldn't load {}: {e}", args[1]);
+            }
+
+            let mut cpu = CPU::new(Some(exe_bytes.clone()), "".to_string());
+
+            if exe_format == Some(ExeFormat::Elf) {
+                // not every ELF has a symbol table, which is fine
+                let _ = cpu.symbols.load_elf(&exe_bytes);
+            }
+
+            cpu
         }
         "bin" => {
             let file = File::open(file_path).unwrap();
@@ -59,6 +187,93 @@ fn main() {
     };
 
     cpu.bus.load_bios(bios);
+
+    if let Some(cart_path) = get_option(&args, "--cart") {
+        let cartridge_type = match get_option(&args, "--cart-type") {
+            Some("xplorer") => CartridgeType::Xplorer,
+            _ => CartridgeType::ActionReplay,
+        };
+
+        cpu.bus
+            .cartridge
+            .load_rom(fs::read(cart_path).unwrap(), cartridge_type);
+    }
+
+    if let Some(port) = get_option(&args, "--gdb") {
+        let port = port.parse::<u16>().expect("invalid gdb port");
+
+        cpu.start_gdb_server(port).unwrap();
+
+        println!("waiting for gdb to attach on 127.0.0.1:{port}");
+    }
+
+    // lets dev builds open files on the host with PCdrv, restricted to the given directory
+    if let Some(pcdrv_root) = get_option(&args, "--pcdrv") {
+        cpu.enable_pcdrv(Path::new(pcdrv_root)).expect("invalid PCdrv directory");
+    }
+
+    // symbols show up in disassembly, ie a .SYM, .MAP, ELF or nm output
+    if let Some(symbols_path) = get_option(&args, "--symbols") {
+        match cpu.symbols.load(&fs::read(symbols_path).unwrap()) {
+            Ok(count) => println!("loaded {count} symbol(s)"),
+            Err(e) => log!(Cpu, Warn, "couldn't load symbols: {e}"),
+        }
+    }
+
+    // keeps a snapshot per frame so the debugger can step backwards
+    if let Some(frames) = get_option(&args, "--history") {
+        let frames = frames.parse::<usize>().expect("invalid number of history frames");
+
+        cpu.enable_reverse_execution(frames);
+    }
+
+    if let Some(trace_path) = get_option(&args, "--trace") {
+        let trace_file = BufWriter::new(File::create(trace_path).unwrap());
+
+        cpu.start_trace(trace_file).unwrap();
+    }
+
+    // restarts the exe whenever it gets rebuilt, --keep-ram skips the reset and jumps straight into it
+    let hot_reload = args.iter().any(|arg| arg == "--hot-reload");
+    let keep_ram = args.iter().any(|arg| arg == "--keep-ram");
+
+    let mut exe_watcher = if exe_format.is_some() && hot_reload {
+        Some(ExeWatcher::new(file_path.to_path_buf(), keep_ram))
+    } else {
+        None
+    };
+
+    // counts every instruction that runs, saved on exit
+    let coverage_path = get_option(&args, "--coverage").map(PathBuf::from);
+
+    if coverage_path.is_some() {
+        cpu.start_coverage();
+    }
+
+    // samples the guest's call stack, saved on exit as folded stacks for flamegraph tools
+    let profile_path = get_option(&args, "--profile").map(PathBuf::from);
+
+    if profile_path.is_some() {
+        let interval = match get_option(&args, "--profile-interval") {
+            Some(interval) => interval.parse::<u64>().expect("invalid profiler interval"),
+            None => profiler::DEFAULT_INTERVAL,
+        };
+
+        cpu.start_profiler(interval);
+    }
+
+    Frontend::load_cheats(&mut cpu);
+
+    // new cheats get added to the game's cheat file, ie: --cheat "Infinite HP" "800A1234 03E7"
+    if let Some(index) = args.iter().position(|arg| arg == "--cheat")
+        && let (Some(name), Some(codes)) = (args.get(index + 1), args.get(index + 2))
+    {
+        match cpu.bus.cheats.add_cheat(name, codes) {
+            Ok(_) => Frontend::save_cheats(&cpu),
+            Err(e) => log!(Cpu, Warn, "couldn't add cheat: {e}"),
+        }
+    }
+
     cpu.bus
         .peripherals
         .memory_card
@@ -66,6 +281,9 @@ fn main() {
 
     let mut frontend = Frontend::new(&cpu.bus.gpu);
 
+    frontend.coverage_path = coverage_path;
+    frontend.profile_path = profile_path;
+
     #[cfg(feature = "hardware_gpu_metal")]
     frontend.renderer.metal_layer.setDrawableSize(CGSize::new(
         cpu.bus.gpu.display_width as f64,
@@ -73,19 +291,40 @@ fn main() {
     ));
 
     loop {
-        while !cpu.bus.gpu.frame_finished {
-            cpu.step();
-            #[cfg(feature = "hardware_gpu_metal")]
-            frontend.renderer.process(&mut cpu.bus.gpu);
-            #[cfg(feature = "hardware_gpu_opengl")]
-            frontend.renderer.process(&mut cpu.bus.gpu);
+        cpu.bus.perf.begin(Subsystem::Cpu);
+
+        // a panic in the core gets written to a crash bundle rather than just unwinding
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            while !cpu.bus.gpu.frame_finished {
+                cpu.step();
+                cpu.bus.perf.begin(Subsystem::Renderer);
+                #[cfg(feature = "hardware_gpu_metal")]
+                frontend.renderer.process(&mut cpu.bus.gpu);
+                #[cfg(feature = "hardware_gpu_opengl")]
+                frontend.renderer.process(&mut cpu.bus.gpu);
+                cpu.bus.perf.end();
+
+                // keep the window responsive while stopped in the debugger
+                if cpu.debugger.is_halted() {
+                    break;
+                }
+            }
+        }));
+
+        if result.is_err() {
+            Frontend::write_crash_bundle(&mut cpu);
+            process::exit(1);
         }
 
+        cpu.bus.perf.end();
+
         cpu.bus.gpu.frame_finished = false;
 
         #[cfg(feature = "software_gpu")]
         cpu.bus.gpu.cap_fps();
 
+        cpu.bus.perf.begin(Subsystem::Renderer);
+
         #[cfg(feature = "hardware_gpu_metal")]
         frontend.renderer.present(&mut cpu.bus.gpu);
         #[cfg(feature = "hardware_gpu_opengl")]
@@ -96,11 +335,28 @@ fn main() {
         #[cfg(feature = "software_gpu")]
         frontend.render(&mut cpu.bus.gpu);
 
+        cpu.bus.perf.end();
+
         #[cfg(any(feature = "hardware_gpu_metal", feature = "hardware_gpu_opengl"))]
         cpu.bus.gpu.cap_fps();
 
         frontend.handle_events(&mut cpu);
         frontend.check_controller_status();
         frontend.push_samples(cpu.bus.spu.audio_buffer.drain(..).collect());
+
+        if let Some(code) = cpu.bus.exit_code() {
+            println!("exited with code {code}");
+            frontend.exit(&mut cpu, code);
+        }
+
+        #[cfg(feature = "perf_counters")]
+        frontend.update_perf_overlay(&mut cpu);
+
+        if let Some(watcher) = &mut exe_watcher
+            && let Some(exe_bytes) = watcher.poll()
+            && let Err(e) = cpu.reload_exe(exe_bytes, watcher.keep_ram)
+        {
+            log!(Cpu, Warn, "couldn't reload exe: {e}");
+        }
     }
 }
@@ -4,17 +4,51 @@ use std::{
     ptr::{read_unaligned, write_unaligned},
 };
 
+use crate::log;
 use bus::{Bus, scheduler::EventType};
 use cop0::{COP0, CauseRegister, StatusRegister};
+use coverage::Coverage;
+use crash::RecentInstructions;
+use debugger::{AccessKind, Debugger, StopReason};
+use exe::{ExeError, Executable};
+#[cfg(not(target_arch = "wasm32"))]
+use gdb::GdbServer;
 use gte::Gte;
 use instructions::Instruction;
+use logger::{Channel, Level};
+#[cfg(feature = "debug")]
+use origin::OriginTracker;
+#[cfg(not(target_arch = "wasm32"))]
+use pcdrv::PcDrv;
+use perf::Subsystem;
+use profiler::Profiler;
 use serde::{Deserialize, Serialize};
+use symbols::SymbolTable;
+use trace::TraceRecorder;
+use watch::WatchList;
 
 pub mod bus;
 pub mod cop0;
+pub mod coverage;
+pub mod crash;
+pub mod debugger;
 pub mod disassembler;
+pub mod exe;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gdb;
 pub mod gte;
+pub mod inspector;
 pub mod instructions;
+pub mod logger;
+#[cfg(feature = "debug")]
+pub mod origin;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pcdrv;
+pub mod perf;
+pub mod profiler;
+pub mod symbols;
+pub mod trace;
+pub mod watch;
 
 pub const RA_REGISTER: usize = 31;
 
@@ -22,43 +56,6 @@ pub const CPU_FREQUENCY: f64 = 33_868_800.0;
 
 const ICACHE_SIZE: usize = 0x1000;
 
-#[cfg(feature = "debug")]
-type OriginId = u32;
-#[cfg(feature = "debug")]
-const UNKNOWN_ORIGIN: OriginId = 0;
-
-#[cfg(feature = "debug")]
-#[derive(Clone, Copy, Debug)]
-pub enum OriginKind {
-    Unknown,
-
-    // register got immediate / constant-ish value
-    Imm,
-
-    // register loaded from RAM
-    Load { addr: u32, mem: OriginId },
-
-    // RAM written from register
-    Store { addr: u32, src: OriginId },
-
-    // register copied from another register
-    Copy { src: OriginId },
-
-    // ALU op
-    Alu { lhs: OriginId, rhs: OriginId },
-    // MMIORead
-    MmioRead { addr: u32 },
-}
-
-#[cfg(feature = "debug")]
-#[derive(Clone, Copy, Debug)]
-pub struct OriginNode {
-    pub pc: u32,
-    pub opcode: u32,
-    pub value: u32,
-    pub kind: OriginKind,
-}
-
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct Registers([u32; 32]);
 
@@ -165,30 +162,50 @@ pub struct CPU {
     cop0: COP0,
     gte: Gte,
     found: HashSet<u32>,
-    pub debug_on: bool,
     ignored_load_delay: Option<usize>,
     branch_taken: bool,
     in_delay_slot: bool,
-    output: String,
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub exe_bytes: Option<Vec<u8>>,
     should_transfer_load: bool,
+    debug_exception_pending: bool,
+    instruction_count: u64,
     isolated_cache: IsolatedCache,
     pub game_path: String,
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
-    #[cfg(feature = "debug")]
-    pub origins: Vec<OriginNode>,
+    pub watches: WatchList,
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
-    #[cfg(feature = "debug")]
-    pub reg_origin: [OriginId; 32],
-    // one origin per RAM byte
+    pub debugger: Debugger,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub symbols: SymbolTable,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    trace: Option<TraceRecorder>,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    coverage: Option<Coverage>,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    profiler: Option<Profiler>,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[cfg(not(target_arch = "wasm32"))]
+    gdb: Option<GdbServer>,
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     #[cfg(feature = "debug")]
-    pub ram_origin: Box<[OriginId]>,
+    pub origins: OriginTracker,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pcdrv: Option<PcDrv>,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    recent_instructions: RecentInstructions,
 }
 
 fn build_instructions() -> [fn(&mut CPU, Instruction) -> usize; 0x40] {
@@ -354,17 +371,6 @@ impl CPU {
         let instructions = build_instructions();
         let special_instructions = build_special_instructions();
 
-        #[cfg(feature = "debug")]
-        let mut origins = Vec::new();
-
-        #[cfg(feature = "debug")]
-        origins.push(OriginNode {
-            pc: 0,
-            opcode: 0,
-            value: 0,
-            kind: OriginKind::Unknown,
-        });
-
         Self {
             r: Registers([0; 32]),
             pc: 0xbfc00000,
@@ -378,22 +384,29 @@ impl CPU {
             delayed_load: None,
             cop0: COP0::new(),
             found: HashSet::new(),
-            debug_on: false,
             ignored_load_delay: None,
             in_delay_slot: false,
             branch_taken: false,
-            output: "".to_string(),
             gte: Gte::new(),
             exe_bytes: exe_bytes,
             should_transfer_load: false,
+            debug_exception_pending: false,
+            instruction_count: 0,
             isolated_cache: IsolatedCache::new(),
             game_path,
+            watches: WatchList::new(),
+            debugger: Debugger::new(),
+            symbols: SymbolTable::new(),
+            trace: None,
+            coverage: None,
+            profiler: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            gdb: None,
             #[cfg(feature = "debug")]
-            origins,
-            #[cfg(feature = "debug")]
-            reg_origin: [0; 32],
-            #[cfg(feature = "debug")]
-            ram_origin: vec![0; 0x200000].into_boxed_slice(),
+            origins: OriginTracker::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            pcdrv: None,
+            recent_instructions: RecentInstructions::new(),
         }
     }
 
@@ -424,7 +437,26 @@ impl CPU {
             return;
         }
 
+        if self.debugger.has_watchpoints() {
+            self.debugger.check_access(address, 1, AccessKind::Write);
+        }
+
+        if self.cop0.check_data_breakpoint(address, true) {
+            self.debug_exception_pending = true;
+        }
+
         self.bus.mem_write8(address, value);
+
+        if let Some(trace) = &mut self.trace {
+            trace.record_access(address, value as u32, 1, true);
+        }
+
+        self.watches
+            .on_write(&self.bus, address, 1, value as u32, self.previous_pc);
+
+        if !self.bus.dma_writes.is_empty() {
+            self.report_dma_writes();
+        }
     }
 
     pub fn store16(&mut self, address: u32, value: u16) {
@@ -433,7 +465,33 @@ impl CPU {
             return;
         }
 
+        if self.debugger.has_watchpoints() {
+            self.debugger.check_access(address, 2, AccessKind::Write);
+        }
+
+        if self.cop0.check_data_breakpoint(address, true) {
+            self.debug_exception_pending = true;
+        }
+
         self.bus.mem_write16(address, value);
+
+        if let Some(trace) = &mut self.trace {
+            trace.record_access(address, value as u32, 2, true);
+        }
+
+        self.watches
+            .on_write(&self.bus, address, 2, value as u32, self.previous_pc);
+
+        if !self.bus.dma_writes.is_empty() {
+            self.report_dma_writes();
+        }
+    }
+
+    // stores to the DMA and MDEC registers and scheduler events can start transfers into RAM
+    fn report_dma_writes(&mut self) {
+        for write in std::mem::take(&mut self.bus.dma_writes) {
+            self.watches.on_dma_write(&self.bus, write);
+        }
     }
 
     fn transfer_load(&mut self) {
@@ -457,77 +515,234 @@ impl CPU {
             return;
         }
 
+        if self.debugger.has_watchpoints() {
+            self.debugger.check_access(address, 4, AccessKind::Write);
+        }
+
+        if self.cop0.check_data_breakpoint(address, true) {
+            self.debug_exception_pending = true;
+        }
+
         self.bus.mem_write32(address, value);
+
+        if let Some(trace) = &mut self.trace {
+            trace.record_access(address, value, 4, true);
+        }
+
+        self.watches
+            .on_write(&self.bus, address, 4, value, self.previous_pc);
+
+        if !self.bus.dma_writes.is_empty() {
+            self.report_dma_writes();
+        }
     }
 
     pub fn load8(&mut self, address: u32) -> u32 {
         if self.cop0.sr.contains(StatusRegister::ISOLATE_CACHE) {
             return self.isolated_cache.load8(address);
         }
-        self.bus.mem_read8(address)
+
+        if self.debugger.has_watchpoints() {
+            self.debugger.check_access(address, 1, AccessKind::Read);
+        }
+
+        if self.cop0.check_data_breakpoint(address, false) {
+            self.debug_exception_pending = true;
+        }
+
+        let value = self.bus.mem_read8(address);
+
+        if let Some(trace) = &mut self.trace {
+            trace.record_access(address, value, 1, false);
+        }
+
+        value
     }
 
     pub fn load32(&mut self, address: u32) -> u32 {
         if self.cop0.sr.contains(StatusRegister::ISOLATE_CACHE) {
             return self.isolated_cache.load32(address);
         }
-        self.bus.mem_read32(address)
+
+        if self.debugger.has_watchpoints() {
+            self.debugger.check_access(address, 4, AccessKind::Read);
+        }
+
+        if self.cop0.check_data_breakpoint(address, false) {
+            self.debug_exception_pending = true;
+        }
+
+        let value = self.bus.mem_read32(address);
+
+        if let Some(trace) = &mut self.trace {
+            trace.record_access(address, value, 4, false);
+        }
+
+        value
     }
 
     pub fn load16(&mut self, address: u32) -> u32 {
         if self.cop0.sr.contains(StatusRegister::ISOLATE_CACHE) {
             return self.isolated_cache.load16(address);
         }
-        self.bus.mem_read16(address)
+
+        if self.debugger.has_watchpoints() {
+            self.debugger.check_access(address, 2, AccessKind::Read);
+        }
+
+        if self.cop0.check_data_breakpoint(address, false) {
+            self.debug_exception_pending = true;
+        }
+
+        let value = self.bus.mem_read16(address);
+
+        if let Some(trace) = &mut self.trace {
+            trace.record_access(address, value, 2, false);
+        }
+
+        value
     }
-    pub fn step_frame(&mut self) {
+    // returns early with the reason if the debugger stops execution partway through the frame
+    pub fn step_frame(&mut self) -> Option<StopReason> {
+        self.bus.perf.begin(Subsystem::Cpu);
+
         while !self.bus.gpu.frame_finished {
             self.step();
+
+            if self.debugger.is_halted() {
+                self.bus.perf.end();
+                return self.debugger.stop_reason();
+            }
         }
 
+        self.bus.perf.end();
+
         self.bus.gpu.frame_finished = false;
+
+        None
     }
 
-    pub fn load_exe(&mut self, bytes: &[u8]) {
-        let mut index = 0x10;
+    // number of steps run since power on, saved along with the rest of the state
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
 
-        self.pc = unsafe { *(&bytes[index] as *const u8 as *const u32) };
-        self.next_pc = self.pc + 4;
+    // accepts PS-X EXE, ELF and CPE files
+    pub fn load_exe(&mut self, bytes: &[u8]) -> Result<(), ExeError> {
+        let exe = Executable::parse(bytes)?;
 
-        index += 4;
+        self.pc = exe.entry;
+        self.next_pc = self.pc.wrapping_add(4);
 
-        self.r[28] = unsafe { *(&bytes[index] as *const u8 as *const u32) };
+        if let Some(gp) = exe.gp {
+            self.r[28] = gp;
+        }
 
-        index += 4;
+        if let Some(stack) = exe.stack {
+            self.r[29] = stack;
+            self.r[30] = stack;
+        }
 
-        let file_dest = unsafe { *(&bytes[index] as *const u8 as *const u32) };
+        for segment in exe.segments {
+            for (i, byte) in segment.data.into_iter().enumerate() {
+                self.bus.main_ram[(segment.address.wrapping_add(i as u32) & 0x1f_ffff) as usize] =
+                    byte;
+            }
+        }
 
-        index += 4;
+        Ok(())
+    }
 
-        let file_size = unsafe { *(&bytes[index] as *const u8 as *const u32) };
+    // power cycles the console, the BIOS, disc, cartridge, memory card and exe stay inserted
+    pub fn reset(&mut self) {
+        let mut cpu = CPU::new(self.exe_bytes.take(), std::mem::take(&mut self.game_path));
 
-        index += 0x10 + 4;
+        self.swap_frontend_state(&mut cpu);
 
-        let sp_base = unsafe { *(&bytes[index] as *const u8 as *const u32) };
+        cpu.bus.load_bios(self.bus.get_bios());
+        cpu.bus.cdrom.swap_media(&mut self.bus.cdrom);
+        cpu.bus
+            .peripherals
+            .memory_card
+            .swap_memory_file(&mut self.bus.peripherals.memory_card);
+        std::mem::swap(&mut cpu.bus.cartridge, &mut self.bus.cartridge);
+        std::mem::swap(&mut cpu.bus.tty, &mut self.bus.tty);
 
-        index += 4;
+        cpu.debugger.clear_history();
+        #[cfg(feature = "debug")]
+        cpu.origins.clear();
+
+        *self = cpu;
+    }
+
+    /*
+      Swaps in a new build of the exe that's running. Normally the console gets reset and the exe
+      is sideloaded again once the BIOS is done booting. With keep_ram, the new exe is copied over
+      the old one and started straight away instead, so anything else in RAM (and the kernel and
+      hardware state) carries over from the previous build.
+    */
+    pub fn reload_exe(&mut self, exe_bytes: Vec<u8>, keep_ram: bool) -> Result<(), ExeError> {
+        Executable::parse(&exe_bytes)?;
 
-        if sp_base != 0 {
-            let sp_offset = unsafe { *(&bytes[index] as *const u8 as *const u32) };
+        if keep_ram {
+            self.load_exe(&exe_bytes)?;
 
-            self.r[29] = sp_base + sp_offset;
-            self.r[30] = self.r[29];
+            self.delayed_load = None;
+            self.ignored_load_delay = None;
+            self.branch_taken = false;
+            self.in_delay_slot = false;
+
+            self.exe_bytes = Some(exe_bytes);
+
+            self.debugger.clear_history();
+            #[cfg(feature = "debug")]
+            self.origins.clear();
+        } else {
+            self.exe_bytes = Some(exe_bytes);
+            self.reset();
         }
 
-        index = 0x800;
+        self.bus.tty.write_marker("exe reloaded");
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_gdb_server(&mut self, port: u16) -> std::io::Result<()> {
+        self.gdb = Some(GdbServer::listen(port)?);
+
+        // wait for gdb to attach before running anything
+        self.debugger.halt(StopReason::Interrupted);
+
+        Ok(())
+    }
 
-        for i in 0..file_size {
-            self.bus.main_ram[((file_dest + i) & 0x1f_ffff) as usize] = bytes[index];
-            index += 1;
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_gdb(&mut self) {
+        if let Some(mut gdb) = self.gdb.take() {
+            gdb.poll(self);
+            self.gdb = Some(gdb);
         }
     }
 
     pub fn step(&mut self) {
+        let should_stop = self.check_debugger();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.poll_gdb();
+
+        if should_stop || self.debugger.is_halted() {
+            return;
+        }
+
+        self.record_history();
+
+        self.execute();
+    }
+
+    fn execute(&mut self) {
+        self.instruction_count += 1;
+
         self.r[0] = 0;
 
         self.handle_interrupts();
@@ -553,10 +768,11 @@ impl CPU {
             return;
         }
 
-        if self.pc == 0x80030000 {
-            if let Some(exe_bytes) = self.exe_bytes.clone() {
-                self.load_exe(&exe_bytes);
-            }
+        if self.pc == 0x80030000
+            && let Some(exe_bytes) = self.exe_bytes.clone()
+            && let Err(e) = self.load_exe(&exe_bytes)
+        {
+            log!(Cpu, Warn, "couldn't load exe: {e}");
         }
 
         self.previous_pc = self.pc;
@@ -567,7 +783,11 @@ impl CPU {
             self.enter_exception(ExceptionType::Interrupt);
 
             if (opcode >> 25) == 0x25 {
+                self.bus.perf.begin(Subsystem::Gte);
                 let cycles = self.gte.execute_command(Instruction(opcode));
+                self.bus.perf.add_cycles(Subsystem::Gte, cycles as u64);
+                self.bus.perf.end();
+
                 self.bus.tick(cycles);
             }
 
@@ -582,24 +802,59 @@ impl CPU {
             return;
         }
 
+        // execution breakpoints fire before the instruction runs
+        if self.cop0.check_execution_breakpoint(self.pc) {
+            self.enter_debug_exception();
+
+            if self.should_transfer_load {
+                self.transfer_load();
+            }
+
+            self.should_transfer_load = false;
+
+            return;
+        }
+
         self.update_tty();
 
         self.pc = self.next_pc;
 
-        if !self.found.contains(&self.previous_pc) && self.debug_on {
-            println!(
+        // every instruction gets logged the first time it runs
+        if logger::enabled(Channel::Cpu, Level::Trace) && self.found.insert(self.previous_pc) {
+            log!(
+                Cpu,
+                Trace,
                 "[Opcode: 0x{:x}] [PC: 0x{:x}] {}",
                 opcode,
                 self.previous_pc,
                 self.disassemble(opcode)
             );
-            self.found.insert(self.previous_pc);
         }
 
         self.next_pc += 4;
 
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(self.previous_pc, Instruction(opcode));
+        }
+
+        self.record_recent_instruction(opcode);
+
+        if self.trace.is_some() {
+            self.trace_begin(opcode);
+        }
+
         let cycles = self.decode_opcode(opcode);
 
+        if self.profiler.is_some() {
+            self.profile_instruction(Instruction(opcode));
+        }
+
+        // data breakpoints fire once the load or store has gone through
+        if self.debug_exception_pending {
+            self.debug_exception_pending = false;
+            self.enter_debug_exception();
+        }
+
         self.bus.tick(cycles);
 
         self.handle_events();
@@ -609,10 +864,22 @@ impl CPU {
         }
 
         self.should_transfer_load = false;
+
+        if self.trace.is_some() {
+            self.trace_end();
+        }
     }
 
     fn handle_events(&mut self) {
         while let Some((event, cycles_left)) = self.bus.scheduler.get_next_event() {
+            self.bus.perf.begin(match event {
+                EventType::Vblank | EventType::HblankStart | EventType::HblankEnd => Subsystem::Gpu,
+                EventType::DmaFinished(_) | EventType::UnhaltDma(_) => Subsystem::Dma,
+                EventType::TickCDRom => Subsystem::Cdrom,
+                EventType::TickSpu => Subsystem::Spu,
+                EventType::ControllerByteTransfer => Subsystem::Events,
+            });
+
             match event {
                 EventType::Vblank => self.bus.gpu.handle_vblank(
                     &mut self.bus.scheduler,
@@ -624,12 +891,18 @@ impl CPU {
                     &mut self.bus.timers,
                     cycles_left,
                 ),
-                EventType::HblankEnd => self.bus.gpu.handle_hblank(
-                    &mut self.bus.scheduler,
-                    &mut self.bus.interrupt_stat,
-                    &mut self.bus.timers,
-                    cycles_left,
-                ),
+                EventType::HblankEnd => {
+                    if self.bus.gpu.handle_hblank(
+                        &mut self.bus.scheduler,
+                        &mut self.bus.interrupt_stat,
+                        &mut self.bus.timers,
+                        cycles_left,
+                    ) {
+                        self.bus.cheats.apply(&mut self.bus.main_ram);
+                        self.watches.update_frame(&mut self.bus);
+                        self.debugger.start_frame();
+                    }
+                }
                 EventType::DmaFinished(channel) => self
                     .bus
                     .dma
@@ -648,26 +921,20 @@ impl CPU {
                     .bus
                     .peripherals
                     .handle_peripherals(&mut self.bus.interrupt_stat, &mut self.bus.scheduler),
-                EventType::UnhaltDma(channel) => self.bus.unhalt_dma(channel),
+                EventType::UnhaltDma(channel) => {
+                    self.bus.unhalt_dma(channel);
+                    self.report_dma_writes();
+                }
             }
+
+            self.bus.perf.end();
         }
     }
 
     fn update_tty(&mut self) {
-        if self.pc == 0xb0 && self.r[9] == 0x3d {
-            let buf = vec![
-                self.r[4] as u8,
-                (self.r[4] >> 8) as u8,
-                (self.r[4] >> 16) as u8,
-                (self.r[4] >> 24) as u8,
-            ];
-
-            self.output += &String::from_utf8(buf).unwrap();
-
-            if self.output.contains("\n") {
-                print!("{}", self.output);
-                self.output = "".to_string();
-            }
+        // once the DUART transmitter is enabled, putchar output reaches the TTY through it instead
+        if self.pc == 0xb0 && self.r[9] == 0x3d && !self.bus.duart.tx_enabled() {
+            self.bus.tty.write_byte(self.r[4] as u8);
         }
     }
 
@@ -709,6 +976,22 @@ impl CPU {
             0x80000080
         };
         self.next_pc = self.pc + 4;
+
+        if self.profiler.is_some() {
+            self.profile_exception();
+        }
+    }
+
+    // COP0 breakpoints use the same exception code as the BREAK opcode, but their own vector
+    fn enter_debug_exception(&mut self) {
+        self.enter_exception(ExceptionType::Break);
+
+        self.pc = if self.cop0.sr.contains(StatusRegister::BEV) {
+            0xbfc00140
+        } else {
+            0x80000040
+        };
+        self.next_pc = self.pc + 4;
     }
 
     pub fn create_save_state(&self) -> (Vec<u8>, usize) {
@@ -719,62 +1002,49 @@ impl CPU {
         (data, len)
     }
 
-    pub fn load_save_state(&mut self, data: &[u8]) {
-        if let Ok((cpu, _)) = bincode::serde::decode_from_slice(data, bincode::config::standard()) {
-            *self = cpu;
-        }
-    }
-
-    #[cfg(feature = "debug")]
-    pub fn add_origin(&mut self, node: OriginNode) -> OriginId {
-        let id = self.origins.len() as OriginId;
-        self.origins.push(node);
-        id
-    }
-
-    #[cfg(feature = "debug")]
-    pub fn dump_origin(&self, id: OriginId, depth: usize) {
-        if id == UNKNOWN_ORIGIN {
-            println!("{:indent$}UNKNOWN", "", indent = depth * 2);
-            return;
-        }
-
-        let node = &self.origins[id as usize];
-
-        println!(
-            "{:indent$}id={} pc={:08x} value={:08x} kind={:x?}",
-            "",
-            id,
-            node.pc,
-            node.value,
-            node.kind,
-            indent = depth * 2
-        );
+    // returns false if the state couldn't be decoded, which leaves everything as it was
+    pub fn load_save_state(&mut self, data: &[u8]) -> bool {
+        let Some(mut cpu) = self.decode_save_state(data) else {
+            return false;
+        };
 
-        match node.kind {
-            OriginKind::Load { mem, .. } => {
-                self.dump_origin(mem, depth + 1);
-            }
+        // the snapshots and origins belong to a different timeline now
+        cpu.debugger.clear_history();
+        #[cfg(feature = "debug")]
+        cpu.origins.clear();
 
-            OriginKind::Store { src, .. } => {
-                self.dump_origin(src, depth + 1);
-            }
+        *self = cpu;
 
-            OriginKind::Copy { src } => {
-                self.dump_origin(src, depth + 1);
-            }
+        true
+    }
 
-            OriginKind::Alu { lhs, rhs } => {
-                self.dump_origin(lhs, depth + 1);
-                self.dump_origin(rhs, depth + 1);
-            }
+    fn decode_save_state(&mut self, data: &[u8]) -> Option<CPU> {
+        if let Ok((mut cpu, _)) =
+            bincode::serde::decode_from_slice::<CPU, _>(data, bincode::config::standard())
+        {
+            self.swap_frontend_state(&mut cpu);
 
-            _ => {}
+            return Some(cpu);
         }
+
+        None
     }
-}
 
-#[cfg(feature = "debug")]
-fn ram_off(addr: u32) -> usize {
-    (addr & 0x1f_ffff) as usize
+    // cheats, watches, symbols, traces, coverage, the profiler, perf counters, origin tracking, PCdrv and the debugger are frontend settings rather than console state, so they survive loading a state or a reset
+    fn swap_frontend_state(&mut self, cpu: &mut CPU) {
+        std::mem::swap(&mut cpu.bus.cheats, &mut self.bus.cheats);
+        std::mem::swap(&mut cpu.bus.perf, &mut self.bus.perf);
+        std::mem::swap(&mut cpu.watches, &mut self.watches);
+        std::mem::swap(&mut cpu.debugger, &mut self.debugger);
+        std::mem::swap(&mut cpu.symbols, &mut self.symbols);
+        std::mem::swap(&mut cpu.trace, &mut self.trace);
+        std::mem::swap(&mut cpu.coverage, &mut self.coverage);
+        std::mem::swap(&mut cpu.profiler, &mut self.profiler);
+        #[cfg(not(target_arch = "wasm32"))]
+        std::mem::swap(&mut cpu.gdb, &mut self.gdb);
+        #[cfg(feature = "debug")]
+        std::mem::swap(&mut cpu.origins, &mut self.origins);
+        #[cfg(not(target_arch = "wasm32"))]
+        std::mem::swap(&mut cpu.pcdrv, &mut self.pcdrv);
+    }
 }
@@ -1,5 +1,8 @@
+use cartridge::Cartridge;
 use cdrom::CDRom;
+use cheats::CheatEngine;
 use dma::dma_channel::Dma;
+use duart::Duart;
 use gpu::GPU;
 use mdec::Mdec;
 use registers::{delay_register::DelayRegister, interrupt_register::InterruptRegister};
@@ -7,26 +10,38 @@ use scheduler::Scheduler;
 use serde::{Deserialize, Serialize};
 use spu::SPU;
 use timer::Timer;
+use tty::Tty;
 
-use crate::cpu::bus::{
-    dma::{
-        dma_channel::{DMA_CDROM, DMA_GPU, DMA_MDEC_IN, DMA_MDEC_OUT, DMA_OTC, DMA_PIO, DMA_SPU},
-        dma_channel_control_register::SyncMode,
+use crate::cpu::{
+    bus::{
+        dma::{
+            dma_channel::{
+                DMA_CDROM, DMA_GPU, DMA_MDEC_IN, DMA_MDEC_OUT, DMA_OTC, DMA_PIO, DMA_SPU, DmaWrite,
+            },
+            dma_channel_control_register::{DmaChannelControlRegister, SyncMode},
+        },
+        peripherals::Peripherals,
+        scheduler::EventType,
+        timer::ClockSource,
     },
-    peripherals::Peripherals,
-    scheduler::EventType,
-    timer::ClockSource,
+    perf::{PerfCounters, Subsystem},
 };
+use crate::log;
 
+pub mod cartridge;
 pub mod cdrom;
+pub mod cheats;
 pub mod dma;
+pub mod duart;
 pub mod gpu;
 pub mod mdec;
 pub mod peripherals;
+pub mod ram_search;
 pub mod registers;
 pub mod scheduler;
 pub mod spu;
 pub mod timer;
+pub mod tty;
 
 #[derive(Serialize, Deserialize)]
 pub struct Bus {
@@ -44,9 +59,9 @@ pub struct Bus {
     exp2_delay: DelayRegister,
     cache_config: u32,
     pub(crate) main_ram: Box<[u8]>,
-    scratchpad: Box<[u8]>,
+    pub(crate) scratchpad: Box<[u8]>,
     pub spu: SPU,
-    exp1_post: u8,
+    post_code: u8,
     pub interrupt_mask: InterruptRegister,
     pub interrupt_stat: InterruptRegister,
     pub timers: [Timer; 3],
@@ -56,6 +71,22 @@ pub struct Bus {
     pub cdrom: CDRom,
     pub mdec: Mdec,
     pub peripherals: Peripherals,
+    pub cartridge: Cartridge,
+    pub duart: Duart,
+    pub tty: Tty,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub cheats: CheatEngine,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub perf: PerfCounters,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    exit_code: Option<i32>,
+    // filled in by transfers into main RAM and drained by the CPU after every store and event
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub dma_writes: Vec<DmaWrite>,
 }
 
 impl Default for Bus {
@@ -83,7 +114,7 @@ impl Bus {
             cache_config: 0,
             main_ram: vec![0; 0x200000].into_boxed_slice(),
             spu: SPU::new(&mut scheduler),
-            exp1_post: 0,
+            post_code: 0,
             interrupt_mask: InterruptRegister::from_bits_truncate(0),
             interrupt_stat: InterruptRegister::from_bits_truncate(0),
             timers: [Timer::new(0), Timer::new(1), Timer::new(2)],
@@ -94,6 +125,13 @@ impl Bus {
             mdec: Mdec::new(),
             scratchpad: vec![0; 0x400].into_boxed_slice(),
             peripherals: Peripherals::new(),
+            cartridge: Cartridge::new(),
+            duart: Duart::new(),
+            tty: Tty::new(),
+            cheats: CheatEngine::new(),
+            perf: PerfCounters::new(),
+            exit_code: None,
+            dma_writes: Vec::new(),
         }
     }
 
@@ -125,6 +163,136 @@ impl Bus {
         self.bios = bios;
     }
 
+    // returns the offset into the expansion 1 window, if the address falls inside of it
+    fn exp1_offset(&self, address: usize) -> Option<usize> {
+        let offset = address.wrapping_sub(self.exp1_base_address as usize);
+
+        // window size is 1 << number of address bits (delay register bits 16-20)
+        if offset < 1 << self.exp1_delay.num_addr_bits {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    fn read_exp1(&self, address: usize) -> u8 {
+        match self.exp1_offset(address) {
+            Some(offset) if self.cartridge.is_loaded() => self.cartridge.read8(offset),
+            _ => 0,
+        }
+    }
+
+    fn write_exp1(&mut self, address: usize, value: u8) {
+        if let Some(offset) = self.exp1_offset(address) {
+            self.cartridge.write8(offset, value);
+        }
+    }
+
+    // value last written to the POST register (1F802041h), shown on the
+    // 7-segment display of dev boards and some debug units
+    pub fn post_code(&self) -> u8 {
+        self.post_code
+    }
+
+    // the digit the 7-segment display shows for the current POST code
+    pub fn post_display(&self) -> char {
+        char::from_digit((self.post_code & 0xf) as u32, 16)
+            .unwrap()
+            .to_ascii_uppercase()
+    }
+
+    /*
+      Test ROMs report their result through the same registers as on pcsx-redux: a 32-bit read of
+      1F802080h returns "PCSX" so they can tell they're running on an emulator, bytes written to
+      1F802080h go to the TTY and writing the exit code to 1F802082h ends the run. What happens
+      then is up to the frontend, see Bus::exit_code.
+    */
+    fn read_exp2(&mut self, address: usize) -> u8 {
+        if !self.exp2_enabled {
+            return 0xff;
+        }
+
+        match address {
+            0x1f802020..=0x1f80202f => self.duart.read(address - 0x1f802020),
+            0x1f802080..=0x1f802083 => b"PCSX"[address - 0x1f802080],
+            _ => 0xff,
+        }
+    }
+
+    fn write_exp2(&mut self, address: usize, value: u8) {
+        // the POST register was always written, whether or not the rest of the region is mapped
+        if address == 0x1f802041 {
+            self.post_code = value;
+            return;
+        }
+
+        if !self.exp2_enabled {
+            return;
+        }
+
+        match address {
+            0x1f802020..=0x1f80202f => self.duart.write(address - 0x1f802020, value, &mut self.tty),
+            0x1f802080 => self.tty.write_byte(value),
+            _ => (),
+        }
+    }
+
+    fn exit(&mut self, code: i32) {
+        self.tty.write_marker(&format!("exited with code {code}"));
+
+        self.exit_code = Some(code);
+    }
+
+    // the exit code the running program reported, if it did, which stays set until the next reset
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    // reads memory without ticking or touching any device state, so it returns None for I/O ports
+    pub fn peek8(&self, address: u32) -> Option<u8> {
+        let address = Self::translate_address(address);
+
+        match address {
+            0x00000000..=0x007f_ffff => Some(self.main_ram[address & 0x1f_ffff]),
+            0x1f000000..=0x1f7fffff => Some(self.read_exp1(address)),
+            0x1f800000..=0x1f8003ff => Some(self.scratchpad[address - 0x1f800000]),
+            0x1fc00000..=0x1fc7ffff => self.bios.get(address - 0x1fc00000).copied(),
+            _ => None,
+        }
+    }
+
+    // writes RAM or the scratchpad without ticking or side effects, returns false for anything else
+    pub fn poke8(&mut self, address: u32, value: u8) -> bool {
+        let address = Self::translate_address(address);
+
+        match address {
+            0x00000000..=0x007f_ffff => self.main_ram[address & 0x1f_ffff] = value,
+            0x1f800000..=0x1f8003ff => self.scratchpad[address - 0x1f800000] = value,
+            _ => return false,
+        }
+
+        true
+    }
+
+    // whether an address is in RAM or the scratchpad, the memory poke8 writes to
+    pub fn is_memory(address: u32) -> bool {
+        matches!(
+            Self::translate_address(address),
+            0x00000000..=0x007f_ffff | 0x1f800000..=0x1f8003ff
+        )
+    }
+
+    // folds the segment and RAM mirrors so that every alias of an address compares equal
+    pub fn physical_address(address: u32) -> u32 {
+        let address = Self::translate_address(address) as u32;
+
+        if address < 0x80_0000 {
+            address & 0x1f_ffff
+        } else {
+            address
+        }
+    }
+
     pub fn translate_address(address: u32) -> usize {
         match address >> 28 {
             0x8 | 0xa => (address & 0xfff_ffff) as usize,
@@ -165,6 +333,15 @@ impl Bus {
                 self.tick(5);
                 self.mdec.read(address)
             }
+            0x1f000000..=0x1f7fffff => (0..4).fold(0, |acc, i| {
+                acc | (self.read_exp1(address + i) as u32) << (i * 8)
+            }),
+            0x1f802000..=0x1f803fff => {
+                self.tick(5);
+                (0..4).fold(0, |acc, i| {
+                    acc | (self.read_exp2(address + i) as u32) << (i * 8)
+                })
+            }
             0x1fc00000..=0x1fc80000 => {
                 if (self.cache_config >> 11) & 1 == 0 {
                     self.tick(4);
@@ -218,6 +395,13 @@ impl Bus {
                 self.tick(5);
                 self.spu.read16(address) as u32
             }
+            0x1f000000..=0x1f7fffff => {
+                self.read_exp1(address) as u32 | (self.read_exp1(address + 1) as u32) << 8
+            }
+            0x1f802000..=0x1f803fff => {
+                self.tick(5);
+                self.read_exp2(address) as u32 | (self.read_exp2(address + 1) as u32) << 8
+            }
             0x1fc00000..=0x1fc80000 => {
                 if (self.cache_config >> 11) & 1 == 0 {
                     self.tick(4);
@@ -261,7 +445,11 @@ impl Bus {
                     ((value >> 8) & 0xff) as u32
                 }
             }
-            0x1f000000..=0x1f02ffff => 0, // expansion 1 I/O, not needed
+            0x1f000000..=0x1f7fffff => self.read_exp1(address) as u32,
+            0x1f802000..=0x1f803fff => {
+                self.tick(5);
+                self.read_exp2(address) as u32
+            }
             0x1fc00000..=0x1fc80000 => {
                 if (self.cache_config >> 1) & 1 == 0 {
                     self.tick(4);
@@ -307,9 +495,14 @@ impl Bus {
             0x1f801080..=0x1f8010f4 => self.write_dma_registers(address, value),
             0x1f801114 => self.timers[1].write_counter_register(value as u16),
             0x1f801118 => self.timers[1].counter_target = value as u16,
-            0x1f801810 => self.gpu.process_gp0_commands(value),
+            0x1f801810 => {
+                self.perf.begin(Subsystem::Gpu);
+                self.gpu.process_gp0_commands(value);
+                self.perf.end();
+            }
             0x1f801814 => self.gpu.process_gp1_commands(value),
             0x1f801820..=0x1f801824 => {
+                let mdec_out_address = self.dma.channels[DMA_MDEC_OUT].base_address;
                 let mdec_dma = self.mdec.write(address, value);
 
                 self.dma.set_request(
@@ -329,6 +522,19 @@ impl Bus {
                     &mut self.scheduler,
                     &mut self.interrupt_stat,
                 );
+
+                self.record_mdec_out_writes(mdec_out_address);
+            }
+            0x1f000000..=0x1f7fffff => {
+                for i in 0..4 {
+                    self.write_exp1(address + i, (value >> (i * 8)) as u8);
+                }
+            }
+            0x1f802000..=0x1f803fff => {
+                self.tick(5);
+                for i in 0..4 {
+                    self.write_exp2(address + i, (value >> (i * 8)) as u8);
+                }
             }
             0xfffe0130 => {
                 self.cache_config = value;
@@ -350,87 +556,169 @@ impl Bus {
                 );
             }
             DMA_MDEC_OUT => {
+                let mdec_out_address = self.dma.channels[DMA_MDEC_OUT].base_address;
+
                 self.dma.start_mdec_out_transfer(
                     &mut self.main_ram,
                     &mut self.mdec,
                     &mut self.scheduler,
                     &mut self.interrupt_stat,
                 );
+
+                self.record_mdec_out_writes(mdec_out_address);
             }
-            _ => println!(
-                "[WARN]: got dma channel {channel}, currently unimplemented unhalting behavior for that channel"
+            _ => log!(
+                Dma,
+                Warn,
+                "got dma channel {channel}, currently unimplemented unhalting behavior for that channel"
             ),
         }
     }
 
+    // MDEC out keeps its running address in the channel, so everything between there and where it started got written
+    fn record_mdec_out_writes(&mut self, start: u32) {
+        let start = start & 0x1ffffc;
+        let end = self.dma.channels[DMA_MDEC_OUT].base_address & 0x1ffffc;
+
+        let (address, len) = if end >= start {
+            (start, end - start)
+        } else {
+            (end + 4, start - end)
+        };
+
+        if len > 0 {
+            self.dma_writes.push(DmaWrite {
+                channel: DMA_MDEC_OUT,
+                address,
+                len,
+            });
+        }
+    }
+
     fn write_dma_registers(&mut self, address: usize, value: u32) {
         // write_registers returns true if a dma transfer is started
         if self.dma.write_registers(address, value) {
-            // start dma transfer
             let channel = (address - 0x1f801080) / 0x10;
 
-            let dma_channel = &mut self.dma.channels[channel];
+            // GPU transfers are mostly the GPU processing commands
+            self.perf.begin(if channel == DMA_GPU {
+                Subsystem::Gpu
+            } else {
+                Subsystem::Dma
+            });
+
+            self.start_dma_transfer(channel);
 
-            let mut num_words = dma_channel.get_num_words();
+            self.perf.end();
+        }
+    }
+
+    fn start_dma_transfer(&mut self, channel: usize) {
+        let dma_channel = &mut self.dma.channels[channel];
 
-            let clocks = match channel {
-                0 | 1 | 2 | 6 => 1,
-                3 => 24,
-                4 => 4,
-                5 => 20,
-                _ => panic!("Unknown DMA channel"),
+        let mut num_words = dma_channel.get_num_words();
+
+        // MDEC out gets recorded once it's done, it can stop and pick up again halfway through
+        let writes_ram = match channel {
+            DMA_OTC | DMA_CDROM => true,
+            DMA_GPU => {
+                dma_channel.control.sync_mode() == SyncMode::Request
+                    && !dma_channel
+                        .control
+                        .contains(DmaChannelControlRegister::TRANSFER_DIR)
+            }
+            DMA_PIO => !dma_channel
+                .control
+                .contains(DmaChannelControlRegister::TRANSFER_DIR),
+            _ => false,
+        };
+
+        if writes_ram && num_words > 0 {
+            let address = dma_channel.base_address & 0x1ffffc;
+            let len = num_words * 4;
+
+            let address = if dma_channel
+                .control
+                .contains(DmaChannelControlRegister::DECREMENT)
+            {
+                (address + 4).wrapping_sub(len) & 0x1ffffc
+            } else {
+                address
             };
 
-            match channel {
-                DMA_GPU => match dma_channel.control.sync_mode() {
-                    SyncMode::LinkedList => {
-                        num_words =
-                            dma_channel.start_gpu_transfer(&mut self.main_ram, &mut self.gpu)
-                    }
-                    SyncMode::Manual | SyncMode::Request => {
-                        dma_channel.start_gpu_transfer(&mut self.main_ram, &mut self.gpu);
-                    }
-                },
-                DMA_CDROM => dma_channel.start_cdrom_transfer(&mut self.main_ram, &mut self.cdrom),
-                DMA_SPU => dma_channel.start_spu_transfer(
-                    &mut self.main_ram,
-                    &mut self.spu,
-                    &mut self.interrupt_stat,
-                ),
-                DMA_PIO => dma_channel.start_pio_transfer(),
-                DMA_OTC => dma_channel.start_otc_transfer(&mut self.main_ram),
-                DMA_MDEC_IN => {
-                    self.dma.start_mdec_in_transfer(
-                        &mut self.main_ram,
-                        &mut self.mdec,
-                        &mut self.scheduler,
-                        &mut self.interrupt_stat,
-                    );
-
-                    return;
+            self.dma_writes.push(DmaWrite {
+                channel,
+                address,
+                len,
+            });
+        }
+
+        let dma_channel = &mut self.dma.channels[channel];
+
+        let clocks = match channel {
+            0 | 1 | 2 | 6 => 1,
+            3 => 24,
+            4 => 4,
+            5 => 20,
+            _ => panic!("Unknown DMA channel"),
+        };
+
+        match channel {
+            DMA_GPU => match dma_channel.control.sync_mode() {
+                SyncMode::LinkedList => {
+                    num_words = dma_channel.start_gpu_transfer(&mut self.main_ram, &mut self.gpu)
                 }
-                DMA_MDEC_OUT => {
-                    self.dma.start_mdec_out_transfer(
-                        &mut self.main_ram,
-                        &mut self.mdec,
-                        &mut self.scheduler,
-                        &mut self.interrupt_stat,
-                    );
-
-                    return;
+                SyncMode::Manual | SyncMode::Request => {
+                    dma_channel.start_gpu_transfer(&mut self.main_ram, &mut self.gpu);
                 }
-                _ => todo!("dma transfer for channel {channel}"),
+            },
+            DMA_CDROM => dma_channel.start_cdrom_transfer(&mut self.main_ram, &mut self.cdrom),
+            DMA_SPU => dma_channel.start_spu_transfer(
+                &mut self.main_ram,
+                &mut self.spu,
+                &mut self.interrupt_stat,
+            ),
+            DMA_PIO => dma_channel.start_pio_transfer(
+                &mut self.main_ram,
+                &mut self.cartridge,
+                self.exp1_delay.auto_increment,
+            ),
+            DMA_OTC => dma_channel.start_otc_transfer(&mut self.main_ram),
+            DMA_MDEC_IN => {
+                self.dma.start_mdec_in_transfer(
+                    &mut self.main_ram,
+                    &mut self.mdec,
+                    &mut self.scheduler,
+                    &mut self.interrupt_stat,
+                );
+
+                return;
             }
+            DMA_MDEC_OUT => {
+                let mdec_out_address = dma_channel.base_address;
 
-            // only OTC finishes the transfer immediately
-            if channel != DMA_OTC {
-                self.scheduler.schedule(
-                    EventType::DmaFinished(channel),
-                    (num_words * clocks) as usize,
+                self.dma.start_mdec_out_transfer(
+                    &mut self.main_ram,
+                    &mut self.mdec,
+                    &mut self.scheduler,
+                    &mut self.interrupt_stat,
                 );
-            } else {
-                self.dma.finish_transfer(channel, &mut self.interrupt_stat);
+
+                self.record_mdec_out_writes(mdec_out_address);
+
+                return;
             }
+            _ => todo!("dma transfer for channel {channel}"),
+        }
+
+        // only OTC finishes the transfer immediately
+        if channel != DMA_OTC {
+            self.scheduler.schedule(
+                EventType::DmaFinished(channel),
+                (num_words * clocks) as usize,
+            );
+        } else {
+            self.dma.finish_transfer(channel, &mut self.interrupt_stat);
         }
     }
 
@@ -475,6 +763,20 @@ impl Bus {
             0x1f801124 => self.timers[2].write_counter_register(value),
             0x1f801128 => self.timers[2].counter_target = value,
             0x1f801c00..=0x1f801e7f => self.spu.write16(address, value, &mut self.interrupt_stat),
+            0x1f000000..=0x1f7fffff => {
+                self.write_exp1(address, value as u8);
+                self.write_exp1(address + 1, (value >> 8) as u8);
+            }
+            // pcsx-redux's exit takes a halfword, which would get cut down to a byte otherwise
+            0x1f802082 if self.exp2_enabled => {
+                self.tick(5);
+                self.exit(value as i16 as i32);
+            }
+            0x1f802000..=0x1f803fff => {
+                self.tick(5);
+                self.write_exp2(address, value as u8);
+                self.write_exp2(address + 1, (value >> 8) as u8);
+            }
             _ => todo!("(mem_write16) address: 0x{:x}", address),
         }
     }
@@ -500,11 +802,110 @@ impl Bus {
                 self.tick(5);
                 self.cdrom.write(address, value, &mut self.interrupt_stat);
             }
-            0x1f802041 => {
+            0x1f802082 if self.exp2_enabled => {
                 self.tick(5);
-                self.exp1_post = value;
+                self.exit(value as i32);
             }
+            0x1f802000..=0x1f803fff => {
+                self.tick(5);
+                self.write_exp2(address, value);
+            }
+            0x1f000000..=0x1f7fffff => self.write_exp1(address, value),
             _ => todo!("(mem_write8) address: 0x{:x}", address),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_the_post_register_even_when_exp2_is_unmapped() {
+        let mut bus = Bus::new();
+
+        bus.mem_write8(0x1f802041, 0x0c);
+
+        assert_eq!(bus.post_code(), 0x0c);
+        assert_eq!(bus.post_display(), 'C');
+
+        bus.mem_write32(0x1f801004, 0x1f80_0000);
+        bus.mem_write8(0x1f802041, 0x07);
+
+        assert_eq!(bus.post_code(), 0x07);
+    }
+
+    #[test]
+    fn routes_duart_channel_a_to_the_tty() {
+        let mut bus = Bus::new();
+
+        // transmitting needs to be enabled first
+        bus.mem_write8(0x1f802023, b'x');
+        assert_eq!(bus.mem_read8(0x1f802021) & 0xc, 0);
+
+        bus.mem_write8(0x1f802022, 0x05);
+        assert_eq!(bus.mem_read8(0x1f802021) & 0xc, 0xc);
+
+        for byte in b"hi\n" {
+            bus.mem_write8(0x1f802023, *byte);
+        }
+
+        // channel B isn't hooked up to anything
+        bus.mem_write8(0x1f80202a, 0x05);
+        bus.mem_write8(0x1f80202b, b'y');
+
+        assert_eq!(bus.tty.take_output(), "hi\n");
+
+        bus.duart.push_rx(0, b'a');
+        bus.duart.push_rx(0, b'b');
+
+        assert_eq!(bus.mem_read8(0x1f802021) & 0x1, 1);
+        assert_eq!(bus.mem_read8(0x1f802023), b'a' as u32);
+        assert_eq!(bus.mem_read8(0x1f802023), b'b' as u32);
+        assert_eq!(bus.mem_read8(0x1f802021) & 0x1, 0);
+    }
+
+    #[test]
+    fn reads_the_duart_mode_registers_in_order() {
+        let mut bus = Bus::new();
+
+        bus.mem_write8(0x1f802020, 0x13);
+        bus.mem_write8(0x1f802020, 0x07);
+
+        // resets the mode register pointer
+        bus.mem_write8(0x1f802022, 0x10);
+
+        assert_eq!(bus.mem_read8(0x1f802020), 0x13);
+        assert_eq!(bus.mem_read8(0x1f802020), 0x07);
+
+        // unmapping expansion 2 hides the DUART
+        bus.mem_write32(0x1f801004, 0x1f80_0000);
+
+        assert_eq!(bus.mem_read8(0x1f802020), 0xff);
+    }
+
+    #[test]
+    fn exits_on_byte_and_halfword_writes() {
+        let mut bus = Bus::new();
+
+        bus.mem_write8(0x1f802082, 3);
+        assert_eq!(bus.exit_code(), Some(3));
+
+        let mut bus = Bus::new();
+
+        bus.mem_write16(0x1f802082, 0xffff);
+        assert_eq!(bus.exit_code(), Some(-1));
+    }
+
+    #[test]
+    fn only_prints_on_word_writes_to_the_tty_port() {
+        let mut bus = Bus::new();
+
+        // the upper bytes overlap the exit port, which a word write shouldn't reach
+        bus.mem_write32(0x1f802080, 0x0002_0041);
+        bus.mem_write32(0x1f802080, 0x0002_000a);
+
+        assert_eq!(bus.exit_code(), None);
+        assert_eq!(bus.tty.take_output(), "A\n");
+    }
+}
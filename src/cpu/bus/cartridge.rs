@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+// Cartridges sit in the parallel port and get mapped to expansion region 1 (1F000000h).
+// The BIOS checks 1F000084h for the "Licensed by Sony Computer Entertainment Inc." string
+// and jumps to the pre-boot/post-boot entrypoints at 1F000080h/1F000000h if it's there.
+pub const CARTRIDGE_ROM_SIZE: usize = 0x40000;
+
+// see https://psx-spx.consoledev.net/cheatdevices/
+const ACTION_REPLAY_SWITCH: usize = 0x20018;
+const ACTION_REPLAY_LATCH: usize = 0x20010;
+const XPLORER_SWITCH: usize = 0x60000;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CartridgeType {
+    // Action Replay, GameShark, and most dev carts built on the same board (Caetla, Unirom)
+    ActionReplay,
+    Xplorer,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Cartridge {
+    rom: Vec<u8>,
+    pub cartridge_type: CartridgeType,
+    pub switch_on: bool,
+    latch: u8,
+    pio_address: usize,
+}
+
+impl Default for Cartridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cartridge {
+    pub fn new() -> Self {
+        Self {
+            rom: Vec::new(),
+            cartridge_type: CartridgeType::ActionReplay,
+            switch_on: false,
+            latch: 0,
+            pio_address: 0,
+        }
+    }
+
+    pub fn load_rom(&mut self, rom: Vec<u8>, cartridge_type: CartridgeType) {
+        self.rom = rom;
+        self.rom.truncate(CARTRIDGE_ROM_SIZE);
+        self.cartridge_type = cartridge_type;
+    }
+
+    pub fn unload(&mut self) {
+        self.rom = Vec::new();
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        !self.rom.is_empty()
+    }
+
+    pub fn toggle_switch(&mut self) {
+        self.switch_on = !self.switch_on;
+    }
+
+    // offset is relative to the expansion 1 base address
+    pub fn read8(&self, offset: usize) -> u8 {
+        // the switch is mapped over the ROM, so it has to win over a full size dump
+        match (self.cartridge_type, offset) {
+            (CartridgeType::ActionReplay, ACTION_REPLAY_SWITCH) => self.switch_on as u8,
+            (CartridgeType::Xplorer, XPLORER_SWITCH) => self.switch_on as u8,
+            _ => self.rom.get(offset).copied().unwrap_or(0xff),
+        }
+    }
+
+    pub fn write8(&mut self, offset: usize, value: u8) {
+        // writes to the ROM area are flash programming sequences, which aren't emulated.
+        if let (CartridgeType::ActionReplay, ACTION_REPLAY_LATCH) = (self.cartridge_type, offset) {
+            self.latch = value;
+        }
+    }
+
+    pub fn reset_pio(&mut self) {
+        self.pio_address = 0;
+    }
+
+    pub fn dma_read(&mut self, auto_increment: bool) -> u32 {
+        let value = (0..4).fold(0, |acc, i| {
+            acc | (self.read8(self.pio_address + i) as u32) << (i * 8)
+        });
+
+        if auto_increment {
+            self.pio_address += 4;
+        }
+
+        value
+    }
+
+    pub fn dma_write(&mut self, value: u32, auto_increment: bool) {
+        for i in 0..4 {
+            self.write8(self.pio_address + i, (value >> (i * 8)) as u8);
+        }
+
+        if auto_increment {
+            self.pio_address += 4;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_rom(cartridge_type: CartridgeType) -> Cartridge {
+        let mut cartridge = Cartridge::new();
+
+        // a full size dump, so the switch overlaps the ROM
+        let rom = (0..CARTRIDGE_ROM_SIZE)
+            .map(|i| (i >> 8) as u8 | 0x80)
+            .collect();
+
+        cartridge.load_rom(rom, cartridge_type);
+
+        cartridge
+    }
+
+    #[test]
+    fn reads_the_switch_over_the_rom() {
+        let mut cartridge = with_rom(CartridgeType::ActionReplay);
+
+        assert_eq!(cartridge.read8(0x100), 0x81);
+        assert_eq!(cartridge.read8(ACTION_REPLAY_SWITCH), 0);
+
+        cartridge.toggle_switch();
+
+        assert_eq!(cartridge.read8(ACTION_REPLAY_SWITCH), 1);
+
+        // only the Xplorer has a switch at the end of its ROM window
+        assert_eq!(cartridge.read8(XPLORER_SWITCH), 0xff);
+
+        let mut xplorer = with_rom(CartridgeType::Xplorer);
+
+        xplorer.toggle_switch();
+
+        assert_eq!(xplorer.read8(ACTION_REPLAY_SWITCH), 0x80);
+        assert_eq!(xplorer.read8(XPLORER_SWITCH), 1);
+    }
+
+    #[test]
+    fn latches_action_replay_writes() {
+        let mut cartridge = with_rom(CartridgeType::ActionReplay);
+
+        cartridge.write8(ACTION_REPLAY_LATCH, 0x5a);
+
+        assert_eq!(cartridge.latch, 0x5a);
+
+        // the ROM itself isn't writable
+        cartridge.write8(0x100, 0);
+
+        assert_eq!(cartridge.read8(0x100), 0x81);
+
+        let mut xplorer = with_rom(CartridgeType::Xplorer);
+
+        xplorer.write8(ACTION_REPLAY_LATCH, 0x5a);
+
+        assert_eq!(xplorer.latch, 0);
+    }
+
+    #[test]
+    fn reads_words_over_pio() {
+        let mut cartridge = with_rom(CartridgeType::ActionReplay);
+
+        assert_eq!(cartridge.dma_read(true), 0x8080_8080);
+
+        cartridge.pio_address = 0xfe;
+
+        assert_eq!(cartridge.dma_read(true), 0x8181_8080);
+        assert_eq!(cartridge.pio_address, 0x102);
+
+        cartridge.reset_pio();
+        cartridge.unload();
+
+        assert!(!cartridge.is_loaded());
+        assert_eq!(cartridge.dma_read(false), 0xffff_ffff);
+    }
+}
@@ -8,6 +8,7 @@ use std::{collections::VecDeque, ops::Deref};
 use memmap2::Mmap;
 use registers::HntmaskRegister;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
 use crate::cpu::bus::spu::{
     SPU,
@@ -18,6 +19,7 @@ use super::{
     registers::interrupt_register::InterruptRegister,
     scheduler::{EventType, Scheduler},
 };
+use crate::log;
 
 pub mod registers;
 
@@ -252,7 +254,7 @@ impl Msf {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 enum ControllerMode {
     Idle,
     ExecuteCommand,
@@ -263,7 +265,7 @@ enum ControllerMode {
     TransferResponse,
 }
 
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 enum DriveMode {
     Idle,
     Seek,
@@ -470,6 +472,19 @@ impl CDRom {
         self.game_bytes = Some(game_bytes);
     }
 
+    // the disc image isn't part of save states, so it gets carried over from the previous drive
+    pub fn swap_media(&mut self, other: &mut CDRom) {
+        #[cfg(not(target_arch = "wasm32"))]
+        std::mem::swap(&mut self.game_data, &mut other.game_data);
+        #[cfg(target_arch = "wasm32")]
+        {
+            std::mem::swap(&mut self.game_bytes, &mut other.game_bytes);
+            std::mem::swap(&mut self.bin_files_map, &mut other.bin_files_map);
+        }
+        std::mem::swap(&mut self.bin_files, &mut other.bin_files);
+        std::mem::swap(&mut self.tracks, &mut other.tracks);
+    }
+
     fn read_hintsts(&self) -> u8 {
         self.irqs | 0x7 << 5
     }
@@ -538,6 +553,61 @@ impl CDRom {
             | ((self.controller_mode != ControllerMode::Idle) as u8) << 7
     }
 
+    // the controller and drive state in a line, for crash reports
+    pub fn status_summary(&self) -> String {
+        format!(
+            "HSTS: 0x{:02x} command: 0x{:02x} controller: {:?} drive: {:?} playing: {} seeking: {} reading: {} motor on: {} shell open: {} position: {:?}",
+            self.read_hsts(),
+            self.command,
+            self.controller_mode,
+            self.drive_mode,
+            self.is_playing,
+            self.is_seeking,
+            self.is_reading,
+            self.motor_on,
+            self.shell_open,
+            self.current_msf
+        )
+    }
+
+    pub fn inspect(&self) -> Value {
+        json!({
+            "hsts": format!("0x{:02x}", self.read_hsts()),
+            "hintmsk": format!("0x{:02x}", self.hntmask.read()),
+            "bank": self.bank,
+            "irq_latch": self.irq_latch,
+            "irqs": self.irqs,
+            "command": format!("0x{:02x}", self.command),
+            "command_latch": self.command_latch,
+            "parameter_fifo": self.parameter_fifo,
+            "result_fifo": self.result_fifo,
+            "controller_param_fifo": self.controller_param_fifo,
+            "controller_response_fifo": self.controller_response_fifo,
+            "controller_mode": self.controller_mode,
+            "drive_mode": self.drive_mode,
+            "subresponse_mode": self.subresponse_mode,
+            "setmode": {
+                "double_speed": self.double_speed,
+                "xa_adpcm": self.send_to_spu,
+                "sector_size": self.sector_size,
+                "report_interrupts": self.report_interrupts,
+                "xa_filter": self.xa_filter,
+            },
+            "xa_filter": {
+                "file": self.filter_file,
+                "channel": self.filter_channel,
+            },
+            "playing": self.is_playing,
+            "seeking": self.is_seeking,
+            "reading": self.is_reading,
+            "motor_on": self.motor_on,
+            "shell_open": self.shell_open,
+            "seek_target": self.msf,
+            "position": self.current_msf,
+            "buffered_bytes": self.sector_size.saturating_sub(self.buffer_index),
+        })
+    }
+
     /*
     7  Play          Playing CD-DA         ;\only ONE of these bits can be set
     6  Seek          Seeking               ; at a time (ie. Read/Play won't get
@@ -1640,7 +1710,7 @@ impl CDRom {
 
             self.subresponse_mode = SubresponseMode::Disabled;
         } else {
-            println!("[WARN]irqs are pending in cd_stat");
+            log!(Cdrom, Warn, "irqs are pending in cd_stat");
             self.drive_cycles += 1;
         }
     }
@@ -1747,7 +1817,7 @@ impl CDRom {
 
             self.subresponse_mode = SubresponseMode::Disabled;
         } else {
-            println!("[WARN]: irqs are pending in read_id");
+            log!(Cdrom, Warn, "irqs are pending in read_id");
             self.subresponse_cycles += 1;
         }
         self.controller_cycles += 10;
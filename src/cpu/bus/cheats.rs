@@ -0,0 +1,430 @@
+use serde::{Deserialize, Serialize};
+
+// GameShark/Action Replay code types, the upper byte of the code address.
+// see https://psx-spx.consoledev.net/cheatdevices/#cheat-devices-code-types
+const WRITE16: u8 = 0x80;
+const WRITE8: u8 = 0x30;
+const INCREMENT16: u8 = 0x10;
+const DECREMENT16: u8 = 0x11;
+const INCREMENT8: u8 = 0x20;
+const DECREMENT8: u8 = 0x21;
+const EQUAL16: u8 = 0xd0;
+const NOT_EQUAL16: u8 = 0xd1;
+const LESS16: u8 = 0xd2;
+const GREATER16: u8 = 0xd3;
+const EQUAL8: u8 = 0xe0;
+const NOT_EQUAL8: u8 = 0xe1;
+const LESS8: u8 = 0xe2;
+const GREATER8: u8 = 0xe3;
+const SERIAL_REPEATER: u8 = 0x50;
+const ACTIVATE_IF_EQUAL: u8 = 0xc0;
+const ACTIVATION_DELAY: u8 = 0xc1;
+const COPY_BYTES: u8 = 0xc2;
+
+// psx-spx puts C1 delays of 4000h-5000h at roughly 20-30 seconds, so 4000h is taken as 20 seconds.
+// the cheat engine runs once per vblank, which is close enough to 60 times a second either way.
+const ACTIVATION_DELAY_UNITS: u64 = 0x4000 / 20;
+const FRAMES_PER_SECOND: u64 = 60;
+
+const SUPPORTED_TYPES: [u8; 18] = [
+    WRITE16,
+    WRITE8,
+    INCREMENT16,
+    DECREMENT16,
+    INCREMENT8,
+    DECREMENT8,
+    EQUAL16,
+    NOT_EQUAL16,
+    LESS16,
+    GREATER16,
+    EQUAL8,
+    NOT_EQUAL8,
+    LESS8,
+    GREATER8,
+    SERIAL_REPEATER,
+    ACTIVATE_IF_EQUAL,
+    ACTIVATION_DELAY,
+    COPY_BYTES,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CheatCode {
+    pub address: u32,
+    pub value: u16,
+}
+
+impl CheatCode {
+    pub fn code_type(&self) -> u8 {
+        (self.address >> 24) as u8
+    }
+
+    fn ram_address(&self) -> usize {
+        (self.address & 0x1f_ffff) as usize
+    }
+
+    // serial repeaters and copies take the code following them as an extra parameter
+    fn len(&self) -> usize {
+        match self.code_type() {
+            SERIAL_REPEATER | COPY_BYTES => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn parse(address: &str, value: &str) -> Result<Self, String> {
+        if address.len() != 8 || value.len() != 4 {
+            return Err(format!("invalid code: {address} {value}"));
+        }
+
+        let address = u32::from_str_radix(address, 16)
+            .map_err(|_| format!("invalid code address: {address}"))?;
+        let value =
+            u16::from_str_radix(value, 16).map_err(|_| format!("invalid code value: {value}"))?;
+
+        let code = Self { address, value };
+
+        if !SUPPORTED_TYPES.contains(&code.code_type()) {
+            return Err(format!("unsupported code type: {:02X}", code.code_type()));
+        }
+
+        Ok(code)
+    }
+}
+
+impl std::fmt::Display for CheatCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08X} {:04X}", self.address, self.value)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cheat {
+    pub name: String,
+    pub codes: Vec<CheatCode>,
+    pub enabled: bool,
+}
+
+#[derive(Default)]
+pub struct CheatEngine {
+    cheats: Vec<Cheat>,
+    frames: u32,
+}
+
+fn read16(ram: &[u8], address: usize) -> u16 {
+    ram[address] as u16 | (ram[(address + 1) & 0x1f_ffff] as u16) << 8
+}
+
+fn write16(ram: &mut [u8], address: usize, value: u16) {
+    ram[address] = value as u8;
+    ram[(address + 1) & 0x1f_ffff] = (value >> 8) as u8;
+}
+
+impl CheatEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // parses whitespace separated "XXXXXXXX YYYY" pairs, usually one per line
+    pub fn parse_codes(codes: &str) -> Result<Vec<CheatCode>, String> {
+        let tokens: Vec<&str> = codes.split_whitespace().collect();
+
+        if tokens.is_empty() || !tokens.len().is_multiple_of(2) {
+            return Err("expected pairs of code addresses and values".to_string());
+        }
+
+        let codes = tokens
+            .chunks(2)
+            .map(|pair| CheatCode::parse(pair[0], pair[1]))
+            .collect::<Result<Vec<CheatCode>, String>>()?;
+
+        Self::validate_codes(&codes)?;
+
+        Ok(codes)
+    }
+
+    // codes that didn't come from parse_codes (ie a hand edited cheats.json) get checked here too
+    fn validate_codes(codes: &[CheatCode]) -> Result<(), String> {
+        if codes.is_empty() {
+            return Err("a cheat needs at least one code".to_string());
+        }
+
+        let mut i = 0;
+
+        while i < codes.len() {
+            let code = codes[i];
+
+            if !SUPPORTED_TYPES.contains(&code.code_type()) {
+                return Err(format!("unsupported code type: {:02X}", code.code_type()));
+            }
+
+            if i + code.len() > codes.len() {
+                return Err(format!("code {code} needs to be followed by another code"));
+            }
+
+            i += code.len();
+        }
+
+        Ok(())
+    }
+
+    pub fn add_cheat(&mut self, name: &str, codes: &str) -> Result<usize, String> {
+        let codes = Self::parse_codes(codes)?;
+
+        self.add_codes(name, codes)
+    }
+
+    pub fn add_codes(&mut self, name: &str, codes: Vec<CheatCode>) -> Result<usize, String> {
+        Self::validate_codes(&codes)?;
+
+        self.cheats.push(Cheat {
+            name: name.to_string(),
+            codes,
+            enabled: true,
+        });
+
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.get_mut(index) {
+            cheat.enabled = enabled;
+
+            // activation delays count from the moment a cheat gets switched on
+            if enabled {
+                self.frames = 0;
+            }
+        }
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) {
+        if index < self.cheats.len() {
+            self.cheats.remove(index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.cheats).unwrap()
+    }
+
+    pub fn load_json(&mut self, json: &str) -> Result<(), String> {
+        let cheats: Vec<Cheat> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        for cheat in &cheats {
+            Self::validate_codes(&cheat.codes).map_err(|e| format!("{}: {e}", cheat.name))?;
+        }
+
+        self.cheats = cheats;
+        self.frames = 0;
+
+        Ok(())
+    }
+
+    // called once per frame at the start of vblank
+    pub fn apply(&mut self, ram: &mut [u8]) {
+        self.frames = self.frames.saturating_add(1);
+
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            Self::apply_codes(&cheat.codes, ram, self.frames);
+        }
+    }
+
+    fn apply_codes(codes: &[CheatCode], ram: &mut [u8], frames: u32) {
+        let mut i = 0;
+
+        while i < codes.len() {
+            let code = codes[i];
+            let address = code.ram_address();
+            let value = code.value;
+
+            let condition = match code.code_type() {
+                WRITE16 => {
+                    write16(ram, address, value);
+                    true
+                }
+                WRITE8 => {
+                    ram[address] = value as u8;
+                    true
+                }
+                INCREMENT16 => {
+                    write16(ram, address, read16(ram, address).wrapping_add(value));
+                    true
+                }
+                DECREMENT16 => {
+                    write16(ram, address, read16(ram, address).wrapping_sub(value));
+                    true
+                }
+                INCREMENT8 => {
+                    ram[address] = ram[address].wrapping_add(value as u8);
+                    true
+                }
+                DECREMENT8 => {
+                    ram[address] = ram[address].wrapping_sub(value as u8);
+                    true
+                }
+                EQUAL16 => read16(ram, address) == value,
+                NOT_EQUAL16 => read16(ram, address) != value,
+                LESS16 => read16(ram, address) < value,
+                GREATER16 => read16(ram, address) > value,
+                EQUAL8 => ram[address] == value as u8,
+                NOT_EQUAL8 => ram[address] != value as u8,
+                LESS8 => ram[address] < value as u8,
+                GREATER8 => ram[address] > value as u8,
+                ACTIVATE_IF_EQUAL => {
+                    if read16(ram, address) != value {
+                        return;
+                    }
+                    true
+                }
+                ACTIVATION_DELAY => {
+                    if (frames as u64) * ACTIVATION_DELAY_UNITS < (value as u64) * FRAMES_PER_SECOND
+                    {
+                        return;
+                    }
+                    true
+                }
+                SERIAL_REPEATER => {
+                    Self::serial_repeat(code, codes[i + 1], ram);
+                    true
+                }
+                COPY_BYTES => {
+                    let destination = codes[i + 1].ram_address();
+
+                    for offset in 0..value as usize {
+                        ram[(destination + offset) & 0x1f_ffff] =
+                            ram[(address + offset) & 0x1f_ffff];
+                    }
+                    true
+                }
+                _ => unreachable!(),
+            };
+
+            i += code.len();
+
+            // conditionals skip the next code when they don't match
+            if !condition && i < codes.len() {
+                i += codes[i].len();
+            }
+        }
+    }
+
+    /*
+      5000XXYY ZZZZ  repeat the next code XX times, adding YY to its
+      TTAAAAAA VVVV  address and ZZZZ to its value after every write
+    */
+    fn serial_repeat(repeater: CheatCode, code: CheatCode, ram: &mut [u8]) {
+        let count = (repeater.address >> 8) & 0xff;
+        let step = (repeater.address & 0xff) as usize;
+
+        let mut address = code.ram_address();
+        let mut value = code.value;
+
+        for _ in 0..count {
+            match code.code_type() {
+                WRITE16 => write16(ram, address, value),
+                WRITE8 => ram[address] = value as u8,
+                _ => (),
+            }
+
+            address = (address + step) & 0x1f_ffff;
+            value = value.wrapping_add(repeater.value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_code_pairs() {
+        let codes = CheatEngine::parse_codes("80010000 1234\n30010002 0056").unwrap();
+
+        assert_eq!(
+            codes,
+            vec![
+                CheatCode {
+                    address: 0x8001_0000,
+                    value: 0x1234
+                },
+                CheatCode {
+                    address: 0x3001_0002,
+                    value: 0x56
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        assert!(CheatEngine::parse_codes("").is_err());
+        assert!(CheatEngine::parse_codes("80010000").is_err());
+        assert!(CheatEngine::parse_codes("8001000 1234").is_err());
+        assert!(CheatEngine::parse_codes("8001000G 1234").is_err());
+        assert!(CheatEngine::parse_codes("FF010000 1234").is_err());
+
+        // a serial repeater without the code it repeats
+        assert!(CheatEngine::parse_codes("50000A02 0001").is_err());
+        assert!(CheatEngine::parse_codes("50000A02 0001 80010000 0000").is_ok());
+    }
+
+    #[test]
+    fn add_codes_validates() {
+        let mut cheats = CheatEngine::new();
+
+        let repeater = CheatCode {
+            address: 0x5000_0a02,
+            value: 1,
+        };
+
+        assert!(cheats.add_codes("empty", Vec::new()).is_err());
+        assert!(cheats.add_codes("repeater", vec![repeater]).is_err());
+        assert!(cheats.cheats().is_empty());
+
+        assert_eq!(cheats.add_cheat("write", "80010000 1234"), Ok(0));
+    }
+
+    #[test]
+    fn load_json_round_trips_and_rejects_bad_cheats() {
+        let mut cheats = CheatEngine::new();
+
+        cheats.add_cheat("write", "80010000 1234").unwrap();
+
+        let json = cheats.to_json();
+        let mut loaded = CheatEngine::new();
+
+        loaded.load_json(&json).unwrap();
+
+        assert_eq!(loaded.cheats().len(), 1);
+        assert_eq!(loaded.cheats()[0].name, "write");
+        assert_eq!(loaded.cheats()[0].codes, cheats.cheats()[0].codes);
+
+        let bad = r#"[{"name":"bad","codes":[{"address":4278190080,"value":0}],"enabled":true}]"#;
+
+        assert!(loaded.load_json(bad).unwrap_err().starts_with("bad: "));
+        assert!(loaded.load_json("not json").is_err());
+
+        // nothing gets replaced when loading fails
+        assert_eq!(loaded.cheats().len(), 1);
+    }
+
+    #[test]
+    fn applies_writes_to_ram() {
+        let mut cheats = CheatEngine::new();
+        let mut ram = vec![0; 0x20_0000];
+
+        cheats
+            .add_cheat("writes", "80010000 1234\n30010010 0056")
+            .unwrap();
+        cheats.apply(&mut ram);
+
+        assert_eq!(ram[0x10000..0x10002], [0x34, 0x12]);
+        assert_eq!(ram[0x10010], 0x56);
+    }
+}
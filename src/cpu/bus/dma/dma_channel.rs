@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
 
 use crate::cpu::bus::scheduler::{EventType, Scheduler};
 use crate::cpu::bus::spu::SPU;
 use crate::cpu::bus::{
-    cdrom::CDRom, gpu::GPU, mdec::Mdec, registers::interrupt_register::InterruptRegister,
+    cartridge::Cartridge, cdrom::CDRom, gpu::GPU, mdec::Mdec,
+    registers::interrupt_register::InterruptRegister,
 };
 
 use super::{
@@ -20,6 +22,16 @@ pub const DMA_SPU: usize = 4;
 pub const DMA_PIO: usize = 5;
 pub const DMA_OTC: usize = 6;
 
+const CHANNEL_NAMES: [&str; 7] = ["mdec_in", "mdec_out", "gpu", "cdrom", "spu", "pio", "otc"];
+
+// a range of main RAM a transfer wrote to, for the watch list
+#[derive(Copy, Clone, Debug)]
+pub struct DmaWrite {
+    pub channel: usize,
+    pub address: u32,
+    pub len: u32,
+}
+
 pub const DMA_TICKS_REMAINING: usize = 100;
 pub const DMA_HALT_TICKS: usize = 100;
 pub const DMA_TICKS_PER_BLOCK: usize = 34;
@@ -52,6 +64,25 @@ impl DmaChannel {
         }
     }
 
+    pub fn inspect(&self) -> Value {
+        // sync mode 3 is reserved, which sync_mode() refuses
+        let sync_mode = match (self.control.bits() >> 9) & 0x3 {
+            3 => "Reserved".to_string(),
+            _ => format!("{:?}", self.control.sync_mode()),
+        };
+
+        json!({
+            "base_address": format!("0x{:06x}", self.base_address),
+            "block_size": self.block_size,
+            "num_blocks": self.num_blocks,
+            "control": format!("0x{:08x}", self.control.bits()),
+            "flags": self.control,
+            "sync_mode": sync_mode,
+            "halted": self.halted,
+            "request": self.request,
+        })
+    }
+
     pub fn write(&mut self, register: usize, value: u32) {
         match register {
             0 => self.base_address = value & 0xffffff,
@@ -137,7 +168,7 @@ impl DmaChannel {
 
                             word_count -= 1;
 
-                            gpu.process_gp0_commands(word);
+                            gpu.process_gp0_dma(word);
                         }
 
                         current_address = packet & 0xffffff;
@@ -159,7 +190,7 @@ impl DmaChannel {
                     for _ in 0..num_words {
                         let word =
                             unsafe { *(&ram[current_address as usize] as *const u8 as *const u32) };
-                        gpu.process_gp0_commands(word);
+                        gpu.process_gp0_dma(word);
 
                         if self.control.contains(DmaChannelControlRegister::DECREMENT) {
                             current_address -= 4;
@@ -184,7 +215,7 @@ impl DmaChannel {
                                     as *const u32)
                             };
 
-                            gpu.process_gp0_commands(word);
+                            gpu.process_gp0_dma(word);
 
                             if self.control.contains(DmaChannelControlRegister::DECREMENT) {
                                 current_address -= 4;
@@ -255,8 +286,36 @@ impl DmaChannel {
         spu.update_dma_request();
     }
 
-    pub fn start_pio_transfer(&mut self) {
-        todo!("pio transfer");
+    pub fn start_pio_transfer(
+        &mut self,
+        ram: &mut [u8],
+        cartridge: &mut Cartridge,
+        auto_increment: bool,
+    ) {
+        let mut current_address = self.base_address & 0x1ffffc;
+
+        cartridge.reset_pio();
+
+        for _ in 0..self.get_num_words() {
+            if self
+                .control
+                .contains(DmaChannelControlRegister::TRANSFER_DIR)
+            {
+                let word = unsafe { *(&ram[current_address as usize] as *const u8 as *const u32) };
+
+                cartridge.dma_write(word, auto_increment);
+            } else {
+                let word = cartridge.dma_read(auto_increment);
+
+                unsafe { *(&mut ram[current_address as usize] as *mut u8 as *mut u32) = word };
+            }
+
+            if self.control.contains(DmaChannelControlRegister::DECREMENT) {
+                current_address = current_address.wrapping_sub(4) & 0x1ffffc;
+            } else {
+                current_address = (current_address + 4) & 0x1ffffc;
+            }
+        }
     }
 
     pub fn start_otc_transfer(&mut self, ram: &mut [u8]) {
@@ -304,6 +363,20 @@ impl Dma {
         }
     }
 
+    pub fn inspect(&self) -> Value {
+        let channels: Map<String, Value> = CHANNEL_NAMES
+            .iter()
+            .zip(&self.channels)
+            .map(|(name, channel)| (name.to_string(), channel.inspect()))
+            .collect();
+
+        json!({
+            "dpcr": format!("0x{:08x}", self.dma_control.bits()),
+            "dicr": format!("0x{:08x}", self.dicr.bits()),
+            "channels": channels,
+        })
+    }
+
     pub fn start_mdec_in_transfer(
         &mut self,
         ram: &mut [u8],
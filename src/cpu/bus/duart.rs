@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::bus::tty::Tty;
+
+// SCN2681 dual UART found on dev boards in expansion region 2 (1F802020h-1F80202Fh).
+// Channel A is the debug terminal and gets routed to the TTY, channel B output is dropped.
+// see https://psx-spx.consoledev.net/expansionportpio/#expansion-region-2-dual-serial-port-for-tty-debug-terminal
+const RX_FIFO_SIZE: usize = 3;
+
+const CHANNEL_A: usize = 0;
+
+#[derive(Default, Serialize, Deserialize)]
+struct DuartChannel {
+    mode: [u8; 2],
+    mode_pointer: usize,
+    clock_select: u8,
+    rx_enabled: bool,
+    tx_enabled: bool,
+    rx_fifo: VecDeque<u8>,
+}
+
+impl DuartChannel {
+    fn read_mode(&mut self) -> u8 {
+        let value = self.mode[self.mode_pointer];
+
+        self.mode_pointer = 1;
+
+        value
+    }
+
+    fn write_mode(&mut self, value: u8) {
+        self.mode[self.mode_pointer] = value;
+
+        self.mode_pointer = 1;
+    }
+
+    /*
+      0-1  Rx control (0=No change, 1=Enable, 2=Disable, 3=Invalid)
+      2-3  Tx control (0=No change, 1=Enable, 2=Disable, 3=Invalid)
+      4-6  Misc command (1=Reset MR pointer, 2=Reset receiver, 3=Reset transmitter, 4-7=Error/break stuff)
+    */
+    fn write_command(&mut self, value: u8) {
+        match value & 0x3 {
+            1 => self.rx_enabled = true,
+            2 => self.rx_enabled = false,
+            _ => (),
+        }
+
+        match (value >> 2) & 0x3 {
+            1 => self.tx_enabled = true,
+            2 => self.tx_enabled = false,
+            _ => (),
+        }
+
+        match (value >> 4) & 0x7 {
+            1 => self.mode_pointer = 0,
+            2 => {
+                self.rx_enabled = false;
+                self.rx_fifo.clear();
+            }
+            3 => self.tx_enabled = false,
+            _ => (),
+        }
+    }
+
+    fn read_status(&self) -> u8 {
+        (!self.rx_fifo.is_empty() as u8)
+            | ((self.rx_fifo.len() >= RX_FIFO_SIZE) as u8) << 1
+            | (self.tx_enabled as u8) << 2
+            | (self.tx_enabled as u8) << 3
+    }
+
+    fn read_rx(&mut self) -> u8 {
+        self.rx_fifo.pop_front().unwrap_or(0)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Duart {
+    channels: [DuartChannel; 2],
+    aux_control: u8,
+    interrupt_mask: u8,
+    counter_preset: u16,
+    output_port_config: u8,
+    output_port: u8,
+}
+
+impl Duart {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tx_enabled(&self) -> bool {
+        self.channels[CHANNEL_A].tx_enabled
+    }
+
+    // feeds a byte typed on the host into the receive FIFO of the given channel
+    pub fn push_rx(&mut self, channel: usize, value: u8) {
+        let channel = &mut self.channels[channel];
+
+        if channel.rx_enabled && channel.rx_fifo.len() < RX_FIFO_SIZE {
+            channel.rx_fifo.push_back(value);
+        }
+    }
+
+    fn read_interrupt_status(&self) -> u8 {
+        let status_a = self.channels[0].read_status();
+        let status_b = self.channels[1].read_status();
+
+        ((status_a >> 2) & 1)
+            | (status_a & 1) << 1
+            | ((status_b >> 2) & 1) << 4
+            | (status_b & 1) << 5
+    }
+
+    // offset is relative to 1F802020h
+    pub fn read(&mut self, offset: usize) -> u8 {
+        let channel = offset >> 3;
+
+        match offset {
+            0x0 | 0x8 => self.channels[channel].read_mode(),
+            0x1 | 0x9 => self.channels[channel].read_status(),
+            0x3 | 0xb => self.channels[channel].read_rx(),
+            0x4 => 0,
+            0x5 => self.read_interrupt_status(),
+            0x6 => (self.counter_preset >> 8) as u8,
+            0x7 => self.counter_preset as u8,
+            // input port, baud rate test mode and counter start/stop strobes
+            _ => 0xff,
+        }
+    }
+
+    pub fn write(&mut self, offset: usize, value: u8, tty: &mut Tty) {
+        let channel = offset >> 3;
+
+        match offset {
+            0x0 | 0x8 => self.channels[channel].write_mode(value),
+            0x1 | 0x9 => self.channels[channel].clock_select = value,
+            0x2 | 0xa => self.channels[channel].write_command(value),
+            0x3 if self.channels[CHANNEL_A].tx_enabled => tty.write_byte(value),
+            // nothing is hooked up to channel B
+            0xb => (),
+            0x4 => self.aux_control = value,
+            0x5 => self.interrupt_mask = value,
+            0x6 => self.counter_preset = (self.counter_preset & 0xff) | (value as u16) << 8,
+            0x7 => self.counter_preset = (self.counter_preset & 0xff00) | value as u16,
+            0xd => self.output_port_config = value,
+            0xe => self.output_port |= value,
+            0xf => self.output_port &= !value,
+            _ => (),
+        }
+    }
+}
@@ -5,7 +5,16 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use dump::{GpuDump, GpuDumpEntry};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+#[cfg(any(
+    feature = "software_gpu",
+    feature = "hardware_gpu_metal",
+    feature = "hardware_gpu_web",
+    feature = "hardware_gpu_opengl"
+))]
+use vram_viewer::VramViewer;
 
 use crate::cpu::CPU_FREQUENCY;
 
@@ -16,8 +25,10 @@ use super::{
 };
 
 pub mod deltas;
+pub mod dump;
 #[cfg(feature = "software_gpu")]
 pub mod render;
+pub mod vram_viewer;
 
 const HBLANK_START: usize = 2813;
 const CYCLES_PER_SCANLINE: usize = 3413;
@@ -349,6 +360,9 @@ pub struct GPU {
     cpu_transfer_height: u32,
     #[cfg(feature = "software_gpu")]
     pub picture: Box<[u8]>,
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    dump: Option<GpuDump>,
 }
 
 impl GPU {
@@ -465,6 +479,7 @@ impl GPU {
             vram_write_tex: vec![0; VRAM_WIDTH * VRAM_HEIGHT * 4].into_boxed_slice(),
             previous_line_vertex: None,
             previous_line_color: None,
+            dump: None,
         }
     }
 
@@ -572,7 +587,7 @@ impl GPU {
         interrupt_stat: &mut InterruptRegister,
         timers: &mut [Timer],
         cycles_left: usize,
-    ) {
+    ) -> bool {
         timers[0].in_xblank = false;
 
         if timers[0]
@@ -599,7 +614,9 @@ impl GPU {
             timers[1].tick(1, interrupt_stat);
         }
 
-        if self.current_line < VBLANK_LINE_START {
+        let entered_vblank = self.current_line >= VBLANK_LINE_START;
+
+        if !entered_vblank {
             scheduler.schedule(
                 EventType::HblankStart,
                 (HBLANK_START as f64 * (GPU_CYCLES_TO_CPU_CYCLES)) as usize - cycles_left,
@@ -608,6 +625,8 @@ impl GPU {
             timers[1].in_xblank = true;
             self.frame_finished = true;
 
+            self.record(GpuDumpEntry::Vblank);
+
             interrupt_stat.insert(InterruptRegister::VBLANK);
 
             scheduler.schedule(
@@ -623,6 +642,8 @@ impl GPU {
         }
 
         self.current_line += 1;
+
+        entered_vblank
     }
 
     fn get_words_left(&mut self, word: u32) -> usize {
@@ -705,7 +726,7 @@ impl GPU {
         }
     }
 
-    fn parse_texpage(word: u32) -> Texpage {
+    pub fn parse_texpage(word: u32) -> Texpage {
         let mut texpage = Texpage::new();
 
         texpage.x_base = word & 0xf;
@@ -974,7 +995,7 @@ impl GPU {
         self.num_vertices = 0;
     }
 
-    fn parse_clut(word: u32) -> (usize, usize) {
+    pub fn parse_clut(word: u32) -> (usize, usize) {
         let x = (word & 0x3f) * 16;
         let y = (word >> 6) & 0x1ff;
 
@@ -1656,6 +1677,8 @@ impl GPU {
     }
 
     pub fn process_gp1_commands(&mut self, word: u32) {
+        self.record(GpuDumpEntry::Gp1(word));
+
         let command = word >> 24;
         match command {
             0x0 => self.reset_gpu(word),
@@ -1780,6 +1803,18 @@ impl GPU {
     }
 
     pub fn process_gp0_commands(&mut self, word: u32) {
+        self.record(GpuDumpEntry::Gp0(word));
+
+        self.write_gp0(word);
+    }
+
+    pub fn process_gp0_dma(&mut self, word: u32) {
+        self.record(GpuDumpEntry::Gp0Dma(word));
+
+        self.write_gp0(word);
+    }
+
+    fn write_gp0(&mut self, word: u32) {
         if let Some(transfer_type) = self.transfer_type {
             if transfer_type == TransferType::ToVram {
                 self.transfer_to_vram(word as u16);
@@ -1857,6 +1892,73 @@ impl GPU {
             bit31 << 31
     }
 
+    // the hardware renderers only copy VRAM back into vram_read_tex when saving a state
+    #[cfg(any(
+        feature = "software_gpu",
+        feature = "hardware_gpu_metal",
+        feature = "hardware_gpu_web",
+        feature = "hardware_gpu_opengl"
+    ))]
+    pub fn vram_viewer(&self) -> VramViewer<'_> {
+        #[cfg(feature = "software_gpu")]
+        let vram = &self.vram;
+        #[cfg(not(feature = "software_gpu"))]
+        let vram = &self.vram_read_tex;
+
+        VramViewer::new(vram).unwrap()
+    }
+
+    // the drawing and display state, VRAM itself is left out
+    pub fn inspect(&self) -> Value {
+        let command_buffer: Vec<String> = self
+            .current_command_buffer
+            .iter()
+            .map(|word| format!("0x{word:08x}"))
+            .collect();
+
+        json!({
+            "gpustat": format!("0x{:08x}", self.read_stat()),
+            "texpage": self.texpage,
+            "texture_window": {
+                "mask_x": self.texture_window_mask_x,
+                "mask_y": self.texture_window_mask_y,
+                "offset_x": self.texture_window_offset_x,
+                "offset_y": self.texture_window_offset_y,
+            },
+            "drawing_area": {
+                "left": self.x1,
+                "top": self.y1,
+                "right": self.x2,
+                "bottom": self.y2,
+            },
+            "drawing_offset": {
+                "x": self.x_offset,
+                "y": self.y_offset,
+            },
+            "force_mask_bit": self.force_mask_bit,
+            "preserve_masked_pixels": self.preserve_masked_pixels,
+            "display": {
+                "enabled": self.display_on,
+                "start_x": self.display_start_x,
+                "start_y": self.display_start_y,
+                "range_x": self.display_range_x,
+                "range_y": self.display_range_y,
+                "width": self.display_width,
+                "height": self.display_height,
+                "depth": self.display_depth,
+                "video_mode": self.video_mode,
+                "interlaced": self.interlaced,
+            },
+            "current_line": self.current_line,
+            "irq_enabled": self.irq_enabled,
+            "dma_direction": self.dma_direction,
+            "command_buffer": command_buffer,
+            "words_left": self.words_left,
+            "transfer": self.transfer_type,
+            "gpuread": format!("0x{:08x}", self.gpuread),
+        })
+    }
+
     pub fn cap_fps(&mut self) {
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
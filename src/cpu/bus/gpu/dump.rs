@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+
+use super::{GPU, TransferType, VRAM_HEIGHT, VRAM_WIDTH};
+
+const MAGIC: &[u8; 8] = b"RSXGPUDP";
+const VERSION: u32 = 1;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GpuDumpEntry {
+    // written to GP0 by the CPU
+    Gp0(u32),
+    // written to GP0 by DMA channel 2
+    Gp0Dma(u32),
+    Gp1(u32),
+    // the start of vblank, which is where a dump gets split into frames
+    Vblank,
+}
+
+/*
+  Everything the GPU got told to do for a while, starting from a copy of VRAM and a few commands
+  that restore the drawing and display settings from when the dump started. That's all it takes
+  to render the same frames again without the game or the BIOS, and since the words go through
+  process_gp0_commands and process_gp1_commands like they did when the game ran, a dump recorded
+  with one renderer can be played back with any other and the results compared.
+*/
+#[derive(Default, Serialize, Deserialize)]
+pub struct GpuDump {
+    pub vram: Vec<u8>,
+    pub entries: Vec<GpuDumpEntry>,
+}
+
+impl GpuDump {
+    pub fn new(vram: Vec<u8>) -> Self {
+        Self {
+            vram,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|&&entry| entry == GpuDumpEntry::Vblank)
+            .count()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend(bincode::serde::encode_to_vec(self, bincode::config::standard()).unwrap());
+
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 12 || &data[..8] != MAGIC {
+            return Err("not a GPU dump".to_string());
+        }
+
+        let version = u32::from_le_bytes(data[8..12].try_into().unwrap());
+
+        if version != VERSION {
+            return Err(format!("unsupported GPU dump version {version}"));
+        }
+
+        let (dump, _): (GpuDump, _) =
+            bincode::serde::decode_from_slice(&data[12..], bincode::config::standard())
+                .map_err(|e| e.to_string())?;
+
+        if dump.vram.len() != VRAM_WIDTH * VRAM_HEIGHT * 2 {
+            return Err(format!(
+                "expected 0x{:x} bytes of VRAM, got 0x{:x}",
+                VRAM_WIDTH * VRAM_HEIGHT * 2,
+                dump.vram.len()
+            ));
+        }
+
+        Ok(dump)
+    }
+}
+
+/*
+  Plays a dump back into a GPU that was just created, a frame at a time. VRAM gets uploaded with a
+  regular CPU to VRAM transfer before anything else, so the hardware renderers see it too. Frontends
+  render and present after every frame like they would when running a game.
+*/
+pub struct GpuPlayer {
+    dump: GpuDump,
+    position: usize,
+    started: bool,
+}
+
+impl GpuPlayer {
+    pub fn new(dump: GpuDump) -> Self {
+        Self {
+            dump,
+            position: 0,
+            started: false,
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.dump.frames()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.started && self.position == self.dump.entries.len()
+    }
+
+    // plays everything up to the next vblank, returns false once there's nothing left to play
+    pub fn play_frame(&mut self, gpu: &mut GPU) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+
+        if !self.started {
+            self.started = true;
+
+            gpu.process_gp0_commands(0xa000_0000);
+            gpu.process_gp0_commands(0);
+            // a width and height of 0 is all of VRAM
+            gpu.process_gp0_commands(0);
+
+            for word in self.dump.vram.chunks_exact(4) {
+                gpu.process_gp0_commands(u32::from_le_bytes(word.try_into().unwrap()));
+            }
+        }
+
+        while let Some(&entry) = self.dump.entries.get(self.position) {
+            self.position += 1;
+
+            match entry {
+                GpuDumpEntry::Gp0(word) => gpu.process_gp0_commands(word),
+                GpuDumpEntry::Gp0Dma(word) => gpu.process_gp0_dma(word),
+                GpuDumpEntry::Gp1(word) => gpu.process_gp1_commands(word),
+                GpuDumpEntry::Vblank => break,
+            }
+        }
+
+        true
+    }
+}
+
+impl GPU {
+    /*
+      Starts recording a dump, with vram being the current contents of VRAM as 16-bit pixels. The
+      software renderer has them in GPU::vram, the hardware renderers have to read them back from
+      their textures first.
+    */
+    pub fn start_dump(&mut self, vram: Vec<u8>) -> Result<(), String> {
+        if vram.len() != VRAM_WIDTH * VRAM_HEIGHT * 2 {
+            return Err(format!(
+                "expected 0x{:x} bytes of VRAM, got 0x{:x}",
+                VRAM_WIDTH * VRAM_HEIGHT * 2,
+                vram.len()
+            ));
+        }
+
+        let mut dump = GpuDump::new(vram);
+
+        dump.entries = self.dump_prelude();
+
+        self.dump = Some(dump);
+
+        Ok(())
+    }
+
+    pub fn stop_dump(&mut self) -> Option<GpuDump> {
+        self.dump.take()
+    }
+
+    pub fn is_dumping(&self) -> bool {
+        self.dump.is_some()
+    }
+
+    #[inline(always)]
+    pub(super) fn record(&mut self, entry: GpuDumpEntry) {
+        if let Some(dump) = &mut self.dump {
+            dump.entries.push(entry);
+        }
+    }
+
+    // the commands that get a new GPU into the same state as this one, short of VRAM
+    fn dump_prelude(&self) -> Vec<GpuDumpEntry> {
+        let display_mode = self.horizontal_bits1
+            | ((self.display_height == 480) as u32) << 2
+            | (self.video_mode as u32) << 3
+            | (self.display_depth as u32) << 4
+            | (self.interlaced as u32) << 5
+            | self.horizontal_bit2 << 6
+            | (self.horizontal_flip as u32) << 7;
+
+        let mut entries = vec![
+            GpuDumpEntry::Gp1(0x0300_0000 | self.display_on as u32),
+            GpuDumpEntry::Gp1(0x0400_0000 | self.dma_direction as u32),
+            GpuDumpEntry::Gp1(0x0500_0000 | self.display_start_x | self.display_start_y << 10),
+            GpuDumpEntry::Gp1(0x0600_0000 | self.display_range_x.0 | self.display_range_x.1 << 12),
+            GpuDumpEntry::Gp1(0x0700_0000 | self.display_range_y.0 | self.display_range_y.1 << 10),
+            GpuDumpEntry::Gp1(0x0800_0000 | display_mode),
+            GpuDumpEntry::Gp0(0xe100_0000 | (self.texpage.value & 0xff_ffff)),
+            GpuDumpEntry::Gp0(
+                0xe200_0000
+                    | (self.texture_window_mask_x / 8)
+                    | (self.texture_window_mask_y / 8) << 5
+                    | (self.texture_window_offset_x / 8) << 10
+                    | (self.texture_window_offset_y / 8) << 15,
+            ),
+            GpuDumpEntry::Gp0(0xe300_0000 | self.x1 | self.y1 << 10),
+            GpuDumpEntry::Gp0(0xe400_0000 | self.x2 | self.y2 << 10),
+            GpuDumpEntry::Gp0(
+                0xe500_0000 | (self.x_offset as u32 & 0x7ff) | (self.y_offset as u32 & 0x7ff) << 11,
+            ),
+            GpuDumpEntry::Gp0(
+                0xe600_0000
+                    | self.force_mask_bit as u32
+                    | (self.preserve_masked_pixels as u32) << 1,
+            ),
+        ];
+
+        // whatever command the game was in the middle of sending
+        if self.transfer_type == Some(TransferType::ToVram) {
+            entries.extend(self.resume_transfer());
+        } else {
+            if self.is_polyline
+                && let (Some(vertex), Some(color)) =
+                    (self.previous_line_vertex, self.previous_line_color)
+            {
+                // the line the next vertex continues from
+                let x = (vertex.x - self.x_offset) as u32 & 0x7ff;
+                let y = (vertex.y - self.y_offset) as u32 & 0x7ff;
+
+                entries.push(GpuDumpEntry::Gp0(
+                    0x4800_0000
+                        | (self.is_shaded as u32) << 28
+                        | (self.is_semitransparent as u32) << 25
+                        | color.r as u32
+                        | (color.g as u32) << 8
+                        | (color.b as u32) << 16,
+                ));
+                entries.push(GpuDumpEntry::Gp0(x | y << 16));
+            }
+
+            entries.extend(
+                self.current_command_buffer
+                    .iter()
+                    .map(|&word| GpuDumpEntry::Gp0(word)),
+            );
+        }
+
+        entries
+    }
+
+    /*
+      A transfer has to be restarted with a new header, which rewrites the halfwords it already
+      got with the same values so the rest of it lines up. The words still to come hold two
+      halfwords each, so it goes back one more row when that's needed to keep them paired the
+      same way.
+    */
+    fn resume_transfer(&self) -> Vec<GpuDumpEntry> {
+        let first_row = if self.read_x % 2 == 1 {
+            self.read_y - 1
+        } else {
+            self.read_y
+        };
+
+        let count = ((self.read_y - first_row) * self.transfer_width + self.read_x) as usize;
+
+        #[cfg(feature = "software_gpu")]
+        let halfwords: Vec<u16> = (0..count as u32)
+            .map(|i| {
+                let x = (self.transfer_x + i % self.transfer_width) & 0x3ff;
+                let y = (self.transfer_y + first_row + i / self.transfer_width) & 0x1ff;
+
+                let address = GPU::get_vram_address(x, y);
+
+                u16::from_le_bytes([self.vram[address], self.vram[address + 1]])
+            })
+            .collect();
+
+        // the hardware renderers only get the transfer once all of it is there
+        #[cfg(not(feature = "software_gpu"))]
+        let halfwords =
+            self.vram_transfer_halfwords[self.vram_transfer_halfwords.len() - count..].to_vec();
+
+        let mut entries = vec![
+            GpuDumpEntry::Gp0(0xa000_0000),
+            GpuDumpEntry::Gp0(self.transfer_x | ((self.transfer_y + first_row) & 0x1ff) << 16),
+            GpuDumpEntry::Gp0(
+                (self.transfer_width & 0x3ff) | ((self.transfer_height - first_row) & 0x1ff) << 16,
+            ),
+        ];
+
+        entries.extend(
+            halfwords
+                .chunks_exact(2)
+                .map(|pair| GpuDumpEntry::Gp0(pair[0] as u32 | (pair[1] as u32) << 16)),
+        );
+
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_round_trip() {
+        let mut dump = GpuDump::new(vec![0x5a; VRAM_WIDTH * VRAM_HEIGHT * 2]);
+
+        dump.entries = vec![
+            GpuDumpEntry::Gp1(0x0800_0001),
+            GpuDumpEntry::Gp0(0x0200_0000),
+            GpuDumpEntry::Gp0Dma(0x1234),
+            GpuDumpEntry::Vblank,
+            GpuDumpEntry::Vblank,
+        ];
+
+        let loaded = GpuDump::from_bytes(&dump.to_bytes()).unwrap();
+
+        assert_eq!(loaded.vram, dump.vram);
+        assert_eq!(loaded.entries, dump.entries);
+        assert_eq!(loaded.frames(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_dumps() {
+        let bytes = GpuDump::new(vec![0; VRAM_WIDTH * VRAM_HEIGHT * 2]).to_bytes();
+
+        assert!(GpuDump::from_bytes(&bytes[..8]).is_err());
+        assert!(GpuDump::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut wrong_magic = bytes.clone();
+
+        wrong_magic[0] = b'X';
+
+        assert!(GpuDump::from_bytes(&wrong_magic).is_err());
+
+        let mut wrong_version = bytes.clone();
+
+        wrong_version[8] = 2;
+
+        assert_eq!(
+            GpuDump::from_bytes(&wrong_version).err().as_deref(),
+            Some("unsupported GPU dump version 2")
+        );
+
+        let small_vram = GpuDump::new(vec![0; 0x10]).to_bytes();
+
+        assert!(GpuDump::from_bytes(&small_vram).is_err());
+    }
+}
@@ -6,6 +6,7 @@ use crate::cpu::bus::gpu::{
     Color, DisplayDepth, GPU, Polygon, Semitransparency, Texpage, TexturePageColors, Vertex,
     deltas::Deltas,
 };
+use crate::log;
 
 #[derive(Serialize, Deserialize)]
 struct Coordinate2d {
@@ -565,14 +566,22 @@ impl Polygon {
             if let Some(slope) = p12_slope {
                 Self::get_boundary_from_slope(&self.vertices[1], slope, curr_point)
             } else {
-                println!("shouldn't happen: p01_slope and p12_slope are both None");
+                log!(
+                    Gpu,
+                    Error,
+                    "shouldn't happen: p01_slope and p12_slope are both None"
+                );
                 0
             }
         } else if p12_slope.is_none() {
             if let Some(slope) = p01_slope {
                 Self::get_boundary_from_slope(&self.vertices[0], slope, curr_point)
             } else {
-                println!("shouldn't happen: p01_slope and p12_slope are both None");
+                log!(
+                    Gpu,
+                    Error,
+                    "shouldn't happen: p01_slope and p12_slope are both None"
+                );
                 0
             }
         } else {
@@ -582,13 +591,21 @@ impl Polygon {
                 if let Some(slope) = p01_slope {
                     Self::get_boundary_from_slope(&self.vertices[0], slope, curr_point)
                 } else {
-                    println!("shouldn't happen: p01_slope is None but should be Some");
+                    log!(
+                        Gpu,
+                        Error,
+                        "shouldn't happen: p01_slope is None but should be Some"
+                    );
                     0
                 }
             } else if let Some(slope) = p12_slope {
                 Self::get_boundary_from_slope(&self.vertices[1], slope, curr_point)
             } else {
-                println!("shouldn't happen: p12_slope is None but should be Some");
+                log!(
+                    Gpu,
+                    Error,
+                    "shouldn't happen: p12_slope is None but should be Some"
+                );
                 0
             }
         };
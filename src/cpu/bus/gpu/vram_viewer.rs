@@ -0,0 +1,268 @@
+use std::io::Write;
+
+use super::{Color, Texpage, TexturePageColors, VRAM_HEIGHT, VRAM_WIDTH};
+
+// an RGBA8 image
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: Vec::with_capacity(width * height * 4),
+        }
+    }
+
+    fn push(&mut self, r: u8, g: u8, b: u8, a: u8) {
+        self.pixels.extend_from_slice(&[r, g, b, a]);
+    }
+
+    // texels that are 0x0000 don't get drawn, so those come out transparent
+    fn push15(&mut self, pixel: u16, transparent_black: bool) {
+        let color = Color::translate15bit_to_24(pixel);
+
+        let a = if transparent_black && pixel == 0 {
+            0
+        } else {
+            0xff
+        };
+
+        self.push(color.r, color.g, color.b, a);
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), String> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/*
+  Decodes VRAM into images, either as it is (every halfword a 15-bit pixel) or the way the GPU
+  would read a region of it: as a 4 or 8-bit texture looked up in a CLUT, or as 15 or 24-bit direct
+  color. Coordinates wrap around like they do on the GPU. Works on anything laid out like VRAM,
+  which includes the software renderer's vram and the hardware renderers' vram_read_tex.
+*/
+pub struct VramViewer<'a> {
+    vram: &'a [u8],
+}
+
+impl<'a> VramViewer<'a> {
+    pub fn new(vram: &'a [u8]) -> Result<Self, String> {
+        if vram.len() != VRAM_WIDTH * VRAM_HEIGHT * 2 {
+            return Err(format!(
+                "expected 0x{:x} bytes of VRAM, got 0x{:x}",
+                VRAM_WIDTH * VRAM_HEIGHT * 2,
+                vram.len()
+            ));
+        }
+
+        Ok(Self { vram })
+    }
+
+    fn halfword(&self, x: usize, y: usize) -> u16 {
+        let address = 2 * ((x % VRAM_WIDTH) + (y % VRAM_HEIGHT) * VRAM_WIDTH);
+
+        u16::from_le_bytes([self.vram[address], self.vram[address + 1]])
+    }
+
+    fn byte(&self, x: usize, y: usize) -> u8 {
+        let address = (x % (VRAM_WIDTH * 2)) + (y % VRAM_HEIGHT) * VRAM_WIDTH * 2;
+
+        self.vram[address]
+    }
+
+    pub fn full(&self) -> Image {
+        self.direct15(0, 0, VRAM_WIDTH, VRAM_HEIGHT)
+    }
+
+    pub fn direct15(&self, x: usize, y: usize, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height);
+
+        for row in y..y + height {
+            for column in x..x + width {
+                image.push15(self.halfword(column, row), false);
+            }
+        }
+
+        image
+    }
+
+    // x is in halfwords like any other VRAM coordinate, width is in 24-bit pixels
+    pub fn direct24(&self, x: usize, y: usize, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height);
+
+        for row in y..y + height {
+            for column in 0..width {
+                let byte = 2 * x + 3 * column;
+
+                image.push(
+                    self.byte(byte, row),
+                    self.byte(byte + 1, row),
+                    self.byte(byte + 2, row),
+                    0xff,
+                );
+            }
+        }
+
+        image
+    }
+
+    // the colors of a CLUT in a single row, 16 of them for 4-bit textures and 256 for 8-bit ones
+    pub fn palette(&self, clut: (usize, usize), colors: TexturePageColors) -> Image {
+        let len = match colors {
+            TexturePageColors::Bit4 => 16,
+            TexturePageColors::Bit8 => 256,
+            TexturePageColors::Bit15 => 0,
+        };
+
+        let mut image = Image::new(len, 1);
+
+        for i in 0..len {
+            image.push15(self.halfword(clut.0 + i, clut.1), true);
+        }
+
+        image
+    }
+
+    /*
+      Decodes width x height texels starting at (u, v) in the texture page, with the texpage and
+      CLUT as returned by GPU::parse_texpage and GPU::parse_clut. The CLUT is ignored for 15-bit
+      textures.
+    */
+    pub fn texture(
+        &self,
+        texpage: &Texpage,
+        clut: (usize, usize),
+        (u, v): (usize, usize),
+        width: usize,
+        height: usize,
+    ) -> Image {
+        let page_x = texpage.x_base as usize * 64;
+        let page_y = texpage.y_base1 as usize * 16;
+
+        let mut image = Image::new(width, height);
+
+        for row in v..v + height {
+            let y = page_y + row;
+
+            for column in u..u + width {
+                let pixel = match texpage.texture_page_colors {
+                    TexturePageColors::Bit4 => {
+                        let indices = self.halfword(page_x + column / 4, y);
+                        let index = (indices >> ((column % 4) * 4)) & 0xf;
+
+                        self.halfword(clut.0 + index as usize, clut.1)
+                    }
+                    TexturePageColors::Bit8 => {
+                        let index = self.byte(2 * page_x + column, y);
+
+                        self.halfword(clut.0 + index as usize, clut.1)
+                    }
+                    TexturePageColors::Bit15 => self.halfword(page_x + column, y),
+                };
+
+                image.push15(pixel, true);
+            }
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(vram: &mut [u8], x: usize, y: usize, value: u16) {
+        let address = 2 * (x + y * VRAM_WIDTH);
+
+        vram[address..address + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn rejects_buffers_that_arent_vram_sized() {
+        assert!(VramViewer::new(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn wraps_direct15_coordinates() {
+        let mut vram = vec![0; VRAM_WIDTH * VRAM_HEIGHT * 2];
+
+        set(&mut vram, 0, 0, 0x001f);
+
+        let viewer = VramViewer::new(&vram).unwrap();
+
+        assert_eq!(viewer.full().pixels[..4], [0xff, 0, 0, 0xff]);
+        assert_eq!(
+            viewer.direct15(VRAM_WIDTH, VRAM_HEIGHT, 1, 1).pixels,
+            [0xff, 0, 0, 0xff]
+        );
+    }
+
+    #[test]
+    fn decodes_direct24_pixels() {
+        let mut vram = vec![0; VRAM_WIDTH * VRAM_HEIGHT * 2];
+
+        vram[4..10].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let viewer = VramViewer::new(&vram).unwrap();
+
+        assert_eq!(
+            viewer.direct24(2, 0, 2, 1).pixels,
+            [1, 2, 3, 0xff, 4, 5, 6, 0xff]
+        );
+    }
+
+    #[test]
+    fn looks_4bit_texels_up_in_the_clut() {
+        let mut vram = vec![0; VRAM_WIDTH * VRAM_HEIGHT * 2];
+
+        // entry 0 stays black, which is transparent
+        set(&mut vram, 17, 1, 0x7c00);
+        set(&mut vram, 64, 0, 0x0010);
+
+        let mut texpage = Texpage::new();
+
+        texpage.x_base = 1;
+        texpage.texture_page_colors = TexturePageColors::Bit4;
+
+        let viewer = VramViewer::new(&vram).unwrap();
+
+        assert_eq!(
+            viewer.texture(&texpage, (16, 1), (0, 0), 2, 1).pixels,
+            [0, 0, 0, 0, 0, 0, 0xff, 0xff]
+        );
+        assert_eq!(
+            viewer.palette((16, 1), TexturePageColors::Bit4).pixels[..8],
+            [0, 0, 0, 0, 0, 0, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn writes_the_full_vram_as_a_png() {
+        let vram = vec![0; VRAM_WIDTH * VRAM_HEIGHT * 2];
+
+        let mut png = Vec::new();
+
+        VramViewer::new(&vram)
+            .unwrap()
+            .full()
+            .write_png(&mut png)
+            .unwrap();
+
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+    }
+}
@@ -1,6 +1,9 @@
 use std::{array::from_fn, collections::VecDeque, mem};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::log;
 
 #[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 enum OutputDepth {
@@ -102,6 +105,27 @@ impl Mdec {
         table
     }
 
+    pub fn inspect(&self) -> Value {
+        json!({
+            "status": format!("0x{:08x}", self.read_status()),
+            "command": self.command.map(|command| format!("0x{command:08x}")),
+            "halfwords_remaining": self.halfwords_remaining,
+            "in_fifo_len": self.in_fifo.len(),
+            "out_fifo_len": self.out_fifo.len(),
+            "dma_in_enable": self.dma_in_enable,
+            "dma_out_enable": self.dma_out_enable,
+            "output_depth": self.output_depth,
+            "signed": self.is_signed,
+            "bit15": self.output_bit15,
+            "with_color": self.with_color,
+            "current_block": self.current_block,
+            "q_scale": self.q_scale,
+            "block_status": self.block_status,
+            "luminance_quant_table": self.luminance_quant_table,
+            "color_quant_table": self.color_quant_table,
+        })
+    }
+
     pub fn read(&self, address: usize) -> u32 {
         match address {
             0x1f801824 => self.read_status(),
@@ -168,8 +192,10 @@ impl Mdec {
 
                             break;
                         } else {
-                            println!(
-                                "[WARN]: attempting to populate quant table but words remaining is non-zero"
+                            log!(
+                                Mdec,
+                                Warn,
+                                "attempting to populate quant table but words remaining is non-zero"
                             );
                             break;
                         }
@@ -182,8 +208,10 @@ impl Mdec {
 
                             break;
                         } else {
-                            println!(
-                                "[WARN]: attempting to populate scale table but words remaining is non-zero"
+                            log!(
+                                Mdec,
+                                Warn,
+                                "attempting to populate scale table but words remaining is non-zero"
                             );
                             break;
                         }
@@ -515,7 +543,7 @@ impl Mdec {
             | (self.is_signed as u32) << 24
             | (self.output_bit15 as u32) << 23
             | ((self.current_block as u32 + 4) % 6) << 16
-            | (self.halfwords_remaining - 1) as u32
+            | self.halfwords_remaining.wrapping_sub(1) as u32
     }
 
     fn write_control(&mut self, value: u32) {
@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
 use crate::cpu::bus::{
     peripherals::{
@@ -214,4 +215,19 @@ impl Peripherals {
     pub fn write_mode(&mut self, value: u16) {
         self.mode.write(value);
     }
+
+    pub fn inspect(&self) -> Value {
+        json!({
+            "stat": format!("0x{:04x}", self.read_stat()),
+            "ctrl": format!("0x{:04x}", self.ctrl.bits()),
+            "mode": format!("0x{:04x}", self.mode.read()),
+            "baudrate_timer": self.baudrate_timer,
+            "tx_fifo": self.tx_fifo,
+            "rx_fifo": self.rx_fifo,
+            "state": self.state,
+            "selected_peripheral": self.selected_peripheral,
+            "selected_controller": self.selected_controller,
+            "interrupt": self.interrupt,
+        })
+    }
 }
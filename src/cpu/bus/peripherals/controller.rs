@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::log;
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct Controller {
     state: usize,
@@ -99,7 +101,11 @@ impl Controller {
                     }
                     0x44 | 0x45 | 0x46 | 0x47 | 0x4c | 0x4d => 0xf3,
                     _ => {
-                        println!("[WARN]got unimplemented command 0x{command:x}, resetting state");
+                        log!(
+                            Sio,
+                            Warn,
+                            "got unimplemented command 0x{command:x}, resetting state"
+                        );
                         reset_state = true;
                         0xff
                     }
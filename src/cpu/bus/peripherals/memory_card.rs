@@ -2,6 +2,8 @@
 use memmap2::MmapMut;
 use serde::{Deserialize, Serialize};
 
+use crate::log;
+
 #[derive(Default, PartialEq, Serialize, Deserialize)]
 enum CardState {
     #[default]
@@ -87,7 +89,11 @@ impl MemoryCard {
                         self.step = 0;
                     }
                     _ => {
-                        println!("[WARN]: invalid byte received for memory card: 0x{command:x}");
+                        log!(
+                            Sio,
+                            Warn,
+                            "invalid byte received for memory card: 0x{command:x}"
+                        );
                         self.card_state = CardState::Idle;
                     }
                 }
@@ -299,6 +305,12 @@ impl MemoryCard {
         self.memory_file = memory_file;
     }
 
+    pub fn swap_memory_file(&mut self, other: &mut MemoryCard) {
+        #[cfg(not(target_arch = "wasm32"))]
+        std::mem::swap(&mut self.memory_file, &mut other.memory_file);
+        std::mem::swap(&mut self.memory_bytes, &mut other.memory_bytes);
+    }
+
     pub fn set_memory_bytes(&mut self, memory_bytes: Vec<u8>) {
         self.memory_bytes = Some(memory_bytes);
     }
@@ -10,6 +10,10 @@ impl SIOMode {
         Self { value: 0 }
     }
 
+    pub fn read(&self) -> u16 {
+        self.value
+    }
+
     pub fn write(&mut self, value: u16) {
         self.value = value;
     }
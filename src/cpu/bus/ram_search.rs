@@ -0,0 +1,329 @@
+use crate::cpu::watch::{WatchEntry, WatchFormat};
+
+use super::{
+    Bus,
+    cheats::{CheatCode, CheatEngine},
+};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SearchRegion {
+    MainRam,
+    Scratchpad,
+}
+
+impl SearchRegion {
+    pub fn base_address(&self) -> u32 {
+        match self {
+            SearchRegion::MainRam => 0x8000_0000,
+            SearchRegion::Scratchpad => 0x1f80_0000,
+        }
+    }
+
+    fn memory<'a>(&self, bus: &'a Bus) -> &'a [u8] {
+        match self {
+            SearchRegion::MainRam => &bus.main_ram,
+            SearchRegion::Scratchpad => &bus.scratchpad,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ValueType {
+    U8,
+    S8,
+    U16,
+    S16,
+    U32,
+    S32,
+}
+
+impl ValueType {
+    pub fn size(&self) -> usize {
+        match self {
+            ValueType::U8 | ValueType::S8 => 1,
+            ValueType::U16 | ValueType::S16 => 2,
+            ValueType::U32 | ValueType::S32 => 4,
+        }
+    }
+
+    pub fn read(&self, memory: &[u8], offset: usize) -> i64 {
+        let bytes = &memory[offset..offset + self.size()];
+
+        match self {
+            ValueType::U8 => bytes[0] as i64,
+            ValueType::S8 => bytes[0] as i8 as i64,
+            ValueType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            ValueType::S16 => i16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            ValueType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+            ValueType::S32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+        }
+    }
+}
+
+// the first four compare against a known value, the rest against the previous snapshot
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SearchCondition {
+    Equal(i64),
+    NotEqual(i64),
+    LessThan(i64),
+    GreaterThan(i64),
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+    ChangedBy(i64),
+}
+
+impl SearchCondition {
+    fn matches(&self, current: i64, previous: i64) -> bool {
+        match *self {
+            SearchCondition::Equal(value) => current == value,
+            SearchCondition::NotEqual(value) => current != value,
+            SearchCondition::LessThan(value) => current < value,
+            SearchCondition::GreaterThan(value) => current > value,
+            SearchCondition::Unchanged => current == previous,
+            SearchCondition::Changed => current != previous,
+            SearchCondition::Increased => current > previous,
+            SearchCondition::Decreased => current < previous,
+            SearchCondition::ChangedBy(delta) => current - previous == delta,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SearchResult {
+    pub address: u32,
+    pub region: SearchRegion,
+    pub value_type: ValueType,
+    pub value: i64,
+    pub previous: i64,
+}
+
+impl SearchResult {
+    // cheat codes can only address main RAM, 32 bit values take two 16 bit writes
+    pub fn to_cheat_codes(&self, value: i64) -> Option<Vec<CheatCode>> {
+        if self.region != SearchRegion::MainRam {
+            return None;
+        }
+
+        let address = self.address & 0x1f_ffff;
+
+        let codes = match self.value_type.size() {
+            1 => vec![CheatCode {
+                address: 0x3000_0000 | address,
+                value: value as u8 as u16,
+            }],
+            2 => vec![CheatCode {
+                address: 0x8000_0000 | address,
+                value: value as u16,
+            }],
+            _ => vec![
+                CheatCode {
+                    address: 0x8000_0000 | address,
+                    value: value as u16,
+                },
+                CheatCode {
+                    address: 0x8000_0000 | (address + 2),
+                    value: (value >> 16) as u16,
+                },
+            ],
+        };
+
+        Some(codes)
+    }
+
+    pub fn to_watch(&self, format: WatchFormat) -> WatchEntry {
+        WatchEntry::new(self.address, self.value_type, format)
+    }
+
+    pub fn to_freeze(&self, format: WatchFormat, value: i64) -> WatchEntry {
+        let mut entry = self.to_watch(format);
+        entry.frozen = Some(value);
+
+        entry
+    }
+
+    pub fn add_cheat(&self, cheats: &mut CheatEngine, name: &str, value: i64) -> Option<usize> {
+        self.to_cheat_codes(value)
+            .and_then(|codes| cheats.add_codes(name, codes).ok())
+    }
+}
+
+/*
+  Searches start out with every aligned address in the region as a candidate. Every call to
+  filter drops the candidates that don't match and takes a new snapshot to compare against,
+  so calling it across a few frames narrows things down to the address being looked for.
+*/
+pub struct RamSearch {
+    pub region: SearchRegion,
+    pub value_type: ValueType,
+    snapshot: Vec<u8>,
+    // what the snapshot was before the last filter, which results show as the previous value
+    previous_snapshot: Vec<u8>,
+    candidates: Vec<u32>,
+}
+
+impl RamSearch {
+    pub fn new(bus: &Bus, region: SearchRegion, value_type: ValueType) -> Self {
+        let mut search = Self {
+            region,
+            value_type,
+            snapshot: Vec::new(),
+            previous_snapshot: Vec::new(),
+            candidates: Vec::new(),
+        };
+
+        search.reset(bus);
+
+        search
+    }
+
+    pub fn reset(&mut self, bus: &Bus) {
+        let memory = self.region.memory(bus);
+        let size = self.value_type.size();
+
+        self.snapshot = memory.to_vec();
+        self.previous_snapshot = memory.to_vec();
+        self.candidates = (0..=memory.len() - size)
+            .step_by(size)
+            .map(|offset| offset as u32)
+            .collect();
+    }
+
+    pub fn filter(&mut self, bus: &Bus, condition: SearchCondition) -> usize {
+        let memory = self.region.memory(bus);
+        let value_type = self.value_type;
+        let snapshot = &self.snapshot;
+
+        self.candidates.retain(|&offset| {
+            let current = value_type.read(memory, offset as usize);
+            let previous = value_type.read(snapshot, offset as usize);
+
+            condition.matches(current, previous)
+        });
+
+        std::mem::swap(&mut self.snapshot, &mut self.previous_snapshot);
+        self.snapshot.copy_from_slice(memory);
+
+        self.candidates.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    pub fn results(&self, bus: &Bus, limit: usize) -> Vec<SearchResult> {
+        let memory = self.region.memory(bus);
+
+        self.candidates
+            .iter()
+            .take(limit)
+            .map(|&offset| SearchResult {
+                address: self.region.base_address() + offset,
+                region: self.region,
+                value_type: self.value_type,
+                value: self.value_type.read(memory, offset as usize),
+                previous: self
+                    .value_type
+                    .read(&self.previous_snapshot, offset as usize),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn narrows_down_changing_values() {
+        let mut bus = Bus::new();
+
+        bus.main_ram[0x100] = 10;
+        bus.main_ram[0x200] = 10;
+
+        let mut search = RamSearch::new(&bus, SearchRegion::MainRam, ValueType::U8);
+
+        assert_eq!(search.len(), 0x20_0000);
+        assert_eq!(search.filter(&bus, SearchCondition::Equal(10)), 2);
+
+        bus.main_ram[0x100] = 9;
+
+        assert_eq!(search.filter(&bus, SearchCondition::Decreased), 1);
+
+        let results = search.results(&bus, 10);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].address, 0x8000_0100);
+        assert_eq!((results[0].value, results[0].previous), (9, 10));
+
+        // the previous value only moves on with the next filter
+        bus.main_ram[0x100] = 6;
+
+        assert_eq!(search.results(&bus, 10)[0].previous, 10);
+        assert_eq!(search.filter(&bus, SearchCondition::ChangedBy(-3)), 1);
+        assert_eq!(search.results(&bus, 10)[0].previous, 9);
+        assert_eq!(search.filter(&bus, SearchCondition::Changed), 0);
+        assert!(search.is_empty());
+
+        search.reset(&bus);
+
+        assert_eq!(search.filter(&bus, SearchCondition::Unchanged), 0x20_0000);
+    }
+
+    #[test]
+    fn reads_signed_and_wide_values() {
+        let mut bus = Bus::new();
+
+        bus.scratchpad[0x10..0x14].copy_from_slice(&(-2i32).to_le_bytes());
+
+        let mut search = RamSearch::new(&bus, SearchRegion::Scratchpad, ValueType::S32);
+
+        assert_eq!(search.len(), 0x100);
+        assert_eq!(search.filter(&bus, SearchCondition::LessThan(0)), 1);
+
+        let result = search.results(&bus, 1)[0];
+
+        assert_eq!(result.address, 0x1f80_0010);
+        assert_eq!(result.value, -2);
+
+        // cheat codes can't reach the scratchpad
+        assert!(result.to_cheat_codes(5).is_none());
+        assert_eq!(ValueType::U16.read(&[0xfe, 0xff], 0), 0xfffe);
+        assert_eq!(ValueType::S16.read(&[0xfe, 0xff], 0), -2);
+    }
+
+    #[test]
+    fn turns_results_into_cheats() {
+        let result = SearchResult {
+            address: 0x8001_0000,
+            region: SearchRegion::MainRam,
+            value_type: ValueType::U32,
+            value: 0,
+            previous: 0,
+        };
+
+        assert_eq!(
+            result.to_cheat_codes(0x1234_5678).unwrap(),
+            [
+                CheatCode {
+                    address: 0x8001_0000,
+                    value: 0x5678
+                },
+                CheatCode {
+                    address: 0x8001_0002,
+                    value: 0x1234
+                },
+            ]
+        );
+
+        let mut cheats = CheatEngine::new();
+
+        assert_eq!(result.add_cheat(&mut cheats, "lives", 99), Some(0));
+        assert_eq!(cheats.cheats()[0].codes.len(), 2);
+    }
+}
@@ -93,6 +93,8 @@ impl Scheduler {
     }
 
     pub fn serialize_scheduler(&mut self) {
+        self.queue_serialized.clear();
+
         for (event_type, Reverse(cycles)) in self.queue.iter() {
             self.queue_serialized.insert(*event_type, *cycles);
         }
@@ -1,6 +1,7 @@
 use std::{array::from_fn, collections::VecDeque};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use spu_control_register::{SoundRamTransferMode, SpuControlRegister};
 use voice::Voice;
 
@@ -9,6 +10,8 @@ use crate::cpu::bus::{
     scheduler::{EventType, Scheduler},
     spu::{reverb::Reverb, spu_stat_register::SpuStatRegister},
 };
+use crate::cpu::inspector::summarize_memory;
+use crate::log;
 
 pub mod reverb;
 pub mod spu_control_register;
@@ -213,6 +216,10 @@ impl SPU {
         }
     }
 
+    pub(crate) fn sound_ram(&self) -> &[u8] {
+        &self.sound_ram.ram
+    }
+
     /*
     15-12 Unknown/Unused (seems to be usually zero)
     11    Writing to First/Second half of Capture Buffers (0=First, 1=Second)
@@ -227,6 +234,34 @@ impl SPU {
         self.spustat.bits()
     }
 
+    pub fn inspect(&self) -> Value {
+        let voices: Vec<Value> = self.voices.iter().map(|voice| voice.inspect()).collect();
+
+        json!({
+            "spucnt": format!("0x{:04x}", self.spucnt.bits()),
+            "spustat": format!("0x{:04x}", self.spustat.bits()),
+            "main_volume": (self.main_volume_left, self.main_volume_right),
+            "cd_volume": self.cd_volume,
+            "external_volume": self.external_volume,
+            "reverb_volume": self.reverb_volume,
+            "current_volume": self.current_volume,
+            "keyon": format!("0x{:06x}", self.keyon),
+            "keyoff": format!("0x{:06x}", self.keyoff),
+            "endx": format!("0x{:06x}", self.endx),
+            "pitch_modulation": format!("0x{:06x}", self.sound_modulation),
+            "noise": format!("0x{:06x}", self.noise_enable),
+            "echo": format!("0x{:06x}", self.echo_on),
+            "irq_address": format!("0x{:05x}", self.irq_address),
+            "transfer_address": format!("0x{:05x}", self.sound_ram_address),
+            "current_transfer_address": format!("0x{:05x}", self.current_ram_address),
+            "transfer_type": format!("0x{:04x}", self.sound_ram_transfer_type),
+            "fifo_len": self.sample_fifo.len(),
+            "reverb": self.reverb,
+            "voices": voices,
+            "sound_ram": summarize_memory(&self.sound_ram.ram),
+        })
+    }
+
     pub fn write_voices(&mut self, address: usize, value: u16) {
         let voice = (address >> 4) & 0x1f;
         let channel = address & 0xf;
@@ -443,7 +478,11 @@ impl SPU {
             0x1f80_1db6 => self.external_volume.1 = value,
             0x1f80_1dc0..=0x1f80_1dfe | 0x1f80_1da2 => self.reverb.write16(address, value),
             0x1f80_1e00..=0x1f801e5f => {
-                println!("[WARN]Writing to internal registers of SPU voices. should not happen.")
+                log!(
+                    Spu,
+                    Warn,
+                    "Writing to internal registers of SPU voices. should not happen."
+                )
             }
             _ => panic!("invalid address given to control spu control registers: 0x{address:x}"),
         }
@@ -1,5 +1,6 @@
 use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
 use crate::cpu::bus::{
     registers::interrupt_register::InterruptRegister,
@@ -554,6 +555,27 @@ impl Voice {
         }
     }
 
+    pub fn inspect(&self) -> Value {
+        let volume = |sweep: &VolumeSweep| {
+            json!({
+                "register": format!("0x{:04x}", sweep.register.bits()),
+                "level": sweep.current_level,
+            })
+        };
+
+        json!({
+            "start_address": format!("0x{:05x}", self.start_address),
+            "repeat_address": format!("0x{:05x}", self.repeat_address),
+            "current_address": format!("0x{:05x}", self.current_address),
+            "sample_rate": format!("0x{:04x}", self.sample_rate),
+            "pitch_counter": self.pitch_counter,
+            "left_volume": volume(&self.left_volume),
+            "right_volume": volume(&self.right_volume),
+            "adsr_volume": self.adsr_volume,
+            "adsr": self.adsr,
+        })
+    }
+
     pub fn write(&mut self, channel: usize, value: u16) {
         match channel {
             0x0 => {
@@ -1,5 +1,6 @@
 use counter_mode_register::CounterModeRegister;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 
 use super::registers::interrupt_register::InterruptRegister;
 
@@ -43,6 +44,20 @@ impl Timer {
         }
     }
 
+    pub fn inspect(&self) -> Value {
+        json!({
+            "counter": self.counter,
+            "target": self.counter_target,
+            "mode": format!("0x{:04x}", self.counter_register.bits()),
+            "flags": self.counter_register,
+            "sync_mode": self.counter_register.sync_mode(),
+            "clock_source": self.clock_source,
+            "active": self.is_active,
+            "in_blank": self.in_xblank,
+            "one_shot_fired": self.one_shot_fired,
+        })
+    }
+
     pub fn write_counter(&mut self, value: u32) {
         self.counter = value;
         self.one_shot_fired = false;
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::log;
+
+// output nobody has taken yet is capped so frontends that never read it don't grow it forever
+const MAX_OUTPUT_SIZE: usize = 0x10000;
+
+// Collects debug text printed by the guest, either through the kernel's putchar (B(3Dh))
+// or the expansion 2 DUART. Complete lines go to the kernel log channel and are buffered until a
+// frontend takes them.
+#[derive(Serialize, Deserialize)]
+pub struct Tty {
+    line: String,
+    output: String,
+}
+
+impl Default for Tty {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tty {
+    pub fn new() -> Self {
+        Self {
+            line: String::new(),
+            output: String::new(),
+        }
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        self.line.push(byte as char);
+
+        if byte == b'\n' {
+            self.flush();
+        }
+    }
+
+    pub fn write_str(&mut self, text: &str) {
+        for byte in text.bytes() {
+            self.write_byte(byte);
+        }
+    }
+
+    // separates output from before and after something like a reset, on a line of its own
+    pub fn write_marker(&mut self, text: &str) {
+        if !self.line.is_empty() {
+            self.write_byte(b'\n');
+        }
+
+        self.write_str(&format!("===== {text} =====\n"));
+    }
+
+    fn flush(&mut self) {
+        log!(Kernel, Info, "{}", self.line.trim_end_matches(['\r', '\n']));
+
+        self.output += &self.line;
+        self.line.clear();
+
+        if self.output.len() > MAX_OUTPUT_SIZE {
+            let mut cut = self.output.len() - MAX_OUTPUT_SIZE;
+
+            while !self.output.is_char_boundary(cut) {
+                cut += 1;
+            }
+
+            self.output.drain(..cut);
+        }
+    }
+
+    // returns all complete lines printed since the last call
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
@@ -35,6 +35,23 @@ impl CauseRegister {
     }
 }
 
+// DCIC bits, see https://psx-spx.consoledev.net/cpuspecifications/#cop0-debug-registers
+const DCIC_ANY_BREAK: u32 = 1 << 0;
+const DCIC_CODE_BREAK: u32 = 1 << 1;
+const DCIC_DATA_BREAK: u32 = 1 << 2;
+const DCIC_DATA_READ_BREAK: u32 = 1 << 3;
+const DCIC_DATA_WRITE_BREAK: u32 = 1 << 4;
+const DCIC_SUPER_MASTER_ENABLE1: u32 = 1 << 23;
+const DCIC_EXECUTION_BREAKPOINT: u32 = 1 << 24;
+const DCIC_DATA_BREAKPOINT: u32 = 1 << 25;
+const DCIC_BREAK_ON_READ: u32 = 1 << 26;
+const DCIC_BREAK_ON_WRITE: u32 = 1 << 27;
+const DCIC_MASTER_ENABLE: u32 = 1 << 30;
+const DCIC_SUPER_MASTER_ENABLE2: u32 = 1 << 31;
+
+// bits 6-11 and 16-22 always read as zero
+const DCIC_WRITE_MASK: u32 = 0xff80_f03f;
+
 #[derive(Serialize, Deserialize)]
 pub struct COP0 {
     pub sr: StatusRegister,
@@ -122,9 +139,13 @@ impl COP0 {
 
     pub fn mfc0(&self, index: usize) -> u32 {
         match index {
+            0x3 => self.bpc,
+            0x5 => self.bda,
             0x6 => self.tar,
             0x7 => self.dcic,
             0x8 => self.bad_addr,
+            0x9 => self.bdam,
+            0xb => self.bpcm,
             0xc => self.sr.bits(),
             0xd => self.cause.bits(),
             0xe => self.epc,
@@ -138,7 +159,7 @@ impl COP0 {
             0x3 => self.bpc = value,
             0x5 => self.bda = value,
             0x6 => (), // read only
-            0x7 => self.dcic = value,
+            0x7 => self.dcic = value & DCIC_WRITE_MASK,
             0x9 => self.bdam = value,
             0xb => self.bpcm = value,
             0xc => self.sr = StatusRegister::from_bits_retain(value),
@@ -150,4 +171,137 @@ impl COP0 {
     pub fn rfe(&mut self) {
         self.sr.return_from_exception();
     }
+
+    fn breakpoints_enabled(&self, enable_bit: u32) -> bool {
+        let enable =
+            DCIC_SUPER_MASTER_ENABLE1 | DCIC_SUPER_MASTER_ENABLE2 | DCIC_MASTER_ENABLE | enable_bit;
+
+        self.dcic & enable == enable
+    }
+
+    // BPCM selects which bits of the address get compared against BPC
+    pub fn check_execution_breakpoint(&mut self, pc: u32) -> bool {
+        if !self.breakpoints_enabled(DCIC_EXECUTION_BREAKPOINT) || (pc ^ self.bpc) & self.bpcm != 0
+        {
+            return false;
+        }
+
+        self.dcic |= DCIC_ANY_BREAK | DCIC_CODE_BREAK;
+
+        true
+    }
+
+    pub fn check_data_breakpoint(&mut self, address: u32, is_write: bool) -> bool {
+        let (enable_bit, status_bit) = if is_write {
+            (DCIC_BREAK_ON_WRITE, DCIC_DATA_WRITE_BREAK)
+        } else {
+            (DCIC_BREAK_ON_READ, DCIC_DATA_READ_BREAK)
+        };
+
+        if !self.breakpoints_enabled(DCIC_DATA_BREAKPOINT | enable_bit)
+            || (address ^ self.bda) & self.bdam != 0
+        {
+            return false;
+        }
+
+        self.dcic |= DCIC_ANY_BREAK | DCIC_DATA_BREAK | status_bit;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    const ALL_ENABLES: u32 =
+        DCIC_SUPER_MASTER_ENABLE1 | DCIC_SUPER_MASTER_ENABLE2 | DCIC_MASTER_ENABLE;
+
+    fn cpu_running(instruction: u32) -> CPU {
+        let mut cpu = CPU::new(None, String::new());
+
+        cpu.bus.mem_write32(0x8001_0000, instruction);
+        cpu.pc = 0x8001_0000;
+        cpu.next_pc = 0x8001_0004;
+
+        cpu
+    }
+
+    #[test]
+    fn masks_the_unused_dcic_bits() {
+        let mut cop0 = COP0::new();
+
+        cop0.mtc0(0x7, 0xffff_ffff);
+
+        assert_eq!(cop0.mfc0(0x7), 0xff80_f03f);
+    }
+
+    #[test]
+    fn compares_execution_breakpoints_through_bpcm() {
+        let mut cop0 = COP0::new();
+
+        cop0.bpc = 0x8001_0000;
+        cop0.bpcm = 0xffff_0000;
+        cop0.dcic = DCIC_EXECUTION_BREAKPOINT;
+
+        // every master enable has to be set as well
+        assert!(!cop0.check_execution_breakpoint(0x8001_0040));
+
+        cop0.dcic |= ALL_ENABLES;
+
+        assert!(!cop0.check_execution_breakpoint(0x8002_0000));
+        assert!(cop0.check_execution_breakpoint(0x8001_0040));
+        assert_eq!(cop0.dcic & 0x3f, DCIC_ANY_BREAK | DCIC_CODE_BREAK);
+    }
+
+    #[test]
+    fn only_breaks_on_the_enabled_data_access() {
+        let mut cop0 = COP0::new();
+
+        cop0.bda = 0x100;
+        cop0.bdam = 0xffff_ffff;
+        cop0.dcic = ALL_ENABLES | DCIC_DATA_BREAKPOINT | DCIC_BREAK_ON_WRITE;
+
+        assert!(!cop0.check_data_breakpoint(0x100, false));
+        assert!(!cop0.check_data_breakpoint(0x104, true));
+        assert!(cop0.check_data_breakpoint(0x100, true));
+        assert_eq!(
+            cop0.dcic & 0x3f,
+            DCIC_ANY_BREAK | DCIC_DATA_BREAK | DCIC_DATA_WRITE_BREAK
+        );
+    }
+
+    #[test]
+    fn enters_the_debug_handler_before_a_breakpointed_instruction() {
+        let mut cpu = cpu_running(0x2508_0001); // addiu t0, t0, 1
+
+        cpu.cop0.bpc = 0x8001_0000;
+        cpu.cop0.bpcm = 0xffff_ffff;
+        cpu.cop0.dcic = ALL_ENABLES | DCIC_EXECUTION_BREAKPOINT;
+
+        cpu.step();
+
+        assert_eq!(cpu.pc, 0x8000_0040);
+        assert_eq!(cpu.r[8], 0);
+        assert_eq!(cpu.cop0.epc, 0x8001_0000);
+        assert_eq!(cpu.cop0.cause.ex_code(), 0x9);
+    }
+
+    #[test]
+    fn enters_the_debug_handler_after_a_breakpointed_store() {
+        let mut cpu = cpu_running(0xac08_0100); // sw t0, 0x100(zero)
+
+        cpu.r[8] = 0x1234_5678;
+        cpu.cop0.bda = 0x100;
+        cpu.cop0.bdam = 0xffff_ffff;
+        cpu.cop0.dcic = ALL_ENABLES | DCIC_DATA_BREAKPOINT | DCIC_BREAK_ON_WRITE;
+
+        cpu.step();
+
+        assert_eq!(cpu.bus.mem_read32(0x100), 0x1234_5678);
+        assert_eq!(cpu.pc, 0x8000_0040);
+        assert_eq!(cpu.cop0.epc, 0x8001_0000);
+        assert_eq!(cpu.cop0.dcic & DCIC_DATA_WRITE_BREAK, DCIC_DATA_WRITE_BREAK);
+    }
 }
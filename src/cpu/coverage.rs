@@ -0,0 +1,321 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Write},
+};
+
+use super::{CPU, instructions::Instruction, symbols::SymbolTable};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BasicBlock {
+    pub start: u32,
+    // exclusive
+    pub end: u32,
+    // how many times the block was entered
+    pub hits: u64,
+}
+
+#[derive(Default)]
+struct LcovRecord {
+    functions: Vec<(u32, String, u64)>,
+    lines: BTreeMap<u32, u64>,
+}
+
+impl LcovRecord {
+    fn write(&self, lcov: &mut String, source: &str) {
+        let _ = writeln!(lcov, "TN:\nSF:{source}");
+
+        for (line, name, _) in &self.functions {
+            let _ = writeln!(lcov, "FN:{line},{name}");
+        }
+        for (_, name, hits) in &self.functions {
+            let _ = writeln!(lcov, "FNDA:{hits},{name}");
+        }
+
+        let functions_hit = self.functions.iter().filter(|(_, _, hits)| *hits > 0);
+
+        let _ = writeln!(lcov, "FNF:{}", self.functions.len());
+        let _ = writeln!(lcov, "FNH:{}", functions_hit.count());
+
+        for (line, hits) in &self.lines {
+            let _ = writeln!(lcov, "DA:{line},{hits}");
+        }
+
+        let lines_hit = self.lines.values().filter(|&&hits| hits > 0);
+
+        let _ = writeln!(lcov, "LF:{}", self.lines.len());
+        let _ = writeln!(lcov, "LH:{}", lines_hit.count());
+        let _ = writeln!(lcov, "end_of_record");
+    }
+}
+
+/*
+  Counts how many times every instruction ran. Basic blocks get split at any instruction that
+  was reached by something other than the previous instruction (branch targets, exception
+  vectors), and right after the delay slot of every branch, taken or not.
+*/
+#[derive(Default)]
+pub struct Coverage {
+    hits: HashMap<u32, u64>,
+    block_starts: HashSet<u32>,
+    last_pc: Option<u32>,
+    in_delay_slot: bool,
+    after_delay_slot: bool,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn record(&mut self, pc: u32, instruction: Instruction) {
+        let sequential = self
+            .last_pc
+            .is_some_and(|last_pc| last_pc.wrapping_add(4) == pc);
+
+        if !sequential || self.after_delay_slot {
+            self.block_starts.insert(pc);
+        }
+
+        *self.hits.entry(pc).or_insert(0) += 1;
+
+        self.after_delay_slot = self.in_delay_slot;
+        self.in_delay_slot = instruction.is_branch();
+        self.last_pc = Some(pc);
+    }
+
+    pub fn hits(&self, address: u32) -> u64 {
+        self.hits.get(&address).copied().unwrap_or(0)
+    }
+
+    // number of distinct addresses that ran
+    pub fn len(&self) -> usize {
+        self.hits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    pub fn addresses(&self) -> BTreeMap<u32, u64> {
+        self.hits
+            .iter()
+            .map(|(&address, &hits)| (address, hits))
+            .collect()
+    }
+
+    pub fn basic_blocks(&self) -> Vec<BasicBlock> {
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+
+        for (address, hits) in self.addresses() {
+            match blocks.last_mut() {
+                Some(block) if block.end == address && !self.block_starts.contains(&address) => {
+                    block.end += 4;
+                }
+                _ => blocks.push(BasicBlock {
+                    start: address,
+                    end: address.wrapping_add(4),
+                    hits,
+                }),
+            }
+        }
+
+        blocks
+    }
+
+    /*
+      Lines come from the line map, with every line getting the highest count of the instructions
+      it compiled to. Functions without line numbers get a record of their own, named after the
+      function with a "line" per instruction. Those are only the ones something ran in, since
+      there's no telling the rest apart from data.
+    */
+    pub fn to_lcov(&self, symbols: &SymbolTable) -> Result<String, String> {
+        let mut records: BTreeMap<String, LcovRecord> = BTreeMap::new();
+
+        for (range, file, line) in symbols.line_ranges() {
+            let hits = range
+                .step_by(4)
+                .map(|address| self.hits(address))
+                .max()
+                .unwrap_or(0);
+
+            let count = records
+                .entry(file.to_string())
+                .or_default()
+                .lines
+                .entry(line)
+                .or_insert(0);
+
+            *count = (*count).max(hits);
+        }
+
+        if symbols.is_empty() && records.is_empty() {
+            return Err("no symbols or line numbers to map coverage to".to_string());
+        }
+
+        for symbol in symbols.symbols() {
+            let hits = self.hits(symbol.address);
+
+            if let Some((file, line)) = symbols.line_at(symbol.address) {
+                records
+                    .entry(file.to_string())
+                    .or_default()
+                    .functions
+                    .push((line, symbol.name.clone(), hits));
+
+                continue;
+            }
+
+            let addresses: Vec<u32> = symbols.range_of(symbol).step_by(4).collect();
+
+            if addresses.iter().all(|&address| self.hits(address) == 0) {
+                continue;
+            }
+
+            let record = records.entry(symbol.name.clone()).or_default();
+
+            record.functions.push((1, symbol.name.clone(), hits));
+
+            for (index, address) in addresses.into_iter().enumerate() {
+                record.lines.insert(index as u32 + 1, self.hits(address));
+            }
+        }
+
+        let mut lcov = String::new();
+
+        for (source, record) in &mut records {
+            record.functions.sort_by_key(|(line, _, _)| *line);
+            record.write(&mut lcov, source);
+        }
+
+        Ok(lcov)
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in self.basic_blocks() {
+            writeln!(f, "{:08x}-{:08x} {}", block.start, block.end, block.hits)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CPU {
+    pub fn start_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn stop_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOP: Instruction = Instruction(0);
+    const BEQ: Instruction = Instruction(0x1000_0040); // beq zero, zero, +0x100
+
+    // a loop at 0x100 that falls through once and then branches to 0x200
+    fn looped() -> Coverage {
+        let mut coverage = Coverage::new();
+
+        for (pc, instruction) in [
+            (0x100, NOP),
+            (0x104, BEQ),
+            (0x108, NOP),
+            (0x10c, NOP),
+            (0x100, NOP),
+            (0x104, BEQ),
+            (0x108, NOP),
+            (0x200, NOP),
+        ] {
+            coverage.record(pc, instruction);
+        }
+
+        coverage
+    }
+
+    #[test]
+    fn splits_basic_blocks_after_delay_slots_and_at_jump_targets() {
+        let coverage = looped();
+
+        assert_eq!(coverage.len(), 5);
+        assert_eq!(coverage.hits(0x104), 2);
+        assert_eq!(coverage.hits(0x300), 0);
+        assert_eq!(
+            coverage.basic_blocks(),
+            [
+                BasicBlock {
+                    start: 0x100,
+                    end: 0x10c,
+                    hits: 2
+                },
+                BasicBlock {
+                    start: 0x10c,
+                    end: 0x110,
+                    hits: 1
+                },
+                BasicBlock {
+                    start: 0x200,
+                    end: 0x204,
+                    hits: 1
+                },
+            ]
+        );
+        assert_eq!(
+            coverage.to_string(),
+            "00000100-0000010c 2\n0000010c-00000110 1\n00000200-00000204 1\n"
+        );
+    }
+
+    #[test]
+    fn writes_lcov_for_functions_that_ran() {
+        let coverage = looped();
+        let mut symbols = SymbolTable::new();
+
+        assert!(coverage.to_lcov(&symbols).is_err());
+
+        symbols.add("main", 0x100, Some(0x10));
+        symbols.add("unused", 0x300, Some(0x8));
+
+        let lcov = coverage.to_lcov(&symbols).unwrap();
+
+        assert!(lcov.contains("SF:main\n"));
+        assert!(lcov.contains("FNDA:2,main\n"));
+        assert!(lcov.contains("DA:3,2\nDA:4,1\n"));
+        assert!(lcov.contains("LF:4\nLH:4\n"));
+        assert!(!lcov.contains("unused"));
+    }
+
+    #[test]
+    fn only_counts_while_started() {
+        let mut cpu = CPU::new(None, String::new());
+
+        cpu.bus.mem_write32(0x8001_0000, 0x2508_0001); // addiu t0, t0, 1
+        cpu.pc = 0x8001_0000;
+        cpu.next_pc = 0x8001_0004;
+
+        cpu.start_coverage();
+        cpu.step();
+
+        assert_eq!(cpu.coverage().unwrap().hits(0x8001_0000), 1);
+
+        let coverage = cpu.stop_coverage().unwrap();
+
+        cpu.step();
+
+        assert!(cpu.coverage().is_none());
+        assert_eq!(coverage.len(), 1);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    backtrace::Backtrace,
+    cmp::Reverse,
+    fmt::Write,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::Mutex,
+};
+
+use super::{
+    CPU, debugger::condition::REGISTER_NAMES, disassembler::disassemble_with_symbols, logger,
+};
+
+// how many of the last executed instructions get kept around for a crash report
+pub const RECENT_INSTRUCTIONS: usize = 64;
+
+static LAST_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+// a ring of (pc, opcode) pairs that gets written to on every instruction, so it never allocates
+pub struct RecentInstructions {
+    entries: [(u32, u32); RECENT_INSTRUCTIONS],
+    next: usize,
+    len: usize,
+}
+
+impl RecentInstructions {
+    pub fn new() -> Self {
+        Self {
+            entries: [(0, 0); RECENT_INSTRUCTIONS],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, pc: u32, opcode: u32) {
+        self.entries[self.next] = (pc, opcode);
+        self.next = (self.next + 1) % RECENT_INSTRUCTIONS;
+        self.len = (self.len + 1).min(RECENT_INSTRUCTIONS);
+    }
+
+    // oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &(u32, u32)> {
+        let start = (self.next + RECENT_INSTRUCTIONS - self.len) % RECENT_INSTRUCTIONS;
+
+        (0..self.len).map(move |i| &self.entries[(start + i) % RECENT_INSTRUCTIONS])
+    }
+}
+
+impl Default for RecentInstructions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/*
+  Keeps the message, location and backtrace of every panic around until a frontend that caught it
+  takes them for a crash report. The previous hook still runs afterwards, so the panic gets printed
+  like it normally would.
+*/
+pub fn install_panic_hook() {
+    let previous_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let message = format!("{info}\n\nbacktrace:\n{}", Backtrace::force_capture());
+
+        if let Ok(mut last_panic) = LAST_PANIC.lock() {
+            *last_panic = Some(message);
+        }
+
+        previous_hook(info);
+    }));
+}
+
+pub fn take_panic_message() -> Option<String> {
+    LAST_PANIC.lock().ok()?.take()
+}
+
+impl CPU {
+    #[inline(always)]
+    pub(crate) fn record_recent_instruction(&mut self, opcode: u32) {
+        self.recent_instructions.push(self.previous_pc, opcode);
+    }
+
+    // everything about the state of the console that's useful to figure out what went wrong
+    pub fn crash_report(&self, message: &str) -> String {
+        let mut report = String::new();
+
+        let _ = writeln!(report, "=== panic ===\n{message}\n");
+
+        let _ = writeln!(report, "=== cpu ===");
+        let _ = writeln!(
+            report,
+            "pc: 0x{:08x} previous pc: 0x{:08x} next pc: 0x{:08x}",
+            self.pc, self.previous_pc, self.next_pc
+        );
+
+        for (i, name) in REGISTER_NAMES.iter().enumerate() {
+            let _ = write!(report, "{name:>4}: 0x{:08x}", self.r[i]);
+
+            report.push(if i % 4 == 3 { '\n' } else { ' ' });
+        }
+
+        let _ = writeln!(report, "  hi: 0x{:08x}   lo: 0x{:08x}", self.hi, self.lo);
+        let _ = writeln!(
+            report,
+            "in delay slot: {} instructions executed: {}\n",
+            self.in_delay_slot, self.instruction_count
+        );
+
+        let cop0 = &self.cop0;
+
+        let _ = writeln!(report, "=== cop0 ===");
+        let _ = writeln!(
+            report,
+            "sr: 0x{:08x} cause: 0x{:08x} epc: 0x{:08x} badvaddr: 0x{:08x}",
+            cop0.sr.bits(),
+            cop0.cause.bits(),
+            cop0.epc,
+            cop0.bad_addr
+        );
+        let _ = writeln!(
+            report,
+            "dcic: 0x{:08x} bpc: 0x{:08x} bpcm: 0x{:08x} bda: 0x{:08x} bdam: 0x{:08x} tar: 0x{:08x}\n",
+            cop0.dcic, cop0.bpc, cop0.bpcm, cop0.bda, cop0.bdam, cop0.tar
+        );
+
+        let _ = writeln!(report, "=== last instructions ===");
+
+        for &(pc, opcode) in self.recent_instructions.iter() {
+            let symbol = self
+                .symbols
+                .describe(pc)
+                .map(|name| format!(" <{name}>"))
+                .unwrap_or_default();
+
+            let _ = writeln!(
+                report,
+                "0x{pc:08x}{symbol}: {opcode:08x} {}",
+                disassemble_with_symbols(opcode, pc.wrapping_add(4), &self.symbols, None)
+            );
+        }
+
+        let scheduler = &self.bus.scheduler;
+
+        let mut events: Vec<_> = scheduler
+            .queue
+            .iter()
+            .map(|(event_type, Reverse(cycles))| (*event_type, *cycles))
+            .collect();
+
+        events.sort_by_key(|(_, cycles)| *cycles);
+
+        let _ = writeln!(report, "\n=== scheduler ===");
+        let _ = writeln!(report, "cycles: {}", scheduler.cycles);
+
+        for (event_type, cycles) in events {
+            let _ = writeln!(
+                report,
+                "{event_type:?} at {cycles} (in {})",
+                cycles as i64 - scheduler.cycles as i64
+            );
+        }
+
+        let _ = writeln!(report, "\n=== interrupts ===");
+        let _ = writeln!(
+            report,
+            "I_STAT: 0x{:04x} I_MASK: 0x{:04x}\n",
+            self.bus.interrupt_stat.bits(),
+            self.bus.interrupt_mask.bits()
+        );
+
+        let _ = writeln!(report, "=== gpu ===");
+        let _ = writeln!(report, "GPUSTAT: 0x{:08x}\n", self.bus.gpu.read_stat());
+
+        let _ = writeln!(report, "=== cd-rom ===");
+        let _ = writeln!(report, "{}\n", self.bus.cdrom.status_summary());
+
+        let _ = writeln!(report, "=== spu ===");
+        let _ = writeln!(
+            report,
+            "SPUCNT: 0x{:04x} SPUSTAT: 0x{:04x}",
+            self.bus.spu.read16(0x1f80_1daa),
+            self.bus.spu.read_stat()
+        );
+
+        report
+    }
+
+    /*
+      Writes a directory that can be attached to a bug report, with the report itself, a save state,
+      whatever the guest printed to the tty and the last messages in the log. The panic could have left the CPU in the middle of
+      an instruction, so the save state is only a best effort: loading it continues from where the
+      crash happened rather than from before it.
+    */
+    pub fn write_crash_bundle(&mut self, dir: &Path, message: &str) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let mut report = self.crash_report(message);
+
+        self.bus.scheduler.serialize_scheduler();
+
+        let save_state = panic::catch_unwind(AssertUnwindSafe(|| self.create_save_state()));
+
+        match save_state {
+            Ok((data, _)) => fs::write(dir.join("savestate.bin"), data)?,
+            Err(_) => {
+                let e = take_panic_message().unwrap_or_default();
+
+                let _ = writeln!(report, "\ncouldn't create a save state: {e}");
+            }
+        }
+
+        fs::write(dir.join("report.txt"), report)?;
+        fs::write(dir.join("tty.log"), self.bus.tty.take_output())?;
+
+        let log: String = logger::entries()
+            .iter()
+            .map(|entry| format!("{entry}\n"))
+            .collect();
+
+        fs::write(dir.join("log.txt"), log)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_a_bundle_for_a_reserved_instruction() {
+        let mut cpu = CPU::new(None, String::new());
+
+        cpu.bus.mem_write32(0x8001_0000, 0xffff_ffff);
+        cpu.pc = 0x8001_0000;
+        cpu.next_pc = 0x8001_0004;
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| cpu.step()));
+
+        assert!(result.is_err());
+
+        let dir = std::env::temp_dir().join(format!("rsx-crash-test-{}", std::process::id()));
+
+        cpu.write_crash_bundle(&dir, "invalid instruction").unwrap();
+
+        let report = fs::read_to_string(dir.join("report.txt")).unwrap();
+
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(report.contains("0x80010000: ffffffff UNKNOWN 0xffffffff"));
+    }
+}
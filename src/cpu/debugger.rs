@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use condition::Condition;
+use history::History;
+
+use super::{CPU, bus::Bus};
+
+pub mod condition;
+pub mod history;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AccessKind {
+    Read,
+    Write,
+    // either reads or writes
+    Access,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StopReason {
+    Breakpoint { address: u32 },
+    Watchpoint { address: u32, kind: AccessKind },
+    Step,
+    Interrupted,
+    // reverse execution ran back to the oldest snapshot without hitting anything
+    HistoryStart,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Watchpoint {
+    pub address: u32,
+    pub len: u32,
+    pub kind: AccessKind,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: u32, size: u32, kind: AccessKind) -> bool {
+        let start = Bus::physical_address(self.address);
+
+        (self.kind == AccessKind::Access || self.kind == kind)
+            && address < start + self.len
+            && start < address + size
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub address: u32,
+    pub condition: Option<Condition>,
+}
+
+/*
+  Breakpoints and watchpoints are checked by the CPU itself rather than by patching memory,
+  so they work in ROM and don't show up in memory reads. Once halted, CPU::step returns
+  without executing anything until the debugger gets resumed.
+*/
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: HashMap<u32, Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    halted: bool,
+    stop_reason: Option<StopReason>,
+    pending_stop: Option<StopReason>,
+    skip_breakpoint: Option<u32>,
+    single_step: bool,
+    stepped: bool,
+    history: History,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u32) {
+        self.breakpoints.insert(
+            Bus::physical_address(address),
+            Breakpoint {
+                address,
+                condition: None,
+            },
+        );
+    }
+
+    // the breakpoint only stops execution when the condition evaluates to true
+    pub fn add_conditional_breakpoint(
+        &mut self,
+        address: u32,
+        condition: &str,
+    ) -> Result<(), String> {
+        let condition = Condition::parse(condition)?;
+
+        self.breakpoints.insert(
+            Bus::physical_address(address),
+            Breakpoint {
+                address,
+                condition: Some(condition),
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn has_breakpoint(&self, address: u32) -> bool {
+        self.breakpoints
+            .contains_key(&Bus::physical_address(address))
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u32) {
+        self.breakpoints.remove(&Bus::physical_address(address));
+    }
+
+    pub fn add_watchpoint(&mut self, address: u32, len: u32, kind: AccessKind) {
+        self.watchpoints.push(Watchpoint { address, len, kind });
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u32, len: u32, kind: AccessKind) {
+        self.watchpoints
+            .retain(|watchpoint| *watchpoint != Watchpoint { address, len, kind });
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.values()
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    pub fn halt(&mut self, reason: StopReason) {
+        self.halted = true;
+        self.stop_reason = Some(reason);
+        self.pending_stop = None;
+        self.single_step = false;
+        self.stepped = false;
+    }
+
+    // pc is where execution picks back up, a breakpoint there doesn't fire again straight away
+    pub fn resume(&mut self, pc: u32) {
+        self.halted = false;
+        self.stop_reason = None;
+        self.skip_breakpoint = Some(Bus::physical_address(pc));
+    }
+
+    pub fn step(&mut self, pc: u32) {
+        self.resume(pc);
+        self.single_step = true;
+    }
+
+    pub(crate) fn start_frame(&mut self) {
+        self.history.start_frame();
+    }
+
+    // drops every snapshot taken so far, reverse execution stays enabled if it was
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn has_watchpoints(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    // handles everything but breakpoints, which need the CPU to evaluate their conditions
+    fn should_stop(&mut self) -> bool {
+        if self.halted {
+            return true;
+        }
+
+        if let Some(reason) = self.pending_stop.take() {
+            self.halt(reason);
+            return true;
+        }
+
+        if self.single_step {
+            if self.stepped {
+                self.halt(StopReason::Step);
+                return true;
+            }
+
+            self.stepped = true;
+        }
+
+        false
+    }
+
+    // called by the CPU loads and stores, the stop happens once the instruction finishes
+    pub(crate) fn check_access(&mut self, address: u32, size: u32, kind: AccessKind) {
+        let physical = Bus::physical_address(address);
+
+        if let Some(watchpoint) = self
+            .watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.matches(physical, size, kind))
+        {
+            self.pending_stop = Some(StopReason::Watchpoint {
+                address: watchpoint.address,
+                kind: watchpoint.kind,
+            });
+        }
+    }
+}
+
+impl CPU {
+    // called at the start of every step, returns true if the instruction at pc shouldn't run
+    pub(crate) fn check_debugger(&mut self) -> bool {
+        let debugger = &mut self.debugger;
+
+        if !debugger.halted
+            && debugger.pending_stop.is_none()
+            && !debugger.single_step
+            && debugger.skip_breakpoint.is_none()
+            && debugger.breakpoints.is_empty()
+        {
+            return false;
+        }
+
+        if debugger.should_stop() {
+            return true;
+        }
+
+        if debugger.skip_breakpoint.take() == Some(Bus::physical_address(self.pc)) {
+            return false;
+        }
+
+        let hit = self.breakpoint_hit();
+
+        if hit {
+            self.debugger
+                .halt(StopReason::Breakpoint { address: self.pc });
+        }
+
+        hit
+    }
+
+    fn breakpoint_hit(&self) -> bool {
+        match self
+            .debugger
+            .breakpoints
+            .get(&Bus::physical_address(self.pc))
+        {
+            Some(breakpoint) => breakpoint
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.evaluate(self)),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use crate::cpu::CPU;
+
+pub(crate) const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+    "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp",
+    "ra",
+];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+impl Operator {
+    fn apply(&self, left: u32, right: u32) -> u32 {
+        match self {
+            Operator::Or => (left != 0 || right != 0) as u32,
+            Operator::And => (left != 0 && right != 0) as u32,
+            Operator::Equal => (left == right) as u32,
+            Operator::NotEqual => (left != right) as u32,
+            Operator::Less => (left < right) as u32,
+            Operator::LessEqual => (left <= right) as u32,
+            Operator::Greater => (left > right) as u32,
+            Operator::GreaterEqual => (left >= right) as u32,
+            Operator::BitOr => left | right,
+            Operator::BitAnd => left & right,
+            Operator::Add => left.wrapping_add(right),
+            Operator::Sub => left.wrapping_sub(right),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Expression {
+    Constant(u32),
+    Register(usize),
+    Hi,
+    Lo,
+    Pc,
+    Memory {
+        size: u32,
+        address: Box<Expression>,
+    },
+    Not(Box<Expression>),
+    Binary {
+        operator: Operator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+}
+
+impl Expression {
+    pub fn evaluate(&self, cpu: &CPU) -> u32 {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Register(index) => cpu.r.0[*index],
+            Expression::Hi => cpu.hi,
+            Expression::Lo => cpu.lo,
+            Expression::Pc => cpu.pc,
+            Expression::Memory { size, address } => {
+                let address = address.evaluate(cpu);
+
+                // unreadable bytes (ie I/O ports) read as 0 so conditions never have side effects
+                (0..*size).fold(0, |acc, i| {
+                    let byte = cpu.bus.peek8(address.wrapping_add(i)).unwrap_or(0);
+                    acc | (byte as u32) << (i * 8)
+                })
+            }
+            Expression::Not(expression) => (expression.evaluate(cpu) == 0) as u32,
+            Expression::Binary {
+                operator,
+                left,
+                right,
+            } => operator.apply(left.evaluate(cpu), right.evaluate(cpu)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(u32),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "&", "|", "+", "-", "!", "(", ")", "[", "]", "$",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            // "$" is an optional register prefix, ie $a0
+            if *symbol != "$" {
+                tokens.push(Token::Symbol(symbol));
+            }
+            rest = &rest[symbol.len()..];
+        } else {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+
+            if len == 0 {
+                return Err(format!("unexpected character in condition: {rest}"));
+            }
+
+            let word = &rest[..len];
+
+            let number = if let Some(hex) = word.strip_prefix("0x") {
+                Some(u32::from_str_radix(hex, 16))
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                Some(word.parse::<u32>())
+            } else {
+                None
+            };
+
+            match number {
+                Some(Ok(value)) => tokens.push(Token::Number(value)),
+                Some(Err(_)) => return Err(format!("invalid number in condition: {word}")),
+                None => tokens.push(Token::Identifier(word.to_lowercase())),
+            }
+
+            rest = &rest[len..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+// lowest to highest precedence, unary operators and operands bind tighter than all of these
+const PRECEDENCE: [&[(&str, Operator)]; 6] = [
+    &[("||", Operator::Or)],
+    &[("&&", Operator::And)],
+    &[
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessEqual),
+        (">=", Operator::GreaterEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+    ],
+    &[("|", Operator::BitOr)],
+    &[("&", Operator::BitAnd)],
+    &[("+", Operator::Add), ("-", Operator::Sub)],
+];
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            _ => Err(format!("expected '{symbol}' in condition")),
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expression, String> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+
+        while let Some(Token::Symbol(symbol)) = self.peek() {
+            let Some((_, operator)) = PRECEDENCE[level].iter().find(|(s, _)| s == symbol) else {
+                break;
+            };
+
+            let operator = *operator;
+
+            self.position += 1;
+
+            let right = self.parse_binary(level + 1)?;
+
+            left = Expression::Binary {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Symbol("!")) => Ok(Expression::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Symbol("(")) => {
+                let expression = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Some(Token::Symbol("[")) => self.parse_memory(4),
+            Some(Token::Number(value)) => Ok(Expression::Constant(value)),
+            Some(Token::Identifier(name)) => self.parse_identifier(&name),
+            _ => Err("unexpected end of condition".to_string()),
+        }
+    }
+
+    // [address] reads a word, byte[address] and half[address] read smaller values
+    fn parse_memory(&mut self, size: u32) -> Result<Expression, String> {
+        let address = self.parse_binary(0)?;
+        self.expect("]")?;
+
+        Ok(Expression::Memory {
+            size,
+            address: Box::new(address),
+        })
+    }
+
+    fn parse_identifier(&mut self, name: &str) -> Result<Expression, String> {
+        let size = match name {
+            "byte" => Some(1),
+            "half" => Some(2),
+            "word" => Some(4),
+            _ => None,
+        };
+
+        if let Some(size) = size {
+            self.expect("[")?;
+            return self.parse_memory(size);
+        }
+
+        match name {
+            "hi" => return Ok(Expression::Hi),
+            "lo" => return Ok(Expression::Lo),
+            "pc" => return Ok(Expression::Pc),
+            "s8" => return Ok(Expression::Register(30)),
+            _ => (),
+        }
+
+        if let Some(index) = REGISTER_NAMES.iter().position(|register| *register == name) {
+            return Ok(Expression::Register(index));
+        }
+
+        match name.strip_prefix('r').map(|index| index.parse::<usize>()) {
+            Some(Ok(index)) if index < 32 => Ok(Expression::Register(index)),
+            _ => Err(format!("unknown register in condition: {name}")),
+        }
+    }
+}
+
+/*
+  Conditions are C-like expressions over registers and memory, ie:
+  a0 == 0x10 && byte[sp + 4] != 0
+  Values are unsigned 32 bit, and the condition is true when it evaluates to anything but 0.
+*/
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub source: String,
+    expression: Expression,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+
+        let expression = parser.parse_binary(0)?;
+
+        if parser.position != parser.tokens.len() {
+            return Err(format!("unexpected trailing input in condition: {source}"));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            expression,
+        })
+    }
+
+    pub fn evaluate(&self, cpu: &CPU) -> bool {
+        self.expression.evaluate(cpu) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str, cpu: &CPU) -> bool {
+        Condition::parse(source).unwrap().evaluate(cpu)
+    }
+
+    #[test]
+    fn follows_operator_precedence() {
+        let cpu = CPU::new(None, String::new());
+
+        assert!(evaluate("1 + 2 == 3 && !0", &cpu));
+        assert!(evaluate("0 || 2 > 1", &cpu));
+        assert!(evaluate("6 & 3 | 8 == 10", &cpu));
+        assert!(evaluate("(1 + 1) - 2 == 0", &cpu));
+        assert!(!evaluate("0 - 1 < 1", &cpu));
+    }
+
+    #[test]
+    fn reads_registers() {
+        let mut cpu = CPU::new(None, String::new());
+
+        cpu.r.0[4] = 0x10;
+        cpu.r.0[30] = 5;
+        cpu.hi = 7;
+
+        assert!(evaluate("a0 == 0x10", &cpu));
+        assert!(evaluate("$a0 == 16", &cpu));
+        assert!(evaluate("r4 == 16 && A0 == 16", &cpu));
+        assert!(evaluate("s8 == fp && fp == 5", &cpu));
+        assert!(evaluate("hi == 7 && lo == 0", &cpu));
+        assert!(evaluate("pc == 0xbfc00000", &cpu));
+    }
+
+    #[test]
+    fn reads_memory() {
+        let mut cpu = CPU::new(None, String::new());
+
+        cpu.bus.main_ram[0x100..0x104].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        cpu.r.0[29] = 0x8000_00fc;
+
+        assert!(evaluate("[0x80000100] == 0x12345678", &cpu));
+        assert!(evaluate("word[0x100] == 0x12345678", &cpu));
+        assert!(evaluate("half[sp + 4] == 0x5678", &cpu));
+        assert!(evaluate("byte[sp + 7] == 0x12", &cpu));
+
+        // I/O ports read as 0
+        assert!(evaluate("[0x1f801070] == 0", &cpu));
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        for source in [
+            "", "a0 ==", "foo == 1", "(1", "1 2", "[4", "r32", "0xfg", "a0 # 1",
+        ] {
+            assert!(Condition::parse(source).is_err(), "{source}");
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::VecDeque;
+
+use crate::cpu::{
+    CPU,
+    logger::{self, CHANNELS},
+};
+
+use super::StopReason;
+
+struct Snapshot {
+    instruction_count: u64,
+    data: Vec<u8>,
+}
+
+// what a PCdrv call returned the first time through, replaying it would go to the host again
+#[derive(Clone)]
+pub(crate) struct HostCall {
+    instruction_count: u64,
+    pub v0: u32,
+    pub v1: u32,
+    // where a PCread put its data and what it read
+    pub read: Option<(u32, Vec<u8>)>,
+}
+
+/*
+  Reverse execution restores the closest snapshot before the point it wants to get back to,
+  then runs forward from there. Emulation is deterministic, so replaying ends up in exactly the
+  same state as the first time through. Snapshots are taken at the start of every frame since
+  that's when the frontend feeds in new controller input, which wouldn't get replayed otherwise.
+  Anything changed by the debugger itself (ie memory writes from gdb) isn't replayed either.
+  Nothing outside the console gets touched again while replaying: PCdrv calls get their results
+  from the first time through, the TTY and the log stay quiet and watches don't notify anyone.
+*/
+#[derive(Default)]
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    host_calls: VecDeque<HostCall>,
+    max_snapshots: usize,
+    frame_started: bool,
+    replaying: bool,
+}
+
+impl History {
+    pub(super) fn start_frame(&mut self) {
+        self.frame_started = true;
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.snapshots.clear();
+        self.host_calls.clear();
+        self.frame_started = false;
+    }
+
+    fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.max_snapshots {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+
+        // calls from before the oldest snapshot can't get replayed anymore
+        let oldest = self.snapshots[0].instruction_count;
+
+        while self
+            .host_calls
+            .front()
+            .is_some_and(|call| call.instruction_count <= oldest)
+        {
+            self.host_calls.pop_front();
+        }
+    }
+
+    // snapshots past this point are from a future that might not happen anymore
+    fn truncate(&mut self, instruction_count: u64) {
+        while self
+            .snapshots
+            .back()
+            .is_some_and(|snapshot| snapshot.instruction_count > instruction_count)
+        {
+            self.snapshots.pop_back();
+        }
+
+        while self
+            .host_calls
+            .back()
+            .is_some_and(|call| call.instruction_count > instruction_count)
+        {
+            self.host_calls.pop_back();
+        }
+    }
+
+    fn latest_before(&self, instruction_count: u64) -> Option<usize> {
+        self.snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.instruction_count <= instruction_count)
+    }
+}
+
+impl CPU {
+    // every snapshot is a full save state (around 6MB), so this keeps max_snapshots frames of history
+    pub fn enable_reverse_execution(&mut self, max_snapshots: usize) {
+        self.debugger.history = History {
+            max_snapshots,
+            ..Default::default()
+        };
+
+        if max_snapshots > 0 {
+            self.take_snapshot();
+        }
+    }
+
+    pub fn disable_reverse_execution(&mut self) {
+        self.debugger.history = History::default();
+    }
+
+    // called before every instruction that runs normally (not while replaying)
+    pub(crate) fn record_history(&mut self) {
+        let history = &mut self.debugger.history;
+
+        if history.max_snapshots == 0 {
+            return;
+        }
+
+        let needs_snapshot = history.frame_started || history.snapshots.is_empty();
+
+        history.frame_started = false;
+
+        if needs_snapshot
+            && history
+                .snapshots
+                .back()
+                .is_none_or(|snapshot| snapshot.instruction_count < self.instruction_count)
+        {
+            self.take_snapshot();
+        }
+    }
+
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.debugger.history.replaying
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn record_host_call(&mut self, v0: u32, v1: u32, read: Option<(u32, Vec<u8>)>) {
+        let history = &mut self.debugger.history;
+
+        if history.max_snapshots == 0 {
+            return;
+        }
+
+        history.host_calls.push_back(HostCall {
+            instruction_count: self.instruction_count,
+            v0,
+            v1,
+            read,
+        });
+    }
+
+    // None if the instruction being replayed didn't make a call the first time through
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn recorded_host_call(&self) -> Option<HostCall> {
+        self.debugger
+            .history
+            .host_calls
+            .iter()
+            .find(|call| call.instruction_count == self.instruction_count)
+            .cloned()
+    }
+
+    fn take_snapshot(&mut self) {
+        self.bus.scheduler.serialize_scheduler();
+
+        let (data, _) = self.create_save_state();
+
+        self.debugger.history.push(Snapshot {
+            instruction_count: self.instruction_count,
+            data,
+        });
+    }
+
+    // unlike loading a save state, everything that lives outside of the state carries over
+    fn restore_snapshot(&mut self, index: usize) -> Result<(), String> {
+        let data = std::mem::take(&mut self.debugger.history.snapshots[index].data);
+
+        let cpu = self.decode_save_state(&data);
+
+        let Some(mut cpu) = cpu else {
+            self.debugger.history.snapshots[index].data = data;
+            return Err("couldn't decode snapshot".to_string());
+        };
+
+        cpu.debugger.history.snapshots[index].data = data;
+
+        std::mem::swap(&mut cpu.exe_bytes, &mut self.exe_bytes);
+        std::mem::swap(&mut cpu.bus.tty, &mut self.bus.tty);
+        cpu.bus.cdrom.swap_media(&mut self.bus.cdrom);
+        cpu.bus
+            .peripherals
+            .memory_card
+            .swap_memory_file(&mut self.bus.peripherals.memory_card);
+
+        cpu.bus.scheduler.deserialize_scheduler();
+
+        *self = cpu;
+
+        Ok(())
+    }
+
+    // runs up to the target without stopping, collecting every breakpoint and watchpoint hit
+    // along with the instruction count execution would have stopped at
+    fn replay(&mut self, target: u64, mut hits: Option<&mut Vec<(u64, StopReason)>>) {
+        // the first run through already got traced, counted and profiled, and already printed
+        // everything, logged everything and notified watchers
+        let trace = self.trace.take();
+        let coverage = self.coverage.take();
+        let profiler = self.profiler.take();
+        let tty = std::mem::take(&mut self.bus.tty);
+        let levels = CHANNELS.map(|channel| (channel, logger::level(channel)));
+
+        for channel in CHANNELS {
+            logger::set_level(channel, None);
+        }
+
+        self.watches.set_muted(true);
+        self.debugger.history.replaying = true;
+
+        while self.instruction_count < target {
+            if let Some(hits) = hits.as_deref_mut()
+                && self.breakpoint_hit()
+            {
+                hits.push((
+                    self.instruction_count,
+                    StopReason::Breakpoint { address: self.pc },
+                ));
+            }
+
+            self.execute();
+
+            if let Some(reason) = self.debugger.pending_stop.take()
+                && let Some(hits) = hits.as_deref_mut()
+            {
+                hits.push((self.instruction_count, reason));
+            }
+        }
+
+        self.debugger.history.replaying = false;
+        self.watches.set_muted(false);
+
+        for (channel, level) in levels {
+            logger::set_level(channel, level);
+        }
+
+        self.trace = trace;
+        self.coverage = coverage;
+        self.profiler = profiler;
+        self.bus.tty = tty;
+    }
+
+    fn finish_reverse(&mut self, reason: StopReason) {
+        self.debugger.history.truncate(self.instruction_count);
+        self.debugger.history.frame_started = false;
+        self.debugger.halt(reason);
+    }
+
+    // goes back to the state right before the last instruction ran, leaving the debugger halted
+    pub fn step_back(&mut self) -> Result<(), String> {
+        let target = self
+            .instruction_count
+            .checked_sub(1)
+            .ok_or("nothing to step back to")?;
+
+        let index = self
+            .debugger
+            .history
+            .latest_before(target)
+            .ok_or("no snapshot to step back from")?;
+
+        self.restore_snapshot(index)?;
+        self.replay(target, None);
+        self.finish_reverse(StopReason::Step);
+
+        Ok(())
+    }
+
+    // goes back to the last breakpoint or watchpoint hit, or the oldest snapshot if there wasn't one
+    pub fn reverse_continue(&mut self) -> Result<StopReason, String> {
+        if self.debugger.history.snapshots.is_empty() {
+            return Err("no snapshot to reverse from".to_string());
+        }
+
+        let current = self.instruction_count;
+        let mut end = current;
+
+        // search one snapshot at a time from the newest, the first one with a hit has the latest
+        for index in (0..self.debugger.history.snapshots.len()).rev() {
+            let start = self.debugger.history.snapshots[index].instruction_count;
+
+            if start >= end {
+                continue;
+            }
+
+            let mut hits = Vec::new();
+
+            self.restore_snapshot(index)?;
+            self.replay(end, Some(&mut hits));
+
+            if let Some(&(position, reason)) =
+                hits.iter().rev().find(|(position, _)| *position < current)
+            {
+                self.restore_snapshot(index)?;
+                self.replay(position, None);
+                self.finish_reverse(reason);
+
+                return Ok(reason);
+            }
+
+            end = start;
+        }
+
+        self.restore_snapshot(0)?;
+        self.finish_reverse(StopReason::HistoryStart);
+
+        Ok(StopReason::HistoryStart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, rc::Rc};
+
+    use super::*;
+    use crate::cpu::{
+        bus::ram_search::ValueType,
+        watch::{WatchEntry, WatchFormat},
+    };
+
+    fn cpu_running(program: &[u32]) -> CPU {
+        let mut cpu = CPU::new(None, String::new());
+
+        for (i, instruction) in program.iter().enumerate() {
+            cpu.bus
+                .mem_write32(0x8001_0000 + 4 * i as u32, *instruction);
+        }
+
+        cpu.pc = 0x8001_0000;
+        cpu.next_pc = 0x8001_0004;
+
+        cpu
+    }
+
+    // counts up in t0, printing a newline to the TTY and storing t0 to 0x100 every time around
+    fn counting_loop() -> CPU {
+        cpu_running(&[
+            0x2508_0001, // addiu t0, t0, 1
+            0x3c09_1f80, // lui t1, 0x1f80
+            0x240a_000a, // addiu t2, zero, 0xa
+            0xa12a_2080, // sb t2, 0x2080(t1)
+            0x0800_4000, // j 0x80010000
+            0xac08_0100, // sw t0, 0x100(zero)
+        ])
+    }
+
+    #[test]
+    fn steps_back_to_the_same_state() {
+        let mut cpu = counting_loop();
+
+        cpu.enable_reverse_execution(4);
+
+        for _ in 0..18 {
+            cpu.step();
+        }
+
+        cpu.step_back().unwrap();
+
+        let mut expected = counting_loop();
+
+        for _ in 0..17 {
+            expected.step();
+        }
+
+        assert_eq!(cpu.instruction_count, 17);
+        assert_eq!(cpu.pc, expected.pc);
+        for register in 0..32 {
+            assert_eq!(cpu.r[register], expected.r[register]);
+        }
+        assert_eq!(cpu.debugger.stop_reason(), Some(StopReason::Step));
+    }
+
+    #[test]
+    fn reverse_continues_to_the_last_breakpoint_hit() {
+        let mut cpu = counting_loop();
+
+        cpu.enable_reverse_execution(4);
+
+        for _ in 0..18 {
+            cpu.step();
+        }
+
+        cpu.debugger.add_breakpoint(0x8001_000c);
+
+        assert_eq!(
+            cpu.reverse_continue(),
+            Ok(StopReason::Breakpoint {
+                address: 0x8001_000c
+            })
+        );
+        assert_eq!(cpu.instruction_count, 15);
+        assert_eq!(cpu.r[8], 3);
+
+        cpu.debugger.remove_breakpoint(0x8001_000c);
+
+        assert_eq!(cpu.reverse_continue(), Ok(StopReason::HistoryStart));
+        assert_eq!(cpu.instruction_count, 0);
+    }
+
+    #[test]
+    fn replays_without_printing_or_notifying_again() {
+        let mut cpu = counting_loop();
+
+        let id = cpu.watches.add(
+            WatchEntry::new(0x100, ValueType::U32, WatchFormat::Hex),
+            &cpu.bus,
+        );
+
+        let seen = Rc::new(RefCell::new(0));
+        let callback_seen = seen.clone();
+
+        cpu.watches.subscribe(id, true);
+        cpu.watches
+            .on_change(move |_| *callback_seen.borrow_mut() += 1);
+        cpu.enable_reverse_execution(4);
+
+        for _ in 0..18 {
+            cpu.step();
+        }
+
+        assert_eq!(cpu.bus.tty.take_output(), "\n\n\n");
+        assert_eq!(cpu.watches.drain_events().len(), 3);
+
+        cpu.step_back().unwrap();
+
+        assert_eq!(cpu.bus.tty.take_output(), "");
+        assert!(cpu.watches.drain_events().is_empty());
+        assert_eq!(*seen.borrow(), 3);
+
+        // the value still follows the replayed writes
+        assert_eq!(cpu.watches.get(id).unwrap().value, 2);
+    }
+
+    #[test]
+    fn replays_pcdrv_calls_without_going_to_the_host() {
+        let root = std::env::temp_dir().join(format!("rsx-history-test-{}", std::process::id()));
+
+        fs::create_dir_all(&root).unwrap();
+
+        let mut cpu = cpu_running(&[
+            0x3c05_8002, // lui a1, 0x8002
+            0x0000_408d, // break 0x102 (PCcreat)
+            0x0060_2825, // or a1, v1, zero
+            0x2406_0003, // addiu a2, zero, 3
+            0x3c07_8002, // lui a3, 0x8002
+            0x0000_418d, // break 0x106 (PCwrite)
+        ]);
+
+        cpu.bus
+            .mem_write32(0x8002_0000, u32::from_le_bytes(*b"out\0"));
+        cpu.enable_pcdrv(&root).unwrap();
+        cpu.enable_reverse_execution(4);
+
+        for _ in 0..6 {
+            cpu.step();
+        }
+
+        assert_eq!(fs::read(root.join("out")).unwrap(), b"out");
+
+        // creating the file again would have truncated it and handed out another handle
+        cpu.step_back().unwrap();
+
+        assert_eq!(fs::read(root.join("out")).unwrap(), b"out");
+        assert_eq!(cpu.r[3], 1);
+
+        cpu.debugger.step(cpu.pc);
+        cpu.step();
+
+        let written = fs::read(root.join("out")).unwrap();
+
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(written, b"outout");
+    }
+}
@@ -1,247 +1,287 @@
-use super::{CPU, instructions::Instruction};
+use super::{CPU, instructions::Instruction, symbols::SymbolTable};
 
 impl CPU {
     pub fn disassemble(&self, instruction: u32) -> String {
         let instr = Instruction(instruction);
 
-        let upper = instruction >> 26;
-
-        let mut taken = false;
-
-        let command = match upper {
-            0x0 => match instruction & 0x3f {
-                0x0 => "SLL",
-                0x2 => "SRL",
-                0x3 => "SRA",
-                0x4 => "SLLV",
-                0x6 => "SRLV",
-                0x7 => "SRAV",
-                0x8 => "JR",
-                0x9 => "JALR",
-                0xc => "SYSCALL",
-                0xd => "BREAK",
-                0x10 => "MFHI",
-                0x11 => "MTHI",
-                0x12 => "MFLO",
-                0x13 => "MTLO",
-                0x18 => "MULT",
-                0x19 => "MULTU",
-                0x1a => "DIV",
-                0x1b => "DIVU",
-                0x20 => "ADD",
-                0x21 => "ADDU",
-                0x22 => "SUB",
-                0x23 => "SUBU",
-                0x24 => "AND",
-                0x25 => "OR",
-                0x26 => "XOR",
-                0x27 => "NOR",
-                0x2a => "SLT",
-                0x2b => "SLTU",
-                _ => panic!("unknown instruction received: 0x{:x}", instruction & 0x3f),
-            },
+        if instruction >> 26 == 0 && instruction & 0x3f == 0x8 {
+            return format!(
+                "JR {} (r{})",
+                format_target(self.r[instr.rs()], &self.symbols),
+                instr.rs()
+            );
+        }
+
+        let taken = match instruction >> 26 {
             0x1 => match instr.rt() {
-                0x0 => {
-                    taken = (self.r[instr.rs()] as i32) < 0;
-                    "BLTZ"
-                }
-                0x1 => {
-                    taken = (self.r[instr.rs()] as i32) >= 0;
-                    "BGEZ"
-                }
-                0x10 => {
-                    taken = (self.r[instr.rs()] as i32) < 0;
-                    "BLTZAL"
-                }
-                0x11 => {
-                    taken = (self.r[instr.rs()] as i32) >= 0;
-                    "BGEZAL"
-                }
-                _ => "UNKNOWN",
+                0x0 | 0x10 => Some((self.r[instr.rs()] as i32) < 0),
+                0x1 | 0x11 => Some((self.r[instr.rs()] as i32) >= 0),
+                _ => None,
             },
-            0x2 => "J",
-            0x3 => "JAL",
-            0x4 => {
-                taken = self.r[instr.rs()] == self.r[instr.rt()];
-                "BEQ"
-            }
-            0x5 => {
-                taken = self.r[instr.rs()] != self.r[instr.rt()];
-                "BNE"
-            }
-            0x6 => {
-                taken = (self.r[instr.rs()] as i32) <= 0;
-                "BLEZ"
-            }
-            0x7 => {
-                taken = (self.r[instr.rs()] as i32) > 0;
-                "BGTZ"
-            }
-            0x8 => "ADDI",
-            0x9 => "ADDIU",
-            0xa => "SLTI",
-            0xb => "SLTIU",
-            0xc => "ANDI",
-            0xd => "ORI",
-            0xe => "XORI",
-            0xf => "LUI",
-            0x10 => "COP0",
-            0x11 => "COP1",
-            0x12 => "COP2",
-            0x13 => "COP3",
-            0x20 => "LB",
-            0x21 => "LH",
-            0x22 => "LWL",
-            0x23 => "LW",
-            0x24 => "LBU",
-            0x25 => "LHU",
-            0x26 => "LWR",
-            0x28 => "SB",
-            0x29 => "SH",
-            0x2a => "SWL",
-            0x2b => "SW",
-            0x2e => "SWR",
-            0x30 => "LWC0",
-            0x31 => "LWC1",
-            0x32 => "LWC2",
-            0x33 => "LWC3",
-            0x38 => "SWC0",
-            0x39 => "SWC1",
-            0x3a => "SWC2",
-            0x3b => "SWC3",
-            _ => panic!("invalid value given to disassembler: 0x{upper:x}"),
+            0x4 => Some(self.r[instr.rs()] == self.r[instr.rt()]),
+            0x5 => Some(self.r[instr.rs()] != self.r[instr.rt()]),
+            0x6 => Some((self.r[instr.rs()] as i32) <= 0),
+            0x7 => Some((self.r[instr.rs()] as i32) > 0),
+            _ => None,
         };
 
-        let taken_str = format!("(Taken? {})", if taken { "Yes ✅" } else { "No ❌" });
-
-        // see https://psx-spx.consoledev.net/cpuspecifications/#cpu-opcode-encoding
-        if upper == 0 {
-            if instruction & 0b111100 == 0b100 {
-                return format!(
-                    "{command} r{}, r{}, r{}",
-                    instr.rd(),
-                    instr.rt(),
-                    instr.rs()
-                );
-            }
-            if instruction & 0b111111 <= 0b11 {
-                return format!(
-                    "{command} r{}, r{}, 0x{:x}",
-                    instr.rd(),
-                    instr.rt(),
-                    instr.immediate5()
-                );
-            }
-            if instruction & 0b111111 == 0b1000 {
-                return format!("{command} 0x{:x} (r{})", self.r[instr.rs()], instr.rs());
-            }
-            if instruction & 0b111111 == 0b1001 {
-                return format!("{command} r{}, r{}", instr.rd(), instr.rs());
-            }
-            if instruction & 0b111110 == 0b1100 {
-                return command.to_string();
-            }
-            if instruction & 0b111101 == 0b10000 {
-                return format!("{command} r{}", instr.rd());
-            }
-            if instruction & 0b111101 == 0b10001 {
-                return format!("{command} r{}", instr.rs());
-            }
-            if instruction & 0b111100 == 0b11000 {
-                return format!("{command} r{}, r{}", instr.rs(), instr.rt());
-            }
-            if instruction & 0b110000 == 0b100000 {
-                return format!(
-                    "{command} r{}, r{}, r{}",
-                    instr.rd(),
-                    instr.rs(),
-                    instr.rt()
-                );
-            }
-        }
+        let text = disassemble_with_symbols(instruction, self.pc, &self.symbols, Some(&self.r.0));
 
-        if upper == 1 {
-            return format!("{command} r{}, 0x{:x}", instr.rs(), instr.immediate16());
+        match taken {
+            Some(taken) => format!("{text} (Taken? {})", if taken { "Yes ✅" } else { "No ❌" }),
+            None => text,
         }
+    }
+}
 
-        if upper & 0b111110 == 0b10 {
-            return format!(
-                "{command} 0x{:x}",
-                (instr.immediate26() << 2) | (self.pc & 0xf0000000)
-            );
-        }
+fn format_target(address: u32, symbols: &SymbolTable) -> String {
+    match symbols.describe(address) {
+        Some(name) => format!("0x{address:x} <{name}>"),
+        None => format!("0x{address:x}"),
+    }
+}
 
-        if upper & 0b111110 == 0b100 {
-            let destination = ((self.pc as i32) + (instr.signed_immediate16() << 2)) as u32;
-            return format!(
-                "{command} r{}, r{}, 0x{:x} {}",
-                instr.rs(),
-                instr.rt(),
-                destination,
-                taken_str
-            );
-        }
+// pc is the address of the delay slot, which is what the CPU's pc holds while executing the instruction
+pub fn disassemble(instruction: u32, pc: u32) -> String {
+    disassemble_with_symbols(instruction, pc, &SymbolTable::new(), None)
+}
+
+/*
+  Jump and branch targets get annotated with the symbol they land in, and so do memory operands
+  when their address is known. That needs the register values, without them only operands
+  relative to r0 can be annotated.
+*/
+pub fn disassemble_with_symbols(
+    instruction: u32,
+    pc: u32,
+    symbols: &SymbolTable,
+    registers: Option<&[u32; 32]>,
+) -> String {
+    let instr = Instruction(instruction);
+
+    let upper = instruction >> 26;
+
+    let base = match instr.rs() {
+        0 => Some(0),
+        rs => registers.map(|registers| registers[rs]),
+    };
 
-        if upper & 0b111110 == 0b110 {
-            let destination = ((self.pc as i32) + (instr.signed_immediate16() << 2)) as u32;
+    let operand = base
+        .map(|base| base.wrapping_add(instr.signed_immediate16() as u32))
+        .and_then(|address| symbols.describe(address))
+        .map(|name| format!(" <{name}>"))
+        .unwrap_or_default();
+
+    let command = match upper {
+        0x0 => match instruction & 0x3f {
+            0x0 => "SLL",
+            0x2 => "SRL",
+            0x3 => "SRA",
+            0x4 => "SLLV",
+            0x6 => "SRLV",
+            0x7 => "SRAV",
+            0x8 => "JR",
+            0x9 => "JALR",
+            0xc => "SYSCALL",
+            0xd => "BREAK",
+            0x10 => "MFHI",
+            0x11 => "MTHI",
+            0x12 => "MFLO",
+            0x13 => "MTLO",
+            0x18 => "MULT",
+            0x19 => "MULTU",
+            0x1a => "DIV",
+            0x1b => "DIVU",
+            0x20 => "ADD",
+            0x21 => "ADDU",
+            0x22 => "SUB",
+            0x23 => "SUBU",
+            0x24 => "AND",
+            0x25 => "OR",
+            0x26 => "XOR",
+            0x27 => "NOR",
+            0x2a => "SLT",
+            0x2b => "SLTU",
+            _ => return format!("UNKNOWN 0x{instruction:08x}"),
+        },
+        0x1 => match instr.rt() {
+            0x0 => "BLTZ",
+            0x1 => "BGEZ",
+            0x10 => "BLTZAL",
+            0x11 => "BGEZAL",
+            _ => "UNKNOWN",
+        },
+        0x2 => "J",
+        0x3 => "JAL",
+        0x4 => "BEQ",
+        0x5 => "BNE",
+        0x6 => "BLEZ",
+        0x7 => "BGTZ",
+        0x8 => "ADDI",
+        0x9 => "ADDIU",
+        0xa => "SLTI",
+        0xb => "SLTIU",
+        0xc => "ANDI",
+        0xd => "ORI",
+        0xe => "XORI",
+        0xf => "LUI",
+        0x10 => "COP0",
+        0x11 => "COP1",
+        0x12 => "COP2",
+        0x13 => "COP3",
+        0x20 => "LB",
+        0x21 => "LH",
+        0x22 => "LWL",
+        0x23 => "LW",
+        0x24 => "LBU",
+        0x25 => "LHU",
+        0x26 => "LWR",
+        0x28 => "SB",
+        0x29 => "SH",
+        0x2a => "SWL",
+        0x2b => "SW",
+        0x2e => "SWR",
+        0x30 => "LWC0",
+        0x31 => "LWC1",
+        0x32 => "LWC2",
+        0x33 => "LWC3",
+        0x38 => "SWC0",
+        0x39 => "SWC1",
+        0x3a => "SWC2",
+        0x3b => "SWC3",
+        _ => return format!("UNKNOWN 0x{instruction:08x}"),
+    };
+
+    // see https://psx-spx.consoledev.net/cpuspecifications/#cpu-opcode-encoding
+    if upper == 0 {
+        if instruction & 0b111100 == 0b100 {
             return format!(
-                "{command} r{}, 0x{:x} {}",
-                instr.rs(),
-                destination,
-                taken_str
+                "{command} r{}, r{}, r{}",
+                instr.rd(),
+                instr.rt(),
+                instr.rs()
             );
         }
-
-        if upper & 0b111000 == 0b1000 {
+        if instruction & 0b111111 <= 0b11 {
             return format!(
                 "{command} r{}, r{}, 0x{:x}",
+                instr.rd(),
                 instr.rt(),
-                instr.rs(),
-                instr.immediate16()
+                instr.immediate5()
             );
         }
-
-        if upper & 0b111111 == 0b1111 {
-            return format!("{command} r{}, 0x{:x}", instr.rt(), instr.immediate16());
+        if instruction & 0b111111 == 0b1000 {
+            return format!("{command} r{}", instr.rs());
         }
-
-        if upper & 0b110000 == 0b100000 {
+        if instruction & 0b111111 == 0b1001 {
+            return format!("{command} r{}, r{}", instr.rd(), instr.rs());
+        }
+        if instruction & 0b111110 == 0b1100 {
+            return command.to_string();
+        }
+        if instruction & 0b111101 == 0b10000 {
+            return format!("{command} r{}", instr.rd());
+        }
+        if instruction & 0b111101 == 0b10001 {
+            return format!("{command} r{}", instr.rs());
+        }
+        if instruction & 0b111100 == 0b11000 {
+            return format!("{command} r{}, r{}", instr.rs(), instr.rt());
+        }
+        if instruction & 0b110000 == 0b100000 {
             return format!(
-                "{command} r{}, [r{} + 0x{:x}]",
-                instr.rt(),
+                "{command} r{}, r{}, r{}",
+                instr.rd(),
                 instr.rs(),
-                instr.immediate16()
+                instr.rt()
             );
         }
+    }
 
-        let upper_cop0 = instruction >> 28;
-        let mid = (instruction >> 21) & 0x1f;
-
-        let processor_number = (instruction >> 26) & 0x3;
-        match upper_cop0 {
-            0x4 => match mid {
-                0x0 => format!("MFC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
-                0x2 => format!("CFC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
-                0x4 => format!("MTC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
-                0x6 => format!("CTC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
-                0x10 => "RFE".to_string(),
-                _ => format!("GTE 0x{:x}", instr.cop2_command()),
-            },
-            0xc => format!(
-                "LWC{} r{}, [r{} + 0x{:x}]",
-                processor_number,
-                instr.rt(),
-                instr.rs(),
-                instr.immediate16()
-            ),
-            0xe => format!(
-                "SWC{} r{}, [r{} + 0x{:x}]",
-                processor_number,
-                instr.rt(),
-                instr.rs(),
-                instr.immediate16()
-            ),
-            _ => panic!("invalid option for cop instruction given: 0x{upper_cop0:x}"),
-        }
+    if upper == 1 {
+        let destination = ((pc as i32) + (instr.signed_immediate16() << 2)) as u32;
+        return format!(
+            "{command} r{}, {}",
+            instr.rs(),
+            format_target(destination, symbols)
+        );
+    }
+
+    if upper & 0b111110 == 0b10 {
+        return format!(
+            "{command} {}",
+            format_target((instr.immediate26() << 2) | (pc & 0xf0000000), symbols)
+        );
+    }
+
+    if upper & 0b111110 == 0b100 {
+        let destination = ((pc as i32) + (instr.signed_immediate16() << 2)) as u32;
+        return format!(
+            "{command} r{}, r{}, {}",
+            instr.rs(),
+            instr.rt(),
+            format_target(destination, symbols)
+        );
+    }
+
+    if upper & 0b111110 == 0b110 {
+        let destination = ((pc as i32) + (instr.signed_immediate16() << 2)) as u32;
+        return format!(
+            "{command} r{}, {}",
+            instr.rs(),
+            format_target(destination, symbols)
+        );
+    }
+
+    if upper & 0b111000 == 0b1000 {
+        return format!(
+            "{command} r{}, r{}, 0x{:x}",
+            instr.rt(),
+            instr.rs(),
+            instr.immediate16()
+        );
+    }
+
+    if upper & 0b111111 == 0b1111 {
+        return format!("{command} r{}, 0x{:x}", instr.rt(), instr.immediate16());
+    }
+
+    if upper & 0b110000 == 0b100000 {
+        return format!(
+            "{command} r{}, [r{} + 0x{:x}]{operand}",
+            instr.rt(),
+            instr.rs(),
+            instr.immediate16()
+        );
+    }
+
+    let upper_cop0 = instruction >> 28;
+    let mid = (instruction >> 21) & 0x1f;
+
+    let processor_number = (instruction >> 26) & 0x3;
+    match upper_cop0 {
+        0x4 => match mid {
+            0x0 => format!("MFC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
+            0x2 => format!("CFC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
+            0x4 => format!("MTC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
+            0x6 => format!("CTC{} r{}, r{}", processor_number, instr.rt(), instr.rd()),
+            0x10 => "RFE".to_string(),
+            _ => format!("GTE 0x{:x}", instr.cop2_command()),
+        },
+        0xc => format!(
+            "LWC{} r{}, [r{} + 0x{:x}]{operand}",
+            processor_number,
+            instr.rt(),
+            instr.rs(),
+            instr.immediate16()
+        ),
+        0xe => format!(
+            "SWC{} r{}, [r{} + 0x{:x}]{operand}",
+            processor_number,
+            instr.rt(),
+            instr.rs(),
+            instr.immediate16()
+        ),
+        _ => format!("UNKNOWN 0x{instruction:08x}"),
     }
 }
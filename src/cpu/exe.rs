@@ -0,0 +1,501 @@
+use std::fmt;
+
+use super::symbols::SymbolTable;
+
+const PSX_EXE_MAGIC: &[u8; 8] = b"PS-X EXE";
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const CPE_MAGIC: &[u8; 4] = b"CPE\x01";
+
+const PSX_EXE_HEADER_SIZE: usize = 0x800;
+const RAM_SIZE: u32 = 0x20_0000;
+
+const ELF_MACHINE_MIPS: u16 = 8;
+const ELF_TYPE_EXEC: u16 = 2;
+const ELF_SEGMENT_LOAD: u32 = 1;
+
+// register number CPE files use for the pc
+const CPE_REGISTER_PC: u16 = 0x90;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ExeFormat {
+    PsxExe,
+    Elf,
+    Cpe,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExeError {
+    UnknownFormat,
+    Truncated,
+    UnsupportedElf(&'static str),
+    UnknownCpeChunk { chunk: u8, offset: usize },
+    OutsideOfRam { address: u32, len: usize },
+}
+
+impl fmt::Display for ExeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExeError::UnknownFormat => write!(f, "not a PS-X EXE, ELF or CPE file"),
+            ExeError::Truncated => write!(f, "file ends before the data its header describes"),
+            ExeError::UnsupportedElf(reason) => write!(f, "unsupported ELF file: {reason}"),
+            ExeError::UnknownCpeChunk { chunk, offset } => {
+                write!(f, "unknown CPE chunk 0x{chunk:x} at offset 0x{offset:x}")
+            }
+            ExeError::OutsideOfRam { address, len } => write!(
+                f,
+                "0x{len:x} bytes at 0x{address:08x} don't fit in main RAM"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExeError {}
+
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Executable {
+    pub entry: u32,
+    pub gp: Option<u32>,
+    pub stack: Option<u32>,
+    pub segments: Vec<Segment>,
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Result<u8, ExeError> {
+    bytes.get(offset).copied().ok_or(ExeError::Truncated)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ExeError> {
+    bytes
+        .get(offset..offset.saturating_add(2))
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or(ExeError::Truncated)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ExeError> {
+    bytes
+        .get(offset..offset.saturating_add(4))
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(ExeError::Truncated)
+}
+
+fn read_slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], ExeError> {
+    bytes
+        .get(offset..offset.checked_add(len).ok_or(ExeError::Truncated)?)
+        .ok_or(ExeError::Truncated)
+}
+
+// segments get checked before anything is allocated for them, the sizes come straight from the header
+fn check_fits_in_ram(address: u32, len: usize) -> Result<(), ExeError> {
+    let start = (address & 0x1fff_ffff) as usize;
+
+    if start.saturating_add(len) > RAM_SIZE as usize {
+        return Err(ExeError::OutsideOfRam { address, len });
+    }
+
+    Ok(())
+}
+
+// only needs the first 8 bytes of the file
+pub fn detect_format(header: &[u8]) -> Option<ExeFormat> {
+    if header.starts_with(PSX_EXE_MAGIC) {
+        Some(ExeFormat::PsxExe)
+    } else if header.starts_with(ELF_MAGIC) {
+        Some(ExeFormat::Elf)
+    } else if header.starts_with(CPE_MAGIC) {
+        Some(ExeFormat::Cpe)
+    } else {
+        None
+    }
+}
+
+impl Executable {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ExeError> {
+        let exe = match detect_format(bytes) {
+            Some(ExeFormat::PsxExe) => Self::parse_psx_exe(bytes)?,
+            Some(ExeFormat::Elf) => Self::parse_elf(bytes)?,
+            Some(ExeFormat::Cpe) => Self::parse_cpe(bytes)?,
+            None => return Err(ExeError::UnknownFormat),
+        };
+
+        for segment in &exe.segments {
+            check_fits_in_ram(segment.address, segment.data.len())?;
+        }
+
+        Ok(exe)
+    }
+
+    // see https://psx-spx.consoledev.net/cdromdrive/#filenameexe-general-purpose-executable
+    pub fn parse_psx_exe(bytes: &[u8]) -> Result<Self, ExeError> {
+        if !bytes.starts_with(PSX_EXE_MAGIC) {
+            return Err(ExeError::UnknownFormat);
+        }
+
+        let entry = read_u32(bytes, 0x10)?;
+        let gp = read_u32(bytes, 0x14)?;
+        let destination = read_u32(bytes, 0x18)?;
+        let size = read_u32(bytes, 0x1c)? as usize;
+        let bss_address = read_u32(bytes, 0x28)?;
+        let bss_size = read_u32(bytes, 0x2c)? as usize;
+        let stack_base = read_u32(bytes, 0x30)?;
+        let stack_offset = read_u32(bytes, 0x34)?;
+
+        let mut segments = vec![Segment {
+            address: destination,
+            data: read_slice(bytes, PSX_EXE_HEADER_SIZE, size)?.to_vec(),
+        }];
+
+        // the BIOS zero fills this area before jumping to the exe
+        if bss_size > 0 {
+            check_fits_in_ram(bss_address, bss_size)?;
+
+            segments.push(Segment {
+                address: bss_address,
+                data: vec![0; bss_size],
+            });
+        }
+
+        Ok(Self {
+            entry,
+            gp: Some(gp),
+            stack: (stack_base != 0).then(|| stack_base.wrapping_add(stack_offset)),
+            segments,
+        })
+    }
+
+    // statically linked executables only, every PT_LOAD segment gets copied to its virtual address
+    pub fn parse_elf(bytes: &[u8]) -> Result<Self, ExeError> {
+        if !bytes.starts_with(ELF_MAGIC) {
+            return Err(ExeError::UnknownFormat);
+        }
+
+        if read_u8(bytes, 4)? != 1 || read_u8(bytes, 5)? != 1 {
+            return Err(ExeError::UnsupportedElf("not a little endian 32 bit file"));
+        }
+
+        if read_u16(bytes, 0x12)? != ELF_MACHINE_MIPS {
+            return Err(ExeError::UnsupportedElf("not a MIPS executable"));
+        }
+
+        if read_u16(bytes, 0x10)? != ELF_TYPE_EXEC {
+            return Err(ExeError::UnsupportedElf(
+                "not a statically linked executable",
+            ));
+        }
+
+        let entry = read_u32(bytes, 0x18)?;
+        let header_offset = read_u32(bytes, 0x1c)? as usize;
+        let header_size = read_u16(bytes, 0x2a)? as usize;
+        let num_headers = read_u16(bytes, 0x2c)? as usize;
+
+        let mut segments = Vec::new();
+
+        for index in 0..num_headers {
+            let header = index
+                .checked_mul(header_size)
+                .and_then(|offset| offset.checked_add(header_offset))
+                .ok_or(ExeError::Truncated)?;
+
+            if read_u32(bytes, header)? != ELF_SEGMENT_LOAD {
+                continue;
+            }
+
+            let offset = read_u32(bytes, header + 0x4)? as usize;
+            let address = read_u32(bytes, header + 0x8)?;
+            let file_size = read_u32(bytes, header + 0x10)? as usize;
+            let memory_size = read_u32(bytes, header + 0x14)? as usize;
+
+            if memory_size == 0 {
+                continue;
+            }
+
+            check_fits_in_ram(address, memory_size.max(file_size))?;
+
+            let mut data = read_slice(bytes, offset, file_size)?.to_vec();
+
+            // whatever isn't in the file is bss
+            data.resize(memory_size.max(file_size), 0);
+
+            segments.push(Segment { address, data });
+        }
+
+        if segments.is_empty() {
+            return Err(ExeError::UnsupportedElf("no loadable segments"));
+        }
+
+        let mut symbols = SymbolTable::new();
+
+        let gp = match symbols.load_elf(bytes) {
+            Ok(_) => symbols.find("_gp"),
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            entry,
+            gp,
+            stack: None,
+            segments,
+        })
+    }
+
+    /*
+      PSY-Q CPE files are the "CPE\x01" magic followed by chunks, each starting with an id byte:
+      0: end of file
+      1: load data, address (u32), length (u32), then the data itself
+      3: set register, register (u16), value (u32)
+      8: select unit (u8), which doesn't mean anything here
+    */
+    pub fn parse_cpe(bytes: &[u8]) -> Result<Self, ExeError> {
+        if !bytes.starts_with(CPE_MAGIC) {
+            return Err(ExeError::UnknownFormat);
+        }
+
+        let mut exe = Self::default();
+        let mut offset = CPE_MAGIC.len();
+
+        loop {
+            let chunk = read_u8(bytes, offset)?;
+
+            offset += 1;
+
+            match chunk {
+                0x0 => break,
+                0x1 => {
+                    let address = read_u32(bytes, offset)?;
+                    let len = read_u32(bytes, offset + 4)? as usize;
+
+                    offset += 8;
+
+                    exe.segments.push(Segment {
+                        address,
+                        data: read_slice(bytes, offset, len)?.to_vec(),
+                    });
+
+                    offset += len;
+                }
+                0x3 => {
+                    let register = read_u16(bytes, offset)?;
+                    let value = read_u32(bytes, offset + 2)?;
+
+                    offset += 6;
+
+                    // the pc is the only register the PSY-Q tools ever set
+                    if register == CPE_REGISTER_PC {
+                        exe.entry = value;
+                    }
+                }
+                0x8 => offset += 1,
+                _ => {
+                    return Err(ExeError::UnknownCpeChunk {
+                        chunk,
+                        offset: offset - 1,
+                    });
+                }
+            }
+        }
+
+        Ok(exe)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn psx_exe(destination: u32, data: &[u8], bss_address: u32, bss_size: u32) -> Vec<u8> {
+        let mut bytes = vec![0; PSX_EXE_HEADER_SIZE];
+
+        bytes[..8].copy_from_slice(PSX_EXE_MAGIC);
+
+        for (offset, value) in [
+            (0x10, 0x8001_0000),
+            (0x14, 0x8002_0000),
+            (0x18, destination),
+            (0x1c, data.len() as u32),
+            (0x28, bss_address),
+            (0x2c, bss_size),
+            (0x30, 0x801f_fff0),
+        ] {
+            bytes[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+        }
+
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+
+    // a header with one PT_LOAD program header and no sections
+    fn elf(address: u32, data: &[u8], memory_size: u32) -> Vec<u8> {
+        let mut bytes = vec![0; 0x54];
+
+        bytes[..4].copy_from_slice(ELF_MAGIC);
+        bytes[4] = 1;
+        bytes[5] = 1;
+        bytes[0x10..0x12].copy_from_slice(&ELF_TYPE_EXEC.to_le_bytes());
+        bytes[0x12..0x14].copy_from_slice(&ELF_MACHINE_MIPS.to_le_bytes());
+        bytes[0x18..0x1c].copy_from_slice(&address.to_le_bytes());
+        bytes[0x1c..0x20].copy_from_slice(&0x34u32.to_le_bytes());
+        bytes[0x2a..0x2c].copy_from_slice(&0x20u16.to_le_bytes());
+        bytes[0x2c..0x2e].copy_from_slice(&1u16.to_le_bytes());
+
+        for (offset, value) in [
+            (0x34, ELF_SEGMENT_LOAD),
+            (0x38, 0x54),
+            (0x3c, address),
+            (0x44, data.len() as u32),
+            (0x48, memory_size),
+        ] {
+            bytes[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+        }
+
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(detect_format(b"PS-X EXE"), Some(ExeFormat::PsxExe));
+        assert_eq!(detect_format(b"\x7fELF\x01\x01"), Some(ExeFormat::Elf));
+        assert_eq!(detect_format(b"CPE\x01\x08\x00"), Some(ExeFormat::Cpe));
+        assert_eq!(detect_format(b"MZ"), None);
+        assert_eq!(
+            Executable::parse(b"nothing").unwrap_err(),
+            ExeError::UnknownFormat
+        );
+    }
+
+    #[test]
+    fn parses_psx_exe() {
+        let exe =
+            Executable::parse(&psx_exe(0x8001_0000, &[1, 2, 3, 4], 0x8003_0000, 0x10)).unwrap();
+
+        assert_eq!(exe.entry, 0x8001_0000);
+        assert_eq!(exe.gp, Some(0x8002_0000));
+        assert_eq!(exe.stack, Some(0x801f_fff0));
+        assert_eq!(exe.segments.len(), 2);
+        assert_eq!(exe.segments[0].address, 0x8001_0000);
+        assert_eq!(exe.segments[0].data, [1, 2, 3, 4]);
+        assert_eq!(exe.segments[1].address, 0x8003_0000);
+        assert_eq!(exe.segments[1].data, [0; 0x10]);
+    }
+
+    #[test]
+    fn rejects_bad_psx_exes() {
+        let mut truncated = psx_exe(0x8001_0000, &[1, 2, 3, 4], 0, 0);
+
+        truncated.pop();
+
+        assert_eq!(
+            Executable::parse(&truncated).unwrap_err(),
+            ExeError::Truncated
+        );
+
+        // the bss size has to be checked before it gets allocated
+        assert_eq!(
+            Executable::parse(&psx_exe(0x8001_0000, &[], 0x8001_0000, u32::MAX)).unwrap_err(),
+            ExeError::OutsideOfRam {
+                address: 0x8001_0000,
+                len: u32::MAX as usize
+            }
+        );
+
+        assert!(matches!(
+            Executable::parse(&psx_exe(0x801f_fffe, &[1, 2, 3, 4], 0, 0)),
+            Err(ExeError::OutsideOfRam { .. })
+        ));
+    }
+
+    #[test]
+    fn parses_elf() {
+        let exe = Executable::parse(&elf(0x8001_0000, &[1, 2, 3, 4], 8)).unwrap();
+
+        assert_eq!(exe.entry, 0x8001_0000);
+        assert_eq!(exe.gp, None);
+        assert_eq!(exe.segments.len(), 1);
+        assert_eq!(exe.segments[0].address, 0x8001_0000);
+        assert_eq!(exe.segments[0].data, [1, 2, 3, 4, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_bad_elfs() {
+        assert!(matches!(
+            Executable::parse(&elf(0x8001_0000, &[], u32::MAX)),
+            Err(ExeError::OutsideOfRam { .. })
+        ));
+
+        let mut big_endian = elf(0x8001_0000, &[1, 2, 3, 4], 4);
+
+        big_endian[5] = 2;
+
+        assert!(matches!(
+            Executable::parse(&big_endian),
+            Err(ExeError::UnsupportedElf(_))
+        ));
+
+        let mut truncated = elf(0x8001_0000, &[1, 2, 3, 4], 4);
+
+        truncated.truncate(0x50);
+
+        assert_eq!(
+            Executable::parse(&truncated).unwrap_err(),
+            ExeError::Truncated
+        );
+    }
+
+    #[test]
+    fn parses_cpe() {
+        let mut bytes = CPE_MAGIC.to_vec();
+
+        // select unit, load data, set the pc, end
+        bytes.extend_from_slice(&[0x8, 0]);
+        bytes.push(0x1);
+        bytes.extend_from_slice(&0x8001_0000u32.to_le_bytes());
+        bytes.extend_from_slice(&4u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3, 4]);
+        bytes.push(0x3);
+        bytes.extend_from_slice(&CPE_REGISTER_PC.to_le_bytes());
+        bytes.extend_from_slice(&0x8001_0008u32.to_le_bytes());
+        bytes.push(0x0);
+
+        let exe = Executable::parse(&bytes).unwrap();
+
+        assert_eq!(exe.entry, 0x8001_0008);
+        assert_eq!(exe.segments.len(), 1);
+        assert_eq!(exe.segments[0].address, 0x8001_0000);
+        assert_eq!(exe.segments[0].data, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_bad_cpes() {
+        let mut bytes = CPE_MAGIC.to_vec();
+
+        bytes.push(0x5);
+
+        assert_eq!(
+            Executable::parse(&bytes).unwrap_err(),
+            ExeError::UnknownCpeChunk {
+                chunk: 0x5,
+                offset: 4
+            }
+        );
+
+        // no end chunk
+        assert_eq!(
+            Executable::parse(CPE_MAGIC).unwrap_err(),
+            ExeError::Truncated
+        );
+
+        let mut bytes = CPE_MAGIC.to_vec();
+
+        bytes.push(0x1);
+        bytes.extend_from_slice(&0x8001_0000u32.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        assert_eq!(Executable::parse(&bytes).unwrap_err(), ExeError::Truncated);
+    }
+}
@@ -0,0 +1,773 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use super::{
+    CPU,
+    bus::Bus,
+    cop0::{CauseRegister, StatusRegister},
+    debugger::{AccessKind, StopReason, Watchpoint},
+};
+use crate::log;
+
+// how many steps to run between checking the socket while the game is running
+const POLL_INTERVAL: u32 = 0x1000;
+
+// how long to wait for the next packet while halted before handing control back to the frontend
+const HALTED_TIMEOUT: Duration = Duration::from_millis(5);
+
+// r0-r31, sr, lo, hi, badvaddr, cause, pc, f0-f31, fcsr, fir, then epc from target.xml
+const NUM_REGISTERS: usize = 73;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+<architecture>mips:3000</architecture>
+<feature name="org.gnu.gdb.mips.cpu">
+<reg name="r0" bitsize="32" regnum="0"/>
+<reg name="r1" bitsize="32"/>
+<reg name="r2" bitsize="32"/>
+<reg name="r3" bitsize="32"/>
+<reg name="r4" bitsize="32"/>
+<reg name="r5" bitsize="32"/>
+<reg name="r6" bitsize="32"/>
+<reg name="r7" bitsize="32"/>
+<reg name="r8" bitsize="32"/>
+<reg name="r9" bitsize="32"/>
+<reg name="r10" bitsize="32"/>
+<reg name="r11" bitsize="32"/>
+<reg name="r12" bitsize="32"/>
+<reg name="r13" bitsize="32"/>
+<reg name="r14" bitsize="32"/>
+<reg name="r15" bitsize="32"/>
+<reg name="r16" bitsize="32"/>
+<reg name="r17" bitsize="32"/>
+<reg name="r18" bitsize="32"/>
+<reg name="r19" bitsize="32"/>
+<reg name="r20" bitsize="32"/>
+<reg name="r21" bitsize="32"/>
+<reg name="r22" bitsize="32"/>
+<reg name="r23" bitsize="32"/>
+<reg name="r24" bitsize="32"/>
+<reg name="r25" bitsize="32"/>
+<reg name="r26" bitsize="32"/>
+<reg name="r27" bitsize="32"/>
+<reg name="r28" bitsize="32"/>
+<reg name="r29" bitsize="32"/>
+<reg name="r30" bitsize="32"/>
+<reg name="r31" bitsize="32"/>
+<reg name="lo" bitsize="32" regnum="33"/>
+<reg name="hi" bitsize="32" regnum="34"/>
+<reg name="pc" bitsize="32" regnum="37"/>
+</feature>
+<feature name="org.gnu.gdb.mips.cp0">
+<reg name="status" bitsize="32" regnum="32"/>
+<reg name="badvaddr" bitsize="32" regnum="35"/>
+<reg name="cause" bitsize="32" regnum="36"/>
+<reg name="epc" bitsize="32" regnum="72"/>
+</feature>
+<feature name="org.gnu.gdb.mips.fpu">
+<reg name="f0" bitsize="32" type="ieee_single" regnum="38"/>
+<reg name="f1" bitsize="32" type="ieee_single"/>
+<reg name="f2" bitsize="32" type="ieee_single"/>
+<reg name="f3" bitsize="32" type="ieee_single"/>
+<reg name="f4" bitsize="32" type="ieee_single"/>
+<reg name="f5" bitsize="32" type="ieee_single"/>
+<reg name="f6" bitsize="32" type="ieee_single"/>
+<reg name="f7" bitsize="32" type="ieee_single"/>
+<reg name="f8" bitsize="32" type="ieee_single"/>
+<reg name="f9" bitsize="32" type="ieee_single"/>
+<reg name="f10" bitsize="32" type="ieee_single"/>
+<reg name="f11" bitsize="32" type="ieee_single"/>
+<reg name="f12" bitsize="32" type="ieee_single"/>
+<reg name="f13" bitsize="32" type="ieee_single"/>
+<reg name="f14" bitsize="32" type="ieee_single"/>
+<reg name="f15" bitsize="32" type="ieee_single"/>
+<reg name="f16" bitsize="32" type="ieee_single"/>
+<reg name="f17" bitsize="32" type="ieee_single"/>
+<reg name="f18" bitsize="32" type="ieee_single"/>
+<reg name="f19" bitsize="32" type="ieee_single"/>
+<reg name="f20" bitsize="32" type="ieee_single"/>
+<reg name="f21" bitsize="32" type="ieee_single"/>
+<reg name="f22" bitsize="32" type="ieee_single"/>
+<reg name="f23" bitsize="32" type="ieee_single"/>
+<reg name="f24" bitsize="32" type="ieee_single"/>
+<reg name="f25" bitsize="32" type="ieee_single"/>
+<reg name="f26" bitsize="32" type="ieee_single"/>
+<reg name="f27" bitsize="32" type="ieee_single"/>
+<reg name="f28" bitsize="32" type="ieee_single"/>
+<reg name="f29" bitsize="32" type="ieee_single"/>
+<reg name="f30" bitsize="32" type="ieee_single"/>
+<reg name="f31" bitsize="32" type="ieee_single"/>
+<reg name="fcsr" bitsize="32" group="float"/>
+<reg name="fir" bitsize="32" group="float"/>
+</feature>
+</target>"#;
+
+enum Received {
+    Data,
+    Nothing,
+    Disconnected,
+}
+
+/*
+  GDB remote serial protocol server, see https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+  The stub gets polled from the start of CPU::step, and any breakpoints, watchpoints or
+  steps requested by gdb go through the CPU's debugger.
+*/
+pub struct GdbServer {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    buffer: Vec<u8>,
+    waiting_for_stop: bool,
+    poll_counter: u32,
+    // only what gdb added gets removed when it disconnects, the frontend might have its own
+    breakpoints: Vec<u32>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |acc: u8, byte| acc.wrapping_add(byte))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_u32(hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex, 16).ok()
+}
+
+// parses "addr,len" pairs used by the memory and breakpoint packets
+fn parse_address_length(args: &str) -> Option<(u32, u32)> {
+    let (address, length) = args.split_once(',')?;
+
+    Some((parse_u32(address)?, parse_u32(length)?))
+}
+
+impl GdbServer {
+    pub fn listen(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            stream: None,
+            buffer: Vec::new(),
+            waiting_for_stop: false,
+            poll_counter: 0,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn poll(&mut self, cpu: &mut CPU) {
+        let halted = cpu.debugger.is_halted();
+
+        if !halted {
+            self.poll_counter += 1;
+
+            if self.poll_counter < POLL_INTERVAL {
+                return;
+            }
+
+            self.poll_counter = 0;
+        }
+
+        if self.stream.is_none() {
+            let Ok((stream, _)) = self.listener.accept() else {
+                return;
+            };
+
+            log!(Cpu, Warn, "gdb connected");
+
+            stream.set_nodelay(true).ok();
+
+            self.stream = Some(stream);
+            self.buffer.clear();
+            self.waiting_for_stop = false;
+
+            // gdb expects the target to be stopped when it attaches
+            cpu.debugger.halt(StopReason::Interrupted);
+        }
+
+        if self.waiting_for_stop && cpu.debugger.is_halted() {
+            self.waiting_for_stop = false;
+
+            if let Some(reason) = cpu.debugger.stop_reason() {
+                self.send_packet(&Self::stop_reply(reason));
+            }
+        }
+
+        loop {
+            match self.receive(cpu.debugger.is_halted()) {
+                Received::Disconnected => {
+                    self.disconnect(cpu);
+                    return;
+                }
+                Received::Nothing => return,
+                Received::Data => self.process_packets(cpu),
+            }
+
+            if !cpu.debugger.is_halted() {
+                return;
+            }
+        }
+    }
+
+    fn disconnect(&mut self, cpu: &mut CPU) {
+        log!(Cpu, Warn, "gdb disconnected");
+
+        self.stream = None;
+        self.waiting_for_stop = false;
+
+        for address in self.breakpoints.drain(..) {
+            cpu.debugger.remove_breakpoint(address);
+        }
+
+        for watchpoint in self.watchpoints.drain(..) {
+            cpu.debugger
+                .remove_watchpoint(watchpoint.address, watchpoint.len, watchpoint.kind);
+        }
+
+        cpu.debugger.resume(cpu.pc);
+    }
+
+    fn receive(&mut self, blocking: bool) -> Received {
+        let Some(stream) = &mut self.stream else {
+            return Received::Nothing;
+        };
+
+        if blocking {
+            stream.set_nonblocking(false).ok();
+            stream.set_read_timeout(Some(HALTED_TIMEOUT)).ok();
+        } else {
+            stream.set_nonblocking(true).ok();
+        }
+
+        let mut bytes = [0; 0x1000];
+
+        match stream.read(&mut bytes) {
+            Ok(0) => Received::Disconnected,
+            Ok(len) => {
+                self.buffer.extend_from_slice(&bytes[..len]);
+                Received::Data
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Received::Nothing
+            }
+            Err(_) => Received::Disconnected,
+        }
+    }
+
+    fn send(&mut self, bytes: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            stream.set_nonblocking(false).ok();
+
+            // a dropped connection gets noticed by the next read
+            stream.write_all(bytes).ok();
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) {
+        let packet = format!("${data}#{:02x}", checksum(data));
+
+        self.send(packet.as_bytes());
+    }
+
+    fn process_packets(&mut self, cpu: &mut CPU) {
+        loop {
+            let Some(start) = self
+                .buffer
+                .iter()
+                .position(|&byte| byte == b'$' || byte == 0x3)
+            else {
+                // only acks left
+                self.buffer.clear();
+                return;
+            };
+
+            if self.buffer[start] == 0x3 {
+                self.buffer.drain(..=start);
+
+                if !cpu.debugger.is_halted() {
+                    cpu.debugger.halt(StopReason::Interrupted);
+                }
+
+                continue;
+            }
+
+            let Some(end) = self.buffer[start..].iter().position(|&byte| byte == b'#') else {
+                return;
+            };
+
+            let end = start + end;
+
+            // wait for the checksum to arrive
+            if self.buffer.len() < end + 3 {
+                return;
+            }
+
+            let packet = String::from_utf8_lossy(&self.buffer[start + 1..end]).to_string();
+            let expected = std::str::from_utf8(&self.buffer[end + 1..end + 3])
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+            self.buffer.drain(..end + 3);
+
+            if expected != Some(checksum(&packet)) {
+                self.send(b"-");
+                continue;
+            }
+
+            self.send(b"+");
+
+            if let Some(reply) = self.handle_packet(cpu, &packet) {
+                self.send_packet(&reply);
+            }
+        }
+    }
+
+    fn stop_reply(reason: StopReason) -> String {
+        match reason {
+            StopReason::Interrupted => "S02".to_string(),
+            StopReason::Breakpoint { .. } | StopReason::Step => "S05".to_string(),
+            StopReason::HistoryStart => "T05replaylog:begin;".to_string(),
+            StopReason::Watchpoint { address, kind } => {
+                let kind = match kind {
+                    AccessKind::Write => "watch",
+                    AccessKind::Read => "rwatch",
+                    AccessKind::Access => "awatch",
+                };
+
+                format!("T05{kind}:{address:08x};")
+            }
+        }
+    }
+
+    // returns None for packets that don't get an immediate reply
+    fn handle_packet(&mut self, cpu: &mut CPU, packet: &str) -> Option<String> {
+        // an empty packet has a valid checksum too, it just doesn't ask for anything
+        let Some(first) = packet.chars().next() else {
+            return Some(String::new());
+        };
+
+        let (command, args) = packet.split_at(first.len_utf8());
+
+        let reply = match command {
+            "?" => Self::stop_reply(
+                cpu.debugger
+                    .stop_reason()
+                    .unwrap_or(StopReason::Interrupted),
+            ),
+            "g" => (0..NUM_REGISTERS)
+                .map(|index| encode_hex(&Self::read_register(cpu, index).to_le_bytes()))
+                .collect(),
+            "G" => match decode_hex(args) {
+                Some(bytes) => {
+                    for (index, value) in bytes.chunks_exact(4).enumerate() {
+                        let value = u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+                        Self::write_register(cpu, index, value);
+                    }
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            },
+            "p" => match parse_u32(args) {
+                Some(index) if (index as usize) < NUM_REGISTERS => {
+                    encode_hex(&Self::read_register(cpu, index as usize).to_le_bytes())
+                }
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let register = args.split_once('=').and_then(|(index, value)| {
+                    let bytes = decode_hex(value)?;
+
+                    Some((parse_u32(index)?, bytes))
+                });
+
+                match register {
+                    Some((index, bytes)) if bytes.len() == 4 => {
+                        let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                        Self::write_register(cpu, index as usize, value);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "m" => match parse_address_length(args) {
+                Some((address, length)) => {
+                    // stop at the first address that can't be read without side effects
+                    let bytes: Vec<u8> = (0..length)
+                        .map_while(|i| cpu.bus.peek8(address.wrapping_add(i)))
+                        .collect();
+
+                    if bytes.is_empty() && length > 0 {
+                        "E01".to_string()
+                    } else {
+                        encode_hex(&bytes)
+                    }
+                }
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let write = args.split_once(':').and_then(|(location, data)| {
+                    let (address, _) = parse_address_length(location)?;
+
+                    Some((address, decode_hex(data)?))
+                });
+
+                // only RAM and the scratchpad, writing an I/O port from the debugger would run its side effects
+                match write {
+                    Some((address, bytes))
+                        if (0..bytes.len() as u32)
+                            .all(|i| Bus::is_memory(address.wrapping_add(i))) =>
+                    {
+                        for (i, byte) in bytes.into_iter().enumerate() {
+                            cpu.bus.poke8(address.wrapping_add(i as u32), byte);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "c" | "s" => {
+                if let Some(address) = parse_u32(args) {
+                    Self::write_register(cpu, 37, address);
+                }
+
+                if command == "c" {
+                    cpu.debugger.resume(cpu.pc);
+                } else {
+                    cpu.debugger.step(cpu.pc);
+                }
+
+                self.waiting_for_stop = true;
+
+                return None;
+            }
+            // reverse step and continue both finish straight away
+            "b" => {
+                let result = match args {
+                    "s" => cpu.step_back().map(|_| StopReason::Step),
+                    "c" => cpu.reverse_continue(),
+                    _ => return Some(String::new()),
+                };
+
+                match result {
+                    Ok(reason) => Self::stop_reply(reason),
+                    Err(_) => "E01".to_string(),
+                }
+            }
+            "Z" | "z" => {
+                let breakpoint = args.split_once(',').and_then(|(kind, location)| {
+                    Some((kind.parse::<u32>().ok()?, parse_address_length(location)?))
+                });
+
+                let insert = command == "Z";
+
+                match breakpoint {
+                    Some((0 | 1, (address, _))) => {
+                        let owned = self.breakpoints.iter().position(|&a| a == address);
+
+                        if insert {
+                            if owned.is_none() && !cpu.debugger.has_breakpoint(address) {
+                                cpu.debugger.add_breakpoint(address);
+                                self.breakpoints.push(address);
+                            }
+                        } else if let Some(index) = owned {
+                            cpu.debugger.remove_breakpoint(address);
+                            self.breakpoints.swap_remove(index);
+                        }
+                        "OK".to_string()
+                    }
+                    Some((kind @ 2..=4, (address, length))) => {
+                        let kind = match kind {
+                            2 => AccessKind::Write,
+                            3 => AccessKind::Read,
+                            _ => AccessKind::Access,
+                        };
+
+                        let watchpoint = Watchpoint {
+                            address,
+                            len: length,
+                            kind,
+                        };
+                        let owned = self.watchpoints.iter().position(|&w| w == watchpoint);
+
+                        if insert {
+                            if owned.is_none() && !cpu.debugger.watchpoints().contains(&watchpoint)
+                            {
+                                cpu.debugger.add_watchpoint(address, length, kind);
+                                self.watchpoints.push(watchpoint);
+                            }
+                        } else if let Some(index) = owned {
+                            cpu.debugger.remove_watchpoint(address, length, kind);
+                            self.watchpoints.swap_remove(index);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => String::new(),
+                }
+            }
+            "D" => {
+                self.send_packet("OK");
+                self.disconnect(cpu);
+
+                return None;
+            }
+            "k" => {
+                self.disconnect(cpu);
+
+                return None;
+            }
+            "H" => "OK".to_string(),
+            "q" => self.handle_query(packet),
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    fn handle_query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=4000;qXfer:features:read+;ReverseStep+;ReverseContinue+"
+                .to_string();
+        }
+
+        if packet == "qAttached" {
+            return "1".to_string();
+        }
+
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = parse_address_length(args) else {
+                return "E01".to_string();
+            };
+
+            let offset = (offset as usize).min(TARGET_XML.len());
+            let end = (offset + length as usize).min(TARGET_XML.len());
+
+            let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+
+            return format!("{prefix}{}", &TARGET_XML[offset..end]);
+        }
+
+        String::new()
+    }
+
+    fn read_register(cpu: &CPU, index: usize) -> u32 {
+        match index {
+            0..=31 => cpu.r.0[index],
+            32 => cpu.cop0.sr.bits(),
+            33 => cpu.lo,
+            34 => cpu.hi,
+            35 => cpu.cop0.bad_addr,
+            36 => cpu.cop0.cause.bits(),
+            37 => cpu.pc,
+            72 => cpu.cop0.epc,
+            // there's no FPU, so f0-f31, fcsr and fir all read as zero
+            _ => 0,
+        }
+    }
+
+    fn write_register(cpu: &mut CPU, index: usize, value: u32) {
+        match index {
+            1..=31 => cpu.r[index] = value,
+            32 => cpu.cop0.sr = StatusRegister::from_bits_retain(value),
+            33 => cpu.lo = value,
+            34 => cpu.hi = value,
+            35 => cpu.cop0.bad_addr = value,
+            36 => cpu.cop0.cause = CauseRegister::from_bits_retain(value),
+            37 => {
+                cpu.pc = value;
+                cpu.next_pc = value.wrapping_add(4);
+            }
+            72 => cpu.cop0.epc = value,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(data: &str) -> String {
+        format!("${data}#{:02x}", checksum(data))
+    }
+
+    fn connect() -> (GdbServer, TcpStream) {
+        let mut server = GdbServer::listen(0).unwrap();
+        let client = TcpStream::connect(server.listener.local_addr().unwrap()).unwrap();
+
+        server.listener.set_nonblocking(false).unwrap();
+        server.stream = Some(server.listener.accept().unwrap().0);
+
+        client
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+
+        (server, client)
+    }
+
+    // everything the server sent back for the bytes it got
+    fn exchange(
+        server: &mut GdbServer,
+        client: &mut TcpStream,
+        cpu: &mut CPU,
+        input: &[u8],
+    ) -> String {
+        server.buffer.extend_from_slice(input);
+        server.process_packets(cpu);
+
+        let mut output = Vec::new();
+        let mut bytes = [0; 0x1000];
+
+        while let Ok(len) = client.read(&mut bytes) {
+            if len == 0 {
+                break;
+            }
+
+            output.extend_from_slice(&bytes[..len]);
+        }
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn checksums_and_hex() {
+        assert_eq!(checksum(""), 0);
+        assert_eq!(checksum("?"), 0x3f);
+        assert_eq!(checksum("S02"), 0xb5);
+        assert_eq!(encode_hex(&[0x12, 0xab]), "12ab");
+        assert_eq!(decode_hex("12ab"), Some(vec![0x12, 0xab]));
+        assert_eq!(decode_hex("1g"), None);
+        assert_eq!(parse_address_length("80000100,4"), Some((0x8000_0100, 4)));
+        assert_eq!(parse_address_length("80000100"), None);
+    }
+
+    #[test]
+    fn frames_packets() {
+        let mut cpu = CPU::new(None, String::new());
+        let (mut server, mut client) = connect();
+
+        assert_eq!(
+            exchange(&mut server, &mut client, &mut cpu, b"+$?#3f"),
+            format!("+{}", packet("S02"))
+        );
+
+        // a bad checksum gets a nak and no reply
+        assert_eq!(exchange(&mut server, &mut client, &mut cpu, b"$?#00"), "-");
+
+        // nothing happens until the checksum is complete
+        assert_eq!(exchange(&mut server, &mut client, &mut cpu, b"$?#3"), "");
+        assert_eq!(
+            exchange(&mut server, &mut client, &mut cpu, b"f"),
+            format!("+{}", packet("S02"))
+        );
+
+        // empty and multi-byte packets mustn't take the stub down
+        assert_eq!(
+            exchange(&mut server, &mut client, &mut cpu, packet("").as_bytes()),
+            format!("+{}", packet(""))
+        );
+        assert!(
+            exchange(&mut server, &mut client, &mut cpu, packet("éx").as_bytes()).starts_with("+$")
+        );
+
+        assert!(!cpu.debugger.is_halted());
+        assert_eq!(exchange(&mut server, &mut client, &mut cpu, &[0x3]), "");
+        assert!(cpu.debugger.is_halted());
+    }
+
+    #[test]
+    fn reads_and_writes_memory() {
+        let mut cpu = CPU::new(None, String::new());
+        let (mut server, mut client) = connect();
+
+        assert_eq!(
+            exchange(
+                &mut server,
+                &mut client,
+                &mut cpu,
+                packet("M80000100,2:1234").as_bytes()
+            ),
+            format!("+{}", packet("OK"))
+        );
+        assert_eq!(cpu.bus.main_ram[0x100..0x102], [0x12, 0x34]);
+        assert_eq!(
+            exchange(
+                &mut server,
+                &mut client,
+                &mut cpu,
+                packet("m80000100,2").as_bytes()
+            ),
+            format!("+{}", packet("1234"))
+        );
+
+        // I/O ports can't be written from the debugger
+        assert_eq!(
+            exchange(
+                &mut server,
+                &mut client,
+                &mut cpu,
+                packet("M1f801070,1:ff").as_bytes()
+            ),
+            format!("+{}", packet("E01"))
+        );
+    }
+
+    #[test]
+    fn only_removes_its_own_breakpoints_on_disconnect() {
+        let mut cpu = CPU::new(None, String::new());
+        let (mut server, mut client) = connect();
+
+        cpu.debugger.add_breakpoint(0x8001_0000);
+        cpu.debugger
+            .add_watchpoint(0x8002_0000, 4, AccessKind::Write);
+
+        for data in [
+            "Z0,80010000,4",
+            "Z0,80010010,4",
+            "Z2,80020000,4",
+            "Z3,80030000,4",
+            "z0,80010000,4",
+        ] {
+            assert_eq!(
+                exchange(&mut server, &mut client, &mut cpu, packet(data).as_bytes()),
+                format!("+{}", packet("OK"))
+            );
+        }
+
+        // the frontend's breakpoint is still there, gdb never owned it
+        assert!(cpu.debugger.has_breakpoint(0x8001_0000));
+        assert!(cpu.debugger.has_breakpoint(0x8001_0010));
+        assert_eq!(cpu.debugger.watchpoints().len(), 2);
+
+        exchange(&mut server, &mut client, &mut cpu, packet("k").as_bytes());
+
+        assert!(!server.is_connected());
+        assert!(cpu.debugger.has_breakpoint(0x8001_0000));
+        assert!(!cpu.debugger.has_breakpoint(0x8001_0010));
+        assert_eq!(
+            cpu.debugger.watchpoints(),
+            [Watchpoint {
+                address: 0x8002_0000,
+                len: 4,
+                kind: AccessKind::Write
+            }]
+        );
+    }
+}
@@ -1,8 +1,13 @@
 use std::{cmp, collections::HashMap};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
 
-use super::Instruction;
+use super::{
+    Instruction,
+    logger::{self, Channel, Level},
+};
+use crate::log;
 
 // see https://psx-spx.consoledev.net/geometrytransformationenginegte/#gte-division-inaccuracy
 const UNR_TABLE: [u8; 0x101] = [
@@ -25,6 +30,18 @@ const UNR_TABLE: [u8; 0x101] = [
     0x00,
 ];
 
+const DATA_REGISTER_NAMES: [&str; 32] = [
+    "vxy0", "vz0", "vxy1", "vz1", "vxy2", "vz2", "rgbc", "otz", "ir0", "ir1", "ir2", "ir3", "sxy0",
+    "sxy1", "sxy2", "sxyp", "sz0", "sz1", "sz2", "sz3", "rgb0", "rgb1", "rgb2", "res1", "mac0",
+    "mac1", "mac2", "mac3", "irgb", "orgb", "lzcs", "lzcr",
+];
+
+const CONTROL_REGISTER_NAMES: [&str; 32] = [
+    "rt11rt12", "rt13rt21", "rt22rt23", "rt31rt32", "rt33", "trx", "try", "trz", "l11l12",
+    "l13l21", "l22l23", "l31l32", "l33", "rbk", "gbk", "bbk", "lr1lr2", "lr3lg1", "lg2lg3",
+    "lb1lb2", "lb3", "rfc", "gfc", "bfc", "ofx", "ofy", "h", "dqa", "dqb", "zsf3", "zsf4", "flag",
+];
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 struct Rgb {
     r: u8,
@@ -35,7 +52,6 @@ struct Rgb {
 
 #[derive(Serialize, Deserialize)]
 pub struct Gte {
-    pub debug_on: bool,
     executed_commands: HashMap<u32, bool>,
     zsf3: i16,
     zsf4: i16,
@@ -118,7 +134,6 @@ impl Gte {
             mac: [0; 4],
             lzcs: 0,
             lzcr: 0,
-            debug_on: false,
             executed_commands: HashMap::new(),
         }
     }
@@ -138,9 +153,9 @@ impl Gte {
 
         self.flags = 0;
 
-        if self.debug_on {
+        if logger::enabled(Channel::Cpu, Level::Debug) {
             self.executed_commands.entry(op_code).or_insert_with(|| {
-                println!("executed {op_code:X}");
+                log!(Cpu, Debug, "executed GTE command {op_code:X}");
                 true
             });
         }
@@ -1291,7 +1306,7 @@ impl Gte {
         val as u8
     }
 
-    pub fn read_data(&mut self, destination: usize) -> u32 {
+    pub fn read_data(&self, destination: usize) -> u32 {
         match destination {
             0 => (self.v[0].0 as u16 as u32) | (self.v[0].1 as u16 as u32) << 16,
             1 => self.v[0].2 as u32,
@@ -1526,4 +1541,35 @@ impl Gte {
             _ => unreachable!("can't happen"),
         }
     }
+
+    // every register as the CPU would read it, along with the matrices and vectors decoded
+    pub fn inspect(&self) -> Value {
+        let registers = |names: [&str; 32], read: &dyn Fn(usize) -> u32| {
+            let mut registers = Map::new();
+
+            for (i, name) in names.iter().enumerate() {
+                registers.insert(name.to_string(), json!(format!("0x{:08x}", read(i))));
+            }
+
+            Value::Object(registers)
+        };
+
+        json!({
+            "data": registers(DATA_REGISTER_NAMES, &|i| self.read_data(i)),
+            "control": registers(CONTROL_REGISTER_NAMES, &|i| self.read_control(i)),
+            "rotation": self.rotation,
+            "light": self.light,
+            "color": self.color,
+            "translation": self.tr,
+            "background_color": self.bk,
+            "far_color": self.fc,
+            "vectors": self.v,
+            "screen_offset": (self.ofx, self.ofy),
+            "projection_distance": self.h,
+            "mac": self.mac,
+            "ir": self.ir,
+            "sxy_fifo": self.sxy_fifo,
+            "sz_fifo": self.sz_fifo,
+        })
+    }
 }
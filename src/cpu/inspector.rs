@@ -0,0 +1,127 @@
+use serde_json::{Map, Value, json};
+
+use super::{CPU, debugger::condition::REGISTER_NAMES};
+
+// memory blocks only get their size and a hash, which is enough to tell whether two snapshots differ
+pub(crate) fn summarize_memory(bytes: &[u8]) -> Value {
+    // FNV-1a
+    let hash = bytes.iter().fold(0x811c_9dc5u32, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+
+    json!({
+        "size": bytes.len(),
+        "hash": format!("0x{hash:08x}"),
+    })
+}
+
+/*
+  A snapshot of the registers and internal state of every component, meant for people (and
+  scripts) poking at what the console is doing rather than for restoring it, which is what save
+  states are for. Addresses and registers are shown as hex strings, counts and coordinates as
+  numbers.
+*/
+impl CPU {
+    pub fn inspect(&self) -> Value {
+        let mut registers = Map::new();
+
+        for (i, name) in REGISTER_NAMES.iter().enumerate() {
+            registers.insert(name.to_string(), json!(format!("0x{:08x}", self.r[i])));
+        }
+
+        registers.insert("hi".to_string(), json!(format!("0x{:08x}", self.hi)));
+        registers.insert("lo".to_string(), json!(format!("0x{:08x}", self.lo)));
+
+        let cop0 = &self.cop0;
+
+        let timers: Vec<Value> = self
+            .bus
+            .timers
+            .iter()
+            .map(|timer| timer.inspect())
+            .collect();
+
+        json!({
+            "cpu": {
+                "pc": format!("0x{:08x}", self.pc),
+                "next_pc": format!("0x{:08x}", self.next_pc),
+                "registers": registers,
+                "delayed_load": self.delayed_load.map(|(register, value)| {
+                    json!({
+                        "register": REGISTER_NAMES[register],
+                        "value": format!("0x{value:08x}"),
+                    })
+                }),
+                "in_delay_slot": self.in_delay_slot,
+                "instruction_count": self.instruction_count,
+                "cycles": self.bus.scheduler.cycles,
+            },
+            "cop0": {
+                "sr": format!("0x{:08x}", cop0.sr.bits()),
+                "cause": format!("0x{:08x}", cop0.cause.bits()),
+                "epc": format!("0x{:08x}", cop0.epc),
+                "badvaddr": format!("0x{:08x}", cop0.bad_addr),
+                "dcic": format!("0x{:08x}", cop0.dcic),
+                "bpc": format!("0x{:08x}", cop0.bpc),
+                "bpcm": format!("0x{:08x}", cop0.bpcm),
+                "bda": format!("0x{:08x}", cop0.bda),
+                "bdam": format!("0x{:08x}", cop0.bdam),
+                "tar": format!("0x{:08x}", cop0.tar),
+            },
+            "gte": self.gte.inspect(),
+            "interrupts": {
+                "i_stat": format!("0x{:04x}", self.bus.interrupt_stat.bits()),
+                "i_mask": format!("0x{:04x}", self.bus.interrupt_mask.bits()),
+            },
+            "dma": self.bus.dma.inspect(),
+            "timers": timers,
+            "gpu": self.bus.gpu.inspect(),
+            "cdrom": self.bus.cdrom.inspect(),
+            "spu": self.bus.spu.inspect(),
+            "mdec": self.bus.mdec.inspect(),
+            "sio": self.bus.peripherals.inspect(),
+            "memory": {
+                "main_ram": summarize_memory(&self.bus.main_ram),
+                "scratchpad": summarize_memory(&self.bus.scratchpad),
+            },
+        })
+    }
+
+    pub fn inspect_json(&self) -> String {
+        serde_json::to_string_pretty(&self.inspect()).unwrap()
+    }
+
+    /*
+      A 64-bit FNV-1a hash of the registers and every block of memory, meant for telling whether two
+      runs ended up in the same place. VRAM only counts with the software renderer, the hardware
+      renderers keep theirs on the GPU. Unlike inspect it leaves out counters and the internal
+      state of the other components, so hashes stay comparable as those get more detailed.
+    */
+    pub fn state_hash(&self) -> u64 {
+        let registers: Vec<u8> = self
+            .r
+            .0
+            .iter()
+            .chain([&self.hi, &self.lo, &self.pc])
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+
+        #[allow(unused_mut)]
+        let mut blocks: Vec<&[u8]> = vec![
+            &registers,
+            &self.bus.main_ram,
+            &self.bus.scratchpad,
+            self.bus.spu.sound_ram(),
+        ];
+
+        #[cfg(feature = "software_gpu")]
+        blocks.push(&self.bus.gpu.vram);
+
+        blocks
+            .iter()
+            .flat_map(|block| block.iter())
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+}
@@ -1,7 +1,4 @@
-#[cfg(feature = "debug")]
-use crate::cpu::{OriginKind, OriginNode, ram_off};
-
-use super::{CPU, ExceptionType, RA_REGISTER};
+use super::{CPU, ExceptionType, RA_REGISTER, perf::Subsystem};
 pub struct Instruction(pub u32);
 
 impl Instruction {
@@ -33,6 +30,10 @@ impl Instruction {
         self.0 & 0x3ffffff
     }
 
+    pub fn break_code(&self) -> u32 {
+        (self.0 >> 6) & 0xfffff
+    }
+
     pub fn cop2_command(&self) -> u32 {
         self.0 & 0x3ff_ffff
     }
@@ -43,6 +44,15 @@ impl Instruction {
     pub fn bcond(&self) -> u32 {
         (self.0 >> 16) & 0b1
     }
+
+    // any branch or jump, taken or not, which means the next instruction is a delay slot
+    pub fn is_branch(&self) -> bool {
+        match self.0 >> 26 {
+            0 => matches!(self.0 & 0x3f, 0x8 | 0x9),
+            0x1..=0x7 => true,
+            _ => false,
+        }
+    }
 }
 
 impl CPU {
@@ -289,7 +299,10 @@ impl CPU {
                 .write_control(instruction.rd(), self.r[instruction.rt()]),
             _ => {
                 if cop_code & 0x10 == 0x10 {
-                    cycles = self.gte.execute_command(instruction)
+                    self.bus.perf.begin(Subsystem::Gte);
+                    cycles = self.gte.execute_command(instruction);
+                    self.bus.perf.add_cycles(Subsystem::Gte, cycles as u64);
+                    self.bus.perf.end();
                 }
             }
         }
@@ -307,30 +320,7 @@ impl CPU {
         let value = self.load8(address) as i8 as i16 as i32 as u32;
 
         #[cfg(feature = "debug")]
-        if address >> 16 == 0x1f80 {
-            let id = self.add_origin(OriginNode {
-                pc: self.previous_pc,
-                opcode: instruction.0,
-                value,
-                kind: OriginKind::MmioRead { addr: address },
-            });
-
-            self.reg_origin[instruction.rt()] = id;
-        } else {
-            let mem_origin = self.ram_origin[ram_off(address)];
-
-            let id = self.add_origin(OriginNode {
-                pc: self.previous_pc,
-                opcode: instruction.0,
-                value,
-                kind: OriginKind::Load {
-                    addr: address,
-                    mem: mem_origin,
-                },
-            });
-
-            self.reg_origin[instruction.rt()] = id;
-        }
+        self.track_load(instruction.0, instruction.rt(), address, value);
 
         self.update_load(instruction.rt(), value);
 
@@ -343,30 +333,7 @@ impl CPU {
             let value = self.load16(address) as i16 as i32 as u32;
 
             #[cfg(feature = "debug")]
-            if address >> 16 == 0x1f80 {
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::MmioRead { addr: address },
-                });
-
-                self.reg_origin[instruction.rt()] = id;
-            } else {
-                let mem_origin = self.ram_origin[ram_off(address)];
-
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::Load {
-                        addr: address,
-                        mem: mem_origin,
-                    },
-                });
-
-                self.reg_origin[instruction.rt()] = id;
-            }
+            self.track_load(instruction.0, instruction.rt(), address, value);
 
             self.update_load(instruction.rt(), value);
         } else {
@@ -408,30 +375,7 @@ impl CPU {
             let value = self.load32(address);
 
             #[cfg(feature = "debug")]
-            if address >> 16 == 0x1f80 {
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::MmioRead { addr: address },
-                });
-
-                self.reg_origin[instruction.rt()] = id;
-            } else {
-                let mem_origin = self.ram_origin[ram_off(address)];
-
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::Load {
-                        addr: address,
-                        mem: mem_origin,
-                    },
-                });
-
-                self.reg_origin[instruction.rt()] = id;
-            }
+            self.track_load(instruction.0, instruction.rt(), address, value);
 
             self.update_load(instruction.rt(), value);
         } else {
@@ -448,30 +392,7 @@ impl CPU {
         let value = self.load8(address);
 
         #[cfg(feature = "debug")]
-        if address >> 16 == 0x1f80 {
-            let id = self.add_origin(OriginNode {
-                pc: self.previous_pc,
-                opcode: instruction.0,
-                value,
-                kind: OriginKind::MmioRead { addr: address },
-            });
-
-            self.reg_origin[instruction.rt()] = id;
-        } else {
-            let mem_origin = self.ram_origin[ram_off(address)];
-
-            let id = self.add_origin(OriginNode {
-                pc: self.previous_pc,
-                opcode: instruction.0,
-                value,
-                kind: OriginKind::Load {
-                    addr: address,
-                    mem: mem_origin,
-                },
-            });
-
-            self.reg_origin[instruction.rt()] = id;
-        }
+        self.track_load(instruction.0, instruction.rt(), address, value);
 
         self.update_load(instruction.rt(), value);
 
@@ -485,30 +406,7 @@ impl CPU {
             let value = self.load16(address);
 
             #[cfg(feature = "debug")]
-            if address >> 16 == 0x1f80 {
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::MmioRead { addr: address },
-                });
-
-                self.reg_origin[instruction.rt()] = id;
-            } else {
-                let mem_origin = self.ram_origin[ram_off(address)];
-
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::Load {
-                        addr: address,
-                        mem: mem_origin,
-                    },
-                });
-
-                self.reg_origin[instruction.rt()] = id;
-            }
+            self.track_load(instruction.0, instruction.rt(), address, value);
             self.update_load(instruction.rt(), value);
         } else {
             self.cop0.bad_addr = address;
@@ -552,21 +450,7 @@ impl CPU {
         self.store8(address, value);
 
         #[cfg(feature = "debug")]
-        {
-            let src_origin = self.reg_origin[instruction.rt()];
-
-            let id = self.add_origin(OriginNode {
-                pc: self.previous_pc,
-                opcode: instruction.0,
-                value: value as u32,
-                kind: OriginKind::Store {
-                    addr: address,
-                    src: src_origin,
-                },
-            });
-
-            self.ram_origin[ram_off(address)] = id;
-        }
+        self.track_store(instruction.0, instruction.rt(), address, value as u32, 1);
 
         2
     }
@@ -578,21 +462,13 @@ impl CPU {
             self.store16(address, self.r[instruction.rt()] as u16);
 
             #[cfg(feature = "debug")]
-            {
-                let src_origin = self.reg_origin[instruction.rt()];
-
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value: self.r[instruction.rt()] as u16 as u32,
-                    kind: OriginKind::Store {
-                        addr: address,
-                        src: src_origin,
-                    },
-                });
-
-                self.ram_origin[ram_off(address)] = id;
-            }
+            self.track_store(
+                instruction.0,
+                instruction.rt(),
+                address,
+                self.r[instruction.rt()] as u16 as u32,
+                2,
+            );
         } else {
             self.cop0.bad_addr = address;
             self.enter_exception(ExceptionType::StoreAddressError);
@@ -629,21 +505,7 @@ impl CPU {
             self.store32(address, value);
 
             #[cfg(feature = "debug")]
-            {
-                let src_origin = self.reg_origin[instruction.rt()];
-
-                let id = self.add_origin(OriginNode {
-                    pc: self.previous_pc,
-                    opcode: instruction.0,
-                    value,
-                    kind: OriginKind::Store {
-                        addr: address,
-                        src: src_origin,
-                    },
-                });
-
-                self.ram_origin[ram_off(address)] = id;
-            }
+            self.track_store(instruction.0, instruction.rt(), address, value, 4);
         } else {
             self.cop0.bad_addr = address;
             self.enter_exception(ExceptionType::StoreAddressError);
@@ -796,7 +658,12 @@ impl CPU {
         2
     }
 
-    pub fn break_(&mut self, _instruction: Instruction) -> usize {
+    pub fn break_(&mut self, instruction: Instruction) -> usize {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.handle_pcdrv(instruction.break_code()) {
+            return 2;
+        }
+
         self.enter_exception(ExceptionType::Break);
 
         2
@@ -0,0 +1,314 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+    },
+};
+
+// how many messages the ring buffer keeps, older ones get dropped as new ones come in
+pub const LOG_CAPACITY: usize = 1000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Channel {
+    Cpu,
+    Gpu,
+    Cdrom,
+    Spu,
+    Dma,
+    // controllers and memory cards
+    Sio,
+    Mdec,
+    // whatever the guest prints to the TTY
+    Kernel,
+}
+
+pub const CHANNELS: [Channel; 8] = [
+    Channel::Cpu,
+    Channel::Gpu,
+    Channel::Cdrom,
+    Channel::Spu,
+    Channel::Dma,
+    Channel::Sio,
+    Channel::Mdec,
+    Channel::Kernel,
+];
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::Cpu => "cpu",
+            Channel::Gpu => "gpu",
+            Channel::Cdrom => "cdrom",
+            Channel::Spu => "spu",
+            Channel::Dma => "dma",
+            Channel::Sio => "sio",
+            Channel::Mdec => "mdec",
+            Channel::Kernel => "kernel",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+// from most to least important, a channel logs everything up to the level it's set to
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+const LEVELS: [Level; 5] = [
+    Level::Error,
+    Level::Warn,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub channel: Channel,
+    pub level: Level,
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}][{}] {}", self.level, self.channel, self.message)
+    }
+}
+
+struct LogBuffer {
+    entries: VecDeque<LogEntry>,
+    // how many of the entries at the end haven't been taken yet
+    unread: usize,
+}
+
+/*
+  The log is global rather than part of the console, so that anything (the controller, the
+  rasterizer, ...) can write to it without a way back to the bus. The level of each channel is an
+  atomic that gets checked before the message is even formatted, so a disabled channel costs a
+  load and a compare. By default every channel logs warnings and errors, and the kernel channel
+  the TTY output as well.
+*/
+static CHANNEL_LEVELS: [AtomicU8; CHANNELS.len()] = [
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Warn as u8),
+    AtomicU8::new(Level::Info as u8),
+];
+
+static ECHO: AtomicBool = AtomicBool::new(true);
+
+static LOG: Mutex<LogBuffer> = Mutex::new(LogBuffer {
+    entries: VecDeque::new(),
+    unread: 0,
+});
+
+// logs a message if its channel is enabled at that level, ie log!(Cdrom, Warn, "got 0x{value:x}")
+#[macro_export]
+macro_rules! log {
+    ($channel:ident, $level:ident, $($arg:tt)*) => {
+        if $crate::cpu::logger::enabled(
+            $crate::cpu::logger::Channel::$channel,
+            $crate::cpu::logger::Level::$level,
+        ) {
+            $crate::cpu::logger::write(
+                $crate::cpu::logger::Channel::$channel,
+                $crate::cpu::logger::Level::$level,
+                format!($($arg)*),
+            );
+        }
+    };
+}
+
+#[inline(always)]
+pub fn enabled(channel: Channel, level: Level) -> bool {
+    level as u8 <= CHANNEL_LEVELS[channel as usize].load(Ordering::Relaxed)
+}
+
+pub fn level(channel: Channel) -> Option<Level> {
+    let level = CHANNEL_LEVELS[channel as usize].load(Ordering::Relaxed);
+
+    LEVELS.into_iter().find(|&l| l as u8 == level)
+}
+
+// None turns the channel off entirely
+pub fn set_level(channel: Channel, level: Option<Level>) {
+    CHANNEL_LEVELS[channel as usize].store(level.map_or(0, |level| level as u8), Ordering::Relaxed);
+}
+
+/*
+  Sets levels from a list like "info,cdrom=debug,kernel=off". A level on its own applies to every
+  channel and later entries override earlier ones. Nothing changes if any of it is invalid.
+*/
+pub fn apply_filters(filters: &str) -> Result<(), String> {
+    let parse_level = |name: &str| -> Result<Option<Level>, String> {
+        if name.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        LEVELS
+            .into_iter()
+            .find(|level| level.to_string().eq_ignore_ascii_case(name))
+            .map(Some)
+            .ok_or_else(|| format!("unknown log level {name}"))
+    };
+
+    let mut changes = Vec::new();
+
+    for filter in filters.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        match filter.split_once('=') {
+            Some((name, level)) => {
+                let level = parse_level(level.trim())?;
+                let name = name.trim();
+
+                if name == "all" {
+                    changes.extend(CHANNELS.map(|channel| (channel, level)));
+                } else {
+                    let channel = CHANNELS
+                        .into_iter()
+                        .find(|channel| channel.to_string().eq_ignore_ascii_case(name))
+                        .ok_or_else(|| format!("unknown log channel {name}"))?;
+
+                    changes.push((channel, level));
+                }
+            }
+            None => {
+                let level = parse_level(filter)?;
+
+                changes.extend(CHANNELS.map(|channel| (channel, level)));
+            }
+        }
+    }
+
+    for (channel, level) in changes {
+        set_level(channel, level);
+    }
+
+    Ok(())
+}
+
+// whether messages also get printed to stdout as they're logged
+pub fn set_echo(echo: bool) {
+    ECHO.store(echo, Ordering::Relaxed);
+}
+
+// use log! instead, which skips formatting the message when the channel is disabled
+pub fn write(channel: Channel, level: Level, message: String) {
+    let entry = LogEntry {
+        channel,
+        level,
+        message,
+    };
+
+    if ECHO.load(Ordering::Relaxed) {
+        println!("{entry}");
+    }
+
+    if let Ok(mut log) = LOG.lock() {
+        if log.entries.len() == LOG_CAPACITY {
+            log.entries.pop_front();
+        }
+
+        log.entries.push_back(entry);
+        log.unread = (log.unread + 1).min(LOG_CAPACITY);
+    }
+}
+
+// everything still in the ring buffer, oldest first
+pub fn entries() -> Vec<LogEntry> {
+    LOG.lock()
+        .map(|log| log.entries.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+// the entries logged since the last call, for frontends that show the log as it grows
+pub fn take_new_entries() -> Vec<LogEntry> {
+    let Ok(mut log) = LOG.lock() else {
+        return Vec::new();
+    };
+
+    let start = log.entries.len() - log.unread;
+
+    log.unread = 0;
+
+    log.entries.range(start..).cloned().collect()
+}
+
+pub fn clear() {
+    if let Ok(mut log) = LOG.lock() {
+        log.entries.clear();
+        log.unread = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the levels are global, so everything that changes them happens in this one test
+    #[test]
+    fn applies_filters() {
+        apply_filters("info,cdrom=debug, kernel=off").unwrap();
+
+        assert_eq!(level(Channel::Cpu), Some(Level::Info));
+        assert_eq!(level(Channel::Cdrom), Some(Level::Debug));
+        assert_eq!(level(Channel::Kernel), None);
+        assert!(enabled(Channel::Cdrom, Level::Debug));
+        assert!(!enabled(Channel::Cdrom, Level::Trace));
+        assert!(!enabled(Channel::Kernel, Level::Error));
+
+        apply_filters("all=TRACE,gpu=error").unwrap();
+
+        assert_eq!(level(Channel::Spu), Some(Level::Trace));
+        assert_eq!(level(Channel::Gpu), Some(Level::Error));
+
+        // nothing changes when any part is invalid
+        assert!(apply_filters("warn,cdrom=loud").is_err());
+        assert!(apply_filters("warn,disk=info").is_err());
+        assert!(apply_filters("loud").is_err());
+
+        assert_eq!(level(Channel::Spu), Some(Level::Trace));
+
+        apply_filters("").unwrap();
+
+        assert_eq!(level(Channel::Spu), Some(Level::Trace));
+
+        apply_filters("warn,kernel=info").unwrap();
+    }
+
+    #[test]
+    fn displays_entries() {
+        let entry = LogEntry {
+            channel: Channel::Cdrom,
+            level: Level::Warn,
+            message: "unknown command".to_string(),
+        };
+
+        assert_eq!(entry.to_string(), "[WARN][cdrom] unknown command");
+    }
+}
@@ -0,0 +1,421 @@
+use std::{collections::HashSet, fmt};
+
+use super::{CPU, bus::Bus};
+
+pub type OriginId = u64;
+pub const UNKNOWN_ORIGIN: OriginId = 0;
+
+pub const DEFAULT_CAPACITY: usize = 0x10_0000;
+
+const RAM_SIZE: usize = 0x20_0000;
+
+#[derive(Clone, Copy, Debug)]
+pub enum OriginKind {
+    Unknown,
+
+    // register got immediate / constant-ish value
+    Imm,
+
+    // register loaded from RAM
+    Load { addr: u32, mem: OriginId },
+
+    // RAM written from register
+    Store { addr: u32, src: OriginId },
+
+    // register copied from another register
+    Copy { src: OriginId },
+
+    // ALU op
+    Alu { lhs: OriginId, rhs: OriginId },
+    // anything read from outside of main RAM, which isn't tracked
+    MmioRead { addr: u32 },
+}
+
+impl OriginKind {
+    fn sources(&self) -> Vec<OriginId> {
+        match *self {
+            OriginKind::Load { mem, .. } => vec![mem],
+            OriginKind::Store { src, .. } | OriginKind::Copy { src } => vec![src],
+            OriginKind::Alu { lhs, rhs } => vec![lhs, rhs],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for OriginKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OriginKind::Unknown => write!(f, "unknown"),
+            OriginKind::Imm => write!(f, "immediate"),
+            OriginKind::Load { addr, .. } => write!(f, "load from {addr:08x}"),
+            OriginKind::Store { addr, .. } => write!(f, "store to {addr:08x}"),
+            OriginKind::Copy { .. } => write!(f, "copy"),
+            OriginKind::Alu { .. } => write!(f, "alu"),
+            OriginKind::MmioRead { addr } => write!(f, "i/o read from {addr:08x}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OriginNode {
+    pub pc: u32,
+    pub opcode: u32,
+    pub value: u32,
+    pub kind: OriginKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct OriginTree {
+    pub id: OriginId,
+    // None if the origin isn't known, or its node got evicted from the ring already
+    pub node: Option<OriginNode>,
+    pub children: Vec<OriginTree>,
+}
+
+impl OriginTree {
+    // values flow from the children up to the root, so that's the direction the edges go
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph origins {\n".to_string();
+        let mut visited = HashSet::new();
+
+        self.write_dot(&mut dot, &mut visited);
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    fn write_dot(&self, dot: &mut String, visited: &mut HashSet<OriginId>) {
+        if !visited.insert(self.id) {
+            return;
+        }
+
+        let label = match &self.node {
+            Some(node) => format!(
+                "{}\\npc={:08x} value={:08x}",
+                node.kind, node.pc, node.value
+            ),
+            None if self.id == UNKNOWN_ORIGIN => "unknown".to_string(),
+            None => "evicted".to_string(),
+        };
+
+        dot.push_str(&format!("  n{} [label=\"{label}\"];\n", self.id));
+
+        for child in &self.children {
+            child.write_dot(dot, visited);
+
+            dot.push_str(&format!("  n{} -> n{};\n", child.id, self.id));
+        }
+    }
+
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        match &self.node {
+            Some(node) => writeln!(
+                f,
+                "{:indent$}id={} pc={:08x} value={:08x} {}",
+                "",
+                self.id,
+                node.pc,
+                node.value,
+                node.kind,
+                indent = depth * 2
+            )?,
+            None if self.id == UNKNOWN_ORIGIN => {
+                writeln!(f, "{:indent$}UNKNOWN", "", indent = depth * 2)?
+            }
+            None => writeln!(
+                f,
+                "{:indent$}id={} EVICTED",
+                "",
+                self.id,
+                indent = depth * 2
+            )?,
+        }
+
+        for child in &self.children {
+            child.write_indented(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for OriginTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+/*
+  Every tracked load and store adds a node pointing back at wherever its value came from, so
+  following them from a register or RAM byte gives the chain of instructions that moved the value
+  there. Nodes live in a ring buffer, once it wraps around the oldest ones get dropped and show up
+  as evicted in queries. Tracking is off until enabled since it costs 16MB for the RAM origins alone.
+*/
+#[derive(Default)]
+pub struct OriginTracker {
+    nodes: Vec<OriginNode>,
+    capacity: usize,
+    next_id: OriginId,
+    registers: [OriginId; 32],
+    // one origin per RAM byte
+    ram: Box<[OriginId]>,
+}
+
+impl OriginTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enable(&mut self, capacity: usize) {
+        *self = Self {
+            nodes: Vec::new(),
+            capacity,
+            next_id: 1,
+            registers: [UNKNOWN_ORIGIN; 32],
+            ram: vec![UNKNOWN_ORIGIN; RAM_SIZE].into_boxed_slice(),
+        };
+    }
+
+    pub fn disable(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    // forgets everything tracked so far, ie after loading a save state
+    pub fn clear(&mut self) {
+        if self.is_enabled() {
+            self.enable(self.capacity);
+        }
+    }
+
+    fn add(&mut self, node: OriginNode) -> OriginId {
+        let id = self.next_id;
+
+        if self.nodes.len() < self.capacity {
+            self.nodes.push(node);
+        } else {
+            self.nodes[((id - 1) % self.capacity as u64) as usize] = node;
+        }
+
+        self.next_id += 1;
+
+        id
+    }
+
+    pub fn get(&self, id: OriginId) -> Option<&OriginNode> {
+        if id == UNKNOWN_ORIGIN || id >= self.next_id || self.next_id - id > self.capacity as u64 {
+            return None;
+        }
+
+        self.nodes.get(((id - 1) % self.capacity as u64) as usize)
+    }
+
+    pub fn register_origin(&self, register: usize) -> OriginId {
+        self.registers
+            .get(register)
+            .copied()
+            .unwrap_or(UNKNOWN_ORIGIN)
+    }
+
+    pub fn memory_origin(&self, address: u32) -> OriginId {
+        self.ram
+            .get(Bus::physical_address(address) as usize)
+            .copied()
+            .unwrap_or(UNKNOWN_ORIGIN)
+    }
+
+    pub fn tree(&self, id: OriginId, max_depth: usize) -> OriginTree {
+        let node = self.get(id).copied();
+
+        let children = match node {
+            Some(node) if max_depth > 0 => node
+                .kind
+                .sources()
+                .into_iter()
+                .map(|source| self.tree(source, max_depth - 1))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        OriginTree { id, node, children }
+    }
+
+    pub fn register_tree(&self, register: usize, max_depth: usize) -> OriginTree {
+        self.tree(self.register_origin(register), max_depth)
+    }
+
+    pub fn memory_tree(&self, address: u32, max_depth: usize) -> OriginTree {
+        self.tree(self.memory_origin(address), max_depth)
+    }
+}
+
+impl CPU {
+    pub(crate) fn track_load(&mut self, opcode: u32, register: usize, address: u32, value: u32) {
+        if !self.origins.is_enabled() {
+            return;
+        }
+
+        let physical = Bus::physical_address(address) as usize;
+
+        let kind = if physical < RAM_SIZE {
+            OriginKind::Load {
+                addr: address,
+                mem: self.origins.ram[physical],
+            }
+        } else {
+            OriginKind::MmioRead { addr: address }
+        };
+
+        let id = self.origins.add(OriginNode {
+            pc: self.previous_pc,
+            opcode,
+            value,
+            kind,
+        });
+
+        self.origins.registers[register] = id;
+    }
+
+    pub(crate) fn track_store(
+        &mut self,
+        opcode: u32,
+        register: usize,
+        address: u32,
+        value: u32,
+        size: usize,
+    ) {
+        if !self.origins.is_enabled() {
+            return;
+        }
+
+        let physical = Bus::physical_address(address) as usize;
+
+        if physical >= RAM_SIZE {
+            return;
+        }
+
+        let id = self.origins.add(OriginNode {
+            pc: self.previous_pc,
+            opcode,
+            value,
+            kind: OriginKind::Store {
+                addr: address,
+                src: self.origins.registers[register],
+            },
+        });
+
+        self.origins.ram[physical..physical + size].fill(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_running(program: &[u32]) -> CPU {
+        let mut cpu = CPU::new(None, String::new());
+
+        for (i, instruction) in program.iter().enumerate() {
+            cpu.bus
+                .mem_write32(0x8001_0000 + 4 * i as u32, *instruction);
+        }
+
+        cpu.pc = 0x8001_0000;
+        cpu.next_pc = 0x8001_0004;
+        cpu.origins.enable(DEFAULT_CAPACITY);
+
+        for _ in program {
+            cpu.step();
+        }
+
+        cpu
+    }
+
+    #[test]
+    fn follows_a_value_through_memory() {
+        let cpu = cpu_running(&[
+            0x8c08_0100, // lw t0, 0x100(zero)
+            0x0000_0000, // nop
+            0xac08_0200, // sw t0, 0x200(zero)
+            0x8c09_0200, // lw t1, 0x200(zero)
+            0x0000_0000, // nop
+        ]);
+
+        let tree = cpu.origins.register_tree(9, 8);
+
+        assert!(matches!(
+            tree.node.unwrap().kind,
+            OriginKind::Load { addr: 0x200, .. }
+        ));
+
+        let store = &tree.children[0];
+
+        assert_eq!(store.node.unwrap().pc, 0x8001_0008);
+        assert!(matches!(
+            store.node.unwrap().kind,
+            OriginKind::Store { addr: 0x200, .. }
+        ));
+
+        let load = &store.children[0];
+
+        assert!(matches!(
+            load.node.unwrap().kind,
+            OriginKind::Load {
+                addr: 0x100,
+                mem: UNKNOWN_ORIGIN
+            }
+        ));
+        assert_eq!(load.children[0].id, UNKNOWN_ORIGIN);
+
+        assert_eq!(cpu.origins.memory_origin(0x8000_0202), store.id);
+        assert!(
+            tree.to_dot()
+                .contains(&format!("n{} -> n{};", store.id, tree.id))
+        );
+    }
+
+    #[test]
+    fn marks_io_reads() {
+        let cpu = cpu_running(&[
+            0x3c09_1f80, // lui t1, 0x1f80
+            0x8d28_1070, // lw t0, 0x1070(t1)
+            0x0000_0000, // nop
+        ]);
+
+        assert!(matches!(
+            cpu.origins.register_tree(8, 1).node.unwrap().kind,
+            OriginKind::MmioRead { addr: 0x1f80_1070 }
+        ));
+    }
+
+    #[test]
+    fn evicts_the_oldest_nodes() {
+        let mut origins = OriginTracker::new();
+
+        origins.enable(2);
+
+        let node = OriginNode {
+            pc: 0,
+            opcode: 0,
+            value: 0,
+            kind: OriginKind::Imm,
+        };
+
+        let first = origins.add(node);
+
+        let second = origins.add(OriginNode {
+            kind: OriginKind::Copy { src: first },
+            ..node
+        });
+
+        origins.add(node);
+
+        assert!(origins.get(first).is_none());
+        assert!(origins.get(second).is_some());
+        assert!(origins.tree(second, 1).to_string().contains("EVICTED"));
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use super::{CPU, bus::Bus};
+use crate::log;
+
+const PCINIT: u32 = 0x101;
+const PCCREAT: u32 = 0x102;
+const PCOPEN: u32 = 0x103;
+const PCCLOSE: u32 = 0x104;
+const PCREAD: u32 = 0x105;
+const PCWRITE: u32 = 0x106;
+const PCLSEEK: u32 = 0x107;
+
+const V0_REGISTER: usize = 2;
+const V1_REGISTER: usize = 3;
+const A1_REGISTER: usize = 5;
+const A2_REGISTER: usize = 6;
+const A3_REGISTER: usize = 7;
+
+const MAX_PATH_LEN: u32 = 0x100;
+
+// no single transfer can be bigger than main RAM
+const MAX_TRANSFER_LEN: u32 = 0x20_0000;
+
+/*
+  PCdrv lets programs built with the PSY-Q or PSn00bSDK dev tools use files on the host PC, by
+  executing break instructions with the codes below. Arguments are passed in a1-a3, v0 is 0 on
+  success or -1 on failure and v1 holds the result (a file handle, byte count or position).
+  0x101 PCinit
+  0x102 PCcreat(path, attributes)
+  0x103 PCopen(path, mode) where mode 0 is read only, 1 write only and 2 read/write
+  0x104 PCclose(handle)
+  0x105 PCread(handle, len, buffer)
+  0x106 PCwrite(handle, len, buffer)
+  0x107 PClseek(handle, offset, whence) with whence being 0 = start, 1 = current, 2 = end
+  Every path is relative to the root directory, anything that would escape it is refused.
+*/
+pub struct PcDrv {
+    root: PathBuf,
+    files: HashMap<u32, File>,
+    next_handle: u32,
+}
+
+impl PcDrv {
+    pub fn new(root: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: fs::canonicalize(root)?,
+            files: HashMap::new(),
+            next_handle: 1,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // PSY-Q code tends to use DOS style paths
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = path.replace('\\', "/");
+        let path = Path::new(&path);
+
+        if path.as_os_str().is_empty()
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return None;
+        }
+
+        let full_path = self.root.join(path);
+
+        // symlinks could still point outside of the root, so check where the file really is. a
+        // file that doesn't exist yet can only be created in a directory inside of the root
+        let resolved = if fs::symlink_metadata(&full_path).is_ok() {
+            fs::canonicalize(&full_path).ok()?
+        } else {
+            fs::canonicalize(full_path.parent()?)
+                .ok()?
+                .join(full_path.file_name()?)
+        };
+
+        if !resolved.starts_with(&self.root) {
+            return None;
+        }
+
+        Some(resolved)
+    }
+
+    fn add_file(&mut self, file: File) -> u32 {
+        let handle = self.next_handle;
+
+        self.next_handle += 1;
+        self.files.insert(handle, file);
+
+        handle
+    }
+
+    fn init(&mut self) {
+        self.files.clear();
+        self.next_handle = 1;
+    }
+
+    fn create(&mut self, path: &str) -> io::Result<u32> {
+        let path = self.resolve(path).ok_or(io::ErrorKind::PermissionDenied)?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        Ok(self.add_file(file))
+    }
+
+    fn open(&mut self, path: &str, mode: u32) -> io::Result<u32> {
+        let path = self.resolve(path).ok_or(io::ErrorKind::PermissionDenied)?;
+
+        let file = match mode {
+            0 => OpenOptions::new().read(true).open(path)?,
+            1 => OpenOptions::new().write(true).open(path)?,
+            2 => OpenOptions::new().read(true).write(true).open(path)?,
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+
+        Ok(self.add_file(file))
+    }
+
+    fn close(&mut self, handle: u32) -> io::Result<u32> {
+        self.files
+            .remove(&handle)
+            .map(|_| 0)
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn file(&mut self, handle: u32) -> io::Result<&mut File> {
+        self.files
+            .get_mut(&handle)
+            .ok_or(io::ErrorKind::NotFound.into())
+    }
+
+    fn read(&mut self, handle: u32, len: u32) -> io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+
+        self.file(handle)?
+            .take(len.min(MAX_TRANSFER_LEN) as u64)
+            .read_to_end(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    fn write(&mut self, handle: u32, data: &[u8]) -> io::Result<u32> {
+        self.file(handle)?.write_all(data)?;
+
+        Ok(data.len() as u32)
+    }
+
+    fn seek(&mut self, handle: u32, offset: u32, whence: u32) -> io::Result<u32> {
+        let position = match whence {
+            0 => SeekFrom::Start(offset as u64),
+            1 => SeekFrom::Current(offset as i32 as i64),
+            2 => SeekFrom::End(offset as i32 as i64),
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+
+        Ok(self.file(handle)?.seek(position)? as u32)
+    }
+}
+
+impl CPU {
+    pub fn enable_pcdrv(&mut self, root: &Path) -> io::Result<()> {
+        self.pcdrv = Some(PcDrv::new(root)?);
+
+        Ok(())
+    }
+
+    pub fn disable_pcdrv(&mut self) {
+        self.pcdrv = None;
+    }
+
+    fn read_guest_string(&self, address: u32) -> String {
+        let bytes: Vec<u8> = (0..MAX_PATH_LEN)
+            .map_while(|i| self.bus.peek8(address.wrapping_add(i)))
+            .take_while(|&byte| byte != 0)
+            .collect();
+
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+
+    // returns false if the break wasn't a PCdrv call, in which case it raises an exception like normal
+    pub(crate) fn handle_pcdrv(&mut self, code: u32) -> bool {
+        if !(PCINIT..=PCLSEEK).contains(&code) {
+            return false;
+        }
+
+        if self.is_replaying() {
+            return self.replay_pcdrv();
+        }
+
+        let Some(mut pcdrv) = self.pcdrv.take() else {
+            return false;
+        };
+
+        let a1 = self.r[A1_REGISTER];
+        let a2 = self.r[A2_REGISTER];
+        let a3 = self.r[A3_REGISTER];
+
+        let mut read = None;
+
+        let result = match code {
+            PCINIT => {
+                pcdrv.init();
+                Ok(0)
+            }
+            PCCREAT => pcdrv.create(&self.read_guest_string(a1)),
+            PCOPEN => pcdrv.open(&self.read_guest_string(a1), a2),
+            PCCLOSE => pcdrv.close(a1),
+            // the whole buffer has to be in memory, a read can't be used to write to I/O registers
+            PCREAD
+                if !(0..a2.min(MAX_TRANSFER_LEN)).all(|i| Bus::is_memory(a3.wrapping_add(i))) =>
+            {
+                Err(io::ErrorKind::InvalidInput.into())
+            }
+            PCREAD => pcdrv.read(a1, a2).map(|data| {
+                for (i, byte) in data.iter().enumerate() {
+                    self.bus.poke8(a3.wrapping_add(i as u32), *byte);
+                }
+
+                let len = data.len() as u32;
+
+                read = Some((a3, data));
+
+                len
+            }),
+            PCWRITE => {
+                let data: Vec<u8> = (0..a2.min(MAX_TRANSFER_LEN))
+                    .map_while(|i| self.bus.peek8(a3.wrapping_add(i)))
+                    .collect();
+
+                pcdrv.write(a1, &data)
+            }
+            PCLSEEK => pcdrv.seek(a1, a2, a3),
+            _ => unreachable!(),
+        };
+
+        match result {
+            Ok(value) => {
+                self.r[V0_REGISTER] = 0;
+                self.r[V1_REGISTER] = value;
+            }
+            Err(e) => {
+                log!(Kernel, Warn, "PCdrv call 0x{code:x} failed: {e}");
+
+                self.r[V0_REGISTER] = 0xffff_ffff;
+                self.r[V1_REGISTER] = 0xffff_ffff;
+            }
+        }
+
+        self.record_host_call(self.r[V0_REGISTER], self.r[V1_REGISTER], read);
+
+        self.pcdrv = Some(pcdrv);
+
+        true
+    }
+
+    // without a call recorded for this instruction PCdrv wasn't attached, so it has to fail the same way
+    fn replay_pcdrv(&mut self) -> bool {
+        let Some(call) = self.recorded_host_call() else {
+            return false;
+        };
+
+        if let Some((address, data)) = call.read {
+            for (i, byte) in data.iter().enumerate() {
+                self.bus.poke8(address.wrapping_add(i as u32), *byte);
+            }
+        }
+
+        self.r[V0_REGISTER] = call.v0;
+        self.r[V1_REGISTER] = call.v1;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory with a root for PcDrv and a sibling directory outside of it
+    fn scratch(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rsx-pcdrv-{name}-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&dir);
+
+        fs::create_dir_all(dir.join("root")).unwrap();
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        fs::write(dir.join("outside").join("secret"), b"secret").unwrap();
+
+        (dir.join("root"), dir)
+    }
+
+    #[test]
+    fn refuses_paths_that_leave_the_root() {
+        let (root, dir) = scratch("parent");
+        let mut pcdrv = PcDrv::new(&root).unwrap();
+
+        for path in [
+            "../outside/secret",
+            "..\\outside\\secret",
+            "sub/../../outside/secret",
+            "/etc/passwd",
+            "",
+        ] {
+            assert!(pcdrv.open(path, 0).is_err(), "{path}");
+        }
+
+        assert!(pcdrv.create("../outside/new").is_err());
+        assert!(!dir.join("outside").join("new").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_that_leave_the_root() {
+        use std::os::unix::fs::symlink;
+
+        let (root, dir) = scratch("symlink");
+        let outside = dir.join("outside");
+
+        symlink(outside.join("secret"), root.join("file")).unwrap();
+        symlink(&outside, root.join("dir")).unwrap();
+        symlink(outside.join("missing"), root.join("dangling")).unwrap();
+
+        let mut pcdrv = PcDrv::new(&root).unwrap();
+
+        assert!(pcdrv.open("file", 0).is_err());
+        assert!(pcdrv.create("file").is_err());
+        assert!(pcdrv.open("dir/secret", 0).is_err());
+        assert!(pcdrv.create("dir/new").is_err());
+        assert!(pcdrv.create("dangling").is_err());
+
+        assert_eq!(fs::read(outside.join("secret")).unwrap(), b"secret");
+        assert!(!outside.join("new").exists());
+        assert!(!outside.join("missing").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn creates_files_in_subdirectories_of_the_root() {
+        let (root, dir) = scratch("subdirectory");
+
+        fs::create_dir(root.join("sub")).unwrap();
+
+        let mut pcdrv = PcDrv::new(&root).unwrap();
+
+        let handle = pcdrv.create("sub\\new.bin").unwrap();
+
+        assert_eq!(pcdrv.write(handle, b"data").unwrap(), 4);
+        assert_eq!(fs::read(root.join("sub").join("new.bin")).unwrap(), b"data");
+        assert!(pcdrv.create("missing/new.bin").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_reads_into_memory() {
+        let (root, dir) = scratch("read");
+
+        fs::write(root.join("data"), b"abcd").unwrap();
+
+        let mut cpu = CPU::new(None, String::new());
+
+        cpu.enable_pcdrv(&root).unwrap();
+
+        let handle = cpu.pcdrv.as_mut().unwrap().open("data", 0).unwrap();
+
+        cpu.r[A1_REGISTER] = handle;
+        cpu.r[A2_REGISTER] = 4;
+        cpu.r[A3_REGISTER] = 0x1f80_1070;
+
+        assert!(cpu.handle_pcdrv(PCREAD));
+        assert_eq!(cpu.r[V0_REGISTER], 0xffff_ffff);
+
+        cpu.r[A3_REGISTER] = 0x8000_0100;
+
+        assert!(cpu.handle_pcdrv(PCREAD));
+        assert_eq!((cpu.r[V0_REGISTER], cpu.r[V1_REGISTER]), (0, 4));
+        assert_eq!(
+            cpu.bus.mem_read32(0x8000_0100),
+            u32::from_le_bytes(*b"abcd")
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
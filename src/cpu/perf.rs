@@ -0,0 +1,183 @@
+use std::{fmt, time::Duration};
+
+#[cfg(feature = "perf_counters")]
+use std::time::Instant;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Subsystem {
+    // everything not covered by the rest, which is mostly the CPU core itself
+    Cpu,
+    Gpu,
+    Gte,
+    Dma,
+    Cdrom,
+    Spu,
+    // any other scheduler event
+    Events,
+    // host side rendering, for frontends that time it
+    Renderer,
+}
+
+pub const SUBSYSTEMS: [Subsystem; 8] = [
+    Subsystem::Cpu,
+    Subsystem::Gpu,
+    Subsystem::Gte,
+    Subsystem::Dma,
+    Subsystem::Cdrom,
+    Subsystem::Spu,
+    Subsystem::Events,
+    Subsystem::Renderer,
+];
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Subsystem::Cpu => "cpu",
+            Subsystem::Gpu => "gpu",
+            Subsystem::Gte => "gte",
+            Subsystem::Dma => "dma",
+            Subsystem::Cdrom => "cdrom",
+            Subsystem::Spu => "spu",
+            Subsystem::Events => "events",
+            Subsystem::Renderer => "renderer",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Counter {
+    pub calls: u64,
+    // host time spent in the subsystem itself, not counting anything nested inside of it
+    pub time: Duration,
+    // emulated cycles, for the subsystems that take a known number of them
+    pub cycles: u64,
+}
+
+/*
+  Host side timing of the emulator's subsystems, which only does anything with the
+  perf_counters feature enabled (otherwise every method compiles down to nothing). Scopes nest,
+  ie a DMA transfer started by the CPU pauses the CPU's timer until it's done, so the times of
+  all subsystems add up to the total time spent. Taking the time costs a little on every scope,
+  so the emulator runs somewhat slower with the feature on.
+*/
+#[derive(Default)]
+pub struct PerfCounters {
+    #[cfg(feature = "perf_counters")]
+    counters: [Counter; SUBSYSTEMS.len()],
+    #[cfg(feature = "perf_counters")]
+    active: Vec<(Subsystem, Instant)>,
+}
+
+#[cfg_attr(not(feature = "perf_counters"), allow(unused_variables))]
+impl PerfCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled() -> bool {
+        cfg!(feature = "perf_counters")
+    }
+
+    #[inline(always)]
+    pub fn begin(&mut self, subsystem: Subsystem) {
+        #[cfg(feature = "perf_counters")]
+        {
+            let now = Instant::now();
+
+            if let Some((parent, start)) = self.active.last_mut() {
+                self.counters[*parent as usize].time += now - *start;
+            }
+
+            self.counters[subsystem as usize].calls += 1;
+            self.active.push((subsystem, now));
+        }
+    }
+
+    #[inline(always)]
+    pub fn end(&mut self) {
+        #[cfg(feature = "perf_counters")]
+        {
+            let now = Instant::now();
+
+            if let Some((subsystem, start)) = self.active.pop() {
+                self.counters[subsystem as usize].time += now - start;
+            }
+
+            // the parent picks up timing from here
+            if let Some((_, start)) = self.active.last_mut() {
+                *start = now;
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn add_cycles(&mut self, subsystem: Subsystem, cycles: u64) {
+        #[cfg(feature = "perf_counters")]
+        {
+            self.counters[subsystem as usize].cycles += cycles;
+        }
+    }
+
+    pub fn counter(&self, subsystem: Subsystem) -> Counter {
+        #[cfg(feature = "perf_counters")]
+        let counter = self.counters[subsystem as usize];
+        #[cfg(not(feature = "perf_counters"))]
+        let counter = Counter::default();
+
+        counter
+    }
+
+    pub fn total_time(&self) -> Duration {
+        SUBSYSTEMS
+            .iter()
+            .map(|&subsystem| self.counter(subsystem).time)
+            .sum()
+    }
+
+    // zeroes the counters, scopes that are still open keep timing
+    pub fn reset(&mut self) {
+        #[cfg(feature = "perf_counters")]
+        {
+            self.counters = Default::default();
+
+            let now = Instant::now();
+
+            for (_, start) in &mut self.active {
+                *start = now;
+            }
+        }
+    }
+}
+
+// ie "cpu 8.1ms (62%) gpu 3.0ms (23%) ...", every subsystem that took any time
+impl fmt::Display for PerfCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total_time().as_secs_f64().max(f64::EPSILON);
+
+        let mut first = true;
+
+        for subsystem in SUBSYSTEMS {
+            let time = self.counter(subsystem).time.as_secs_f64();
+
+            if time == 0.0 {
+                continue;
+            }
+
+            if !first {
+                write!(f, " ")?;
+            }
+            first = false;
+
+            write!(
+                f,
+                "{subsystem} {:.1}ms ({:.0}%)",
+                time * 1000.0,
+                time / total * 100.0
+            )?;
+        }
+
+        Ok(())
+    }
+}
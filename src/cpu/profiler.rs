@@ -0,0 +1,301 @@
+use std::{collections::HashMap, fmt::Write};
+
+use super::{CPU, CPU_FREQUENCY, instructions::Instruction, symbols::SymbolTable};
+
+// one sample per millisecond of emulated time
+pub const DEFAULT_INTERVAL: u64 = (CPU_FREQUENCY / 1000.0) as u64;
+
+// anything deeper is most likely calls that never returned, ie a longjmp or a task switch
+const MAX_DEPTH: usize = 256;
+
+#[derive(Copy, Clone, Debug)]
+enum Jump {
+    Call(Frame),
+    Return(u32),
+    // jalr, which might be either
+    CallOrReturn(Frame),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Frame {
+    function: u32,
+    return_address: u32,
+    is_exception: bool,
+}
+
+/*
+  Samples the call stack every interval cycles. There's no way to walk the stack the game
+  actually uses, so the profiler keeps a shadow stack instead: jal and jalr push a frame for
+  the function being called, and any jr/jalr back to the return address of a frame pops
+  everything above it. Exceptions get a frame too, which returns to EPC (or the instruction
+  after it, for syscalls and breaks). Jumps only change the stack once their delay slot has run,
+  since that still belongs to the function doing the jumping.
+*/
+pub struct Profiler {
+    interval: u64,
+    next_sample: u64,
+    stack: Vec<Frame>,
+    pending_jump: Option<Jump>,
+    // the function addresses of every frame followed by the pc, and how often that got sampled
+    samples: HashMap<Vec<u32>, u64>,
+    total_samples: u64,
+}
+
+impl Profiler {
+    pub fn new(interval: u64) -> Self {
+        Self {
+            interval: interval.max(1),
+            next_sample: 0,
+            stack: Vec::new(),
+            pending_jump: None,
+            samples: HashMap::new(),
+            total_samples: 0,
+        }
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.total_samples
+    }
+
+    // drops the samples but keeps the call stack, ie to only profile a single frame
+    pub fn clear_samples(&mut self) {
+        self.samples.clear();
+        self.total_samples = 0;
+    }
+
+    fn push(&mut self, frame: Frame) {
+        if self.stack.len() == MAX_DEPTH {
+            self.stack.remove(0);
+        }
+
+        self.stack.push(frame);
+    }
+
+    fn apply_jump(&mut self, jump: Jump) {
+        match jump {
+            Jump::Call(frame) => self.push(frame),
+            Jump::Return(target) => self.pop_to(target),
+            Jump::CallOrReturn(frame) => {
+                self.pop_to(frame.function);
+                self.push(frame);
+            }
+        }
+    }
+
+    fn pop_to(&mut self, target: u32) {
+        let frame = self.stack.iter().rposition(|frame| {
+            frame.return_address == target
+                || (frame.is_exception && frame.return_address.wrapping_add(4) == target)
+        });
+
+        if let Some(index) = frame {
+            self.stack.truncate(index);
+        }
+    }
+
+    fn sample(&mut self, pc: u32) {
+        let mut key: Vec<u32> = self.stack.iter().map(|frame| frame.function).collect();
+
+        key.push(pc);
+
+        *self.samples.entry(key).or_insert(0) += 1;
+        self.total_samples += 1;
+    }
+
+    // one line per distinct stack, ie "main;update_player;memcpy 12", which is what flamegraph tools take
+    pub fn to_folded(&self, symbols: &SymbolTable) -> String {
+        let name = |address: u32| match symbols.lookup(address) {
+            Some((symbol, _)) => symbol.name.clone(),
+            None => format!("{address:08x}"),
+        };
+
+        let mut folded: HashMap<String, u64> = HashMap::new();
+
+        for (key, &count) in &self.samples {
+            let (&pc, functions) = key.split_last().unwrap();
+
+            let mut frames: Vec<String> =
+                functions.iter().map(|&function| name(function)).collect();
+
+            // without a symbol for it the pc is assumed to be in the innermost function already
+            let leaf = match symbols.lookup(pc) {
+                Some((symbol, _)) => Some(symbol.name.clone()),
+                None if frames.is_empty() => Some(format!("{pc:08x}")),
+                None => None,
+            };
+
+            if let Some(leaf) = leaf
+                && frames.last() != Some(&leaf)
+            {
+                frames.push(leaf);
+            }
+
+            *folded.entry(frames.join(";")).or_insert(0) += count;
+        }
+
+        let mut lines: Vec<(String, u64)> = folded.into_iter().collect();
+
+        lines.sort();
+
+        let mut output = String::new();
+
+        for (stack, count) in lines {
+            let _ = writeln!(output, "{stack} {count}");
+        }
+
+        output
+    }
+}
+
+impl CPU {
+    pub fn start_profiler(&mut self, interval: u64) {
+        self.profiler = Some(Profiler::new(interval));
+    }
+
+    pub fn stop_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_mut()
+    }
+
+    // called after every instruction, with next_pc holding the target of any jump it made
+    pub(crate) fn profile_instruction(&mut self, instruction: Instruction) {
+        let Some(profiler) = &mut self.profiler else {
+            return;
+        };
+
+        let cycles = self.bus.scheduler.cycles;
+
+        // loading a state can send the cycle count backwards
+        if profiler.next_sample > cycles + profiler.interval {
+            profiler.next_sample = cycles;
+        }
+
+        if cycles >= profiler.next_sample {
+            profiler.sample(self.previous_pc);
+            profiler.next_sample = cycles + profiler.interval;
+        }
+
+        if let Some(jump) = profiler.pending_jump.take() {
+            profiler.apply_jump(jump);
+        }
+
+        let frame = Frame {
+            function: self.next_pc,
+            return_address: self.previous_pc.wrapping_add(8),
+            is_exception: false,
+        };
+
+        profiler.pending_jump = match (instruction.0 >> 26, instruction.0 & 0x3f) {
+            (0x3, _) => Some(Jump::Call(frame)),
+            (0, 0x8) => Some(Jump::Return(self.next_pc)),
+            (0, 0x9) => Some(Jump::CallOrReturn(frame)),
+            _ => None,
+        };
+    }
+
+    pub(crate) fn profile_exception(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            // a jump with its delay slot interrupted gets executed again after the exception
+            profiler.pending_jump = None;
+
+            profiler.push(Frame {
+                function: self.pc,
+                return_address: self.cop0.epc,
+                is_exception: true,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols() -> SymbolTable {
+        let mut symbols = SymbolTable::new();
+
+        symbols.add("main", 0x8001_0000, Some(0x10));
+        symbols.add("leaf", 0x8001_0010, Some(0x8));
+
+        symbols
+    }
+
+    #[test]
+    fn folds_samples_by_call_stack() {
+        let mut cpu = CPU::new(None, String::new());
+
+        for (i, instruction) in [
+            0x0c00_4004, // jal 0x80010010
+            0x0000_0000, // nop
+            0x0800_4002, // j 0x80010008
+            0x0000_0000, // nop
+            0x03e0_0008, // jr ra
+            0x0000_0000, // nop
+        ]
+        .iter()
+        .enumerate()
+        {
+            cpu.bus
+                .mem_write32(0x8001_0000 + 4 * i as u32, *instruction);
+        }
+
+        cpu.pc = 0x8001_0000;
+        cpu.next_pc = 0x8001_0004;
+        cpu.start_profiler(1);
+
+        for _ in 0..8 {
+            cpu.step();
+        }
+
+        let profiler = cpu.stop_profiler().unwrap();
+
+        // the delay slots still belong to the function doing the jumping
+        assert_eq!(profiler.total_samples(), 8);
+        assert_eq!(profiler.to_folded(&symbols()), "leaf 2\nmain 6\n");
+    }
+
+    #[test]
+    fn pops_exception_frames_on_return_to_the_next_instruction() {
+        let mut profiler = Profiler::new(DEFAULT_INTERVAL);
+
+        profiler.apply_jump(Jump::Call(Frame {
+            function: 0x8001_0010,
+            return_address: 0x8001_0008,
+            is_exception: false,
+        }));
+        profiler.apply_jump(Jump::Call(Frame {
+            function: 0x8000_0080,
+            return_address: 0x8001_0014,
+            is_exception: true,
+        }));
+        profiler.sample(0x8000_0084);
+
+        // a syscall handler returns past EPC
+        profiler.apply_jump(Jump::Return(0x8001_0018));
+        profiler.sample(0x8001_0018);
+
+        profiler.apply_jump(Jump::Return(0x8001_0008));
+        profiler.sample(0x8001_0008);
+
+        assert_eq!(
+            profiler.to_folded(&symbols()),
+            "leaf 1\nleaf;80000080 1\nmain 1\n"
+        );
+
+        profiler.clear_samples();
+
+        assert_eq!(profiler.total_samples(), 0);
+        assert_eq!(profiler.to_folded(&symbols()), "");
+    }
+}
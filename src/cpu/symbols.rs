@@ -0,0 +1,580 @@
+use std::{collections::BTreeMap, ops::Range};
+
+// without a size, addresses further than this past a symbol aren't considered part of it
+const MAX_UNSIZED_OFFSET: u32 = 0x10000;
+
+const ELF_SECTION_SYMTAB: u32 = 2;
+const ELF_SYMBOL_SIZE: usize = 16;
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    pub size: Option<u32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LineEntry {
+    file: usize,
+    line: u32,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, String> {
+    bytes
+        .get(offset..offset.saturating_add(2))
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| "unexpected end of symbol file".to_string())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset.saturating_add(4))
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| "unexpected end of symbol file".to_string())
+}
+
+// pascal style string, one length byte followed by the characters
+fn read_string(bytes: &[u8], offset: &mut usize) -> Result<String, String> {
+    let len = *bytes
+        .get(*offset)
+        .ok_or_else(|| "unexpected end of symbol file".to_string())? as usize;
+
+    let string = bytes
+        .get(*offset + 1..*offset + 1 + len)
+        .ok_or_else(|| "unexpected end of symbol file".to_string())?;
+
+    *offset += len + 1;
+
+    Ok(String::from_utf8_lossy(string).to_string())
+}
+
+fn read_c_string(bytes: &[u8], offset: usize) -> String {
+    let string = bytes.get(offset..).unwrap_or_default();
+    let len = string
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(string.len());
+
+    String::from_utf8_lossy(&string[..len]).to_string()
+}
+
+fn is_symbol_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '@'))
+}
+
+fn parse_address(text: &str) -> Option<u32> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+
+    // 64 bit addresses show up in GNU maps, but only the low 32 bits mean anything here
+    u64::from_str_radix(hex, 16)
+        .ok()
+        .map(|address| address as u32)
+}
+
+/*
+  Maps addresses to names for the disassembler, traces and the debugger. Symbols come from
+  PSY-Q .SYM files, linker maps, ELF symbol tables or nm output, and several files can be
+  loaded into the same table. SYM files also carry line numbers, which end up in a line map.
+*/
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: BTreeMap<u32, Symbol>,
+    files: Vec<String>,
+    lines: BTreeMap<u32, Option<LineEntry>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    // the first name given to an address is kept, later ones tend to be local labels
+    pub fn add(&mut self, name: &str, address: u32, size: Option<u32>) {
+        self.symbols.entry(address).or_insert_with(|| Symbol {
+            name: name.to_string(),
+            address,
+            size: size.filter(|&size| size > 0),
+        });
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.values()
+    }
+
+    pub fn find(&self, name: &str) -> Option<u32> {
+        self.symbols
+            .values()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.address)
+    }
+
+    // returns the symbol containing address along with the offset into it
+    pub fn lookup(&self, address: u32) -> Option<(&Symbol, u32)> {
+        let (_, symbol) = self.symbols.range(..=address).next_back()?;
+
+        let offset = address - symbol.address;
+
+        if offset < symbol.size.unwrap_or(MAX_UNSIZED_OFFSET) {
+            Some((symbol, offset))
+        } else {
+            None
+        }
+    }
+
+    // without a size, a symbol is assumed to run up to the next one
+    pub fn range_of(&self, symbol: &Symbol) -> Range<u32> {
+        let end = match symbol.size {
+            Some(size) => symbol.address.saturating_add(size),
+            None => self
+                .symbols
+                .range(symbol.address.saturating_add(1)..)
+                .next()
+                .map(|(&address, _)| address)
+                .unwrap_or(u32::MAX)
+                .min(symbol.address.saturating_add(MAX_UNSIZED_OFFSET)),
+        };
+
+        symbol.address..end
+    }
+
+    // ie "main+0x10", or None if no symbol covers the address
+    pub fn describe(&self, address: u32) -> Option<String> {
+        self.lookup(address).map(|(symbol, offset)| {
+            if offset == 0 {
+                symbol.name.clone()
+            } else {
+                format!("{}+0x{offset:x}", symbol.name)
+            }
+        })
+    }
+
+    pub fn line_at(&self, address: u32) -> Option<(&str, u32)> {
+        let (_, entry) = self.lines.range(..=address).next_back()?;
+
+        entry.map(|entry| (self.files[entry.file].as_str(), entry.line))
+    }
+
+    // every address range the line map covers, along with its file and line
+    pub fn line_ranges(&self) -> impl Iterator<Item = (Range<u32>, &str, u32)> {
+        self.lines
+            .iter()
+            .zip(self.lines.keys().skip(1).map(Some).chain([None]))
+            .filter_map(|((&start, entry), end)| {
+                let entry = (*entry)?;
+                let end = end.copied().unwrap_or(start.wrapping_add(4));
+
+                Some((start..end, self.files[entry.file].as_str(), entry.line))
+            })
+    }
+
+    // picks the format based on the contents, returns the number of symbols added
+    pub fn load(&mut self, bytes: &[u8]) -> Result<usize, String> {
+        if bytes.starts_with(b"MND") {
+            self.load_sym(bytes)
+        } else if bytes.starts_with(b"\x7fELF") {
+            self.load_elf(bytes)
+        } else {
+            let text = String::from_utf8_lossy(bytes);
+
+            let count = self.load_map(&text) + self.load_nm(&text);
+
+            if count == 0 {
+                return Err("no symbols found, unknown symbol file format".to_string());
+            }
+
+            Ok(count)
+        }
+    }
+
+    /*
+      PSY-Q .SYM files are an 8 byte "MND" header followed by records of an address, a tag byte
+      and tag specific data. Tags below 0x80 are symbols, the rest are line numbers and debug
+      info, which is skipped over apart from function starts and the line numbers.
+    */
+    pub fn load_sym(&mut self, bytes: &[u8]) -> Result<usize, String> {
+        if !bytes.starts_with(b"MND") {
+            return Err("not a SYM file".to_string());
+        }
+
+        let previous_len = self.len();
+
+        let mut offset = 8;
+        let mut line: Option<LineEntry> = None;
+
+        while offset < bytes.len() {
+            let address = read_u32(bytes, offset)?;
+            let tag = *bytes
+                .get(offset + 4)
+                .ok_or_else(|| "unexpected end of symbol file".to_string())?;
+
+            offset += 5;
+
+            match tag {
+                0x1 | 0x2 => {
+                    let name = read_string(bytes, &mut offset)?;
+
+                    self.add(&name, address, None);
+                }
+                0x80 => {
+                    if let Some(line) = &mut line {
+                        line.line += 1;
+                    }
+                }
+                0x82 => {
+                    let increment = *bytes
+                        .get(offset)
+                        .ok_or_else(|| "unexpected end of symbol file".to_string())?;
+
+                    if let Some(line) = &mut line {
+                        line.line += increment as u32;
+                    }
+                    offset += 1;
+                }
+                0x84 => {
+                    if let Some(line) = &mut line {
+                        line.line += read_u16(bytes, offset)? as u32;
+                    }
+                    offset += 2;
+                }
+                0x86 => {
+                    if let Some(line) = &mut line {
+                        line.line = read_u32(bytes, offset)?;
+                    }
+                    offset += 4;
+                }
+                0x88 => {
+                    let line_number = read_u32(bytes, offset)?;
+                    offset += 4;
+
+                    let file = read_string(bytes, &mut offset)?;
+
+                    self.files.push(file);
+
+                    line = Some(LineEntry {
+                        file: self.files.len() - 1,
+                        line: line_number,
+                    });
+                }
+                0x8a => line = None,
+                0x8c => {
+                    // frame register, frame size, return register, mask, mask offset, line
+                    offset += 2 + 4 + 2 + 4 + 4 + 4;
+
+                    read_string(bytes, &mut offset)?;
+                    let name = read_string(bytes, &mut offset)?;
+
+                    self.add(&name, address, None);
+                }
+                0x8e | 0x90 | 0x92 => offset += 4,
+                0x94 => {
+                    // class, type, size
+                    offset += 2 + 2 + 4;
+
+                    read_string(bytes, &mut offset)?;
+                }
+                0x96 => {
+                    offset += 2 + 2 + 4;
+
+                    let dimensions = read_u16(bytes, offset)? as usize;
+
+                    offset += 2 + dimensions * 4;
+
+                    // tag followed by the name
+                    read_string(bytes, &mut offset)?;
+                    read_string(bytes, &mut offset)?;
+                }
+                // overlay definition, then switching overlays
+                0x98 => offset += 8,
+                0x9a => (),
+                _ => {
+                    return Err(format!(
+                        "unknown SYM tag 0x{tag:x} at offset 0x{:x}",
+                        offset - 1
+                    ));
+                }
+            }
+
+            if (0x80..=0x8a).contains(&tag) {
+                self.lines.insert(address, line);
+            }
+        }
+
+        Ok(self.len() - previous_len)
+    }
+
+    pub fn load_elf(&mut self, bytes: &[u8]) -> Result<usize, String> {
+        if !bytes.starts_with(b"\x7fELF") || bytes.get(4) != Some(&1) || bytes.get(5) != Some(&1) {
+            return Err("not a little endian 32 bit ELF file".to_string());
+        }
+
+        let previous_len = self.len();
+
+        let section_offset = read_u32(bytes, 0x20)? as usize;
+        let section_size = read_u16(bytes, 0x2e)? as usize;
+        let num_sections = read_u16(bytes, 0x30)? as usize;
+
+        let section = |index: usize| {
+            index
+                .checked_mul(section_size)
+                .and_then(|offset| offset.checked_add(section_offset))
+                .ok_or_else(|| "section header out of bounds".to_string())
+        };
+
+        for index in 0..num_sections {
+            let header = section(index)?;
+
+            if read_u32(bytes, header + 4)? != ELF_SECTION_SYMTAB {
+                continue;
+            }
+
+            let symbols_offset = read_u32(bytes, header + 0x10)? as usize;
+            let symbols_size = read_u32(bytes, header + 0x14)? as usize;
+            let strings_offset = read_u32(
+                bytes,
+                section(read_u32(bytes, header + 0x18)? as usize)?.saturating_add(0x10),
+            )? as usize;
+
+            let symbols = symbols_offset
+                .checked_add(symbols_size)
+                .and_then(|end| bytes.get(symbols_offset..end))
+                .ok_or_else(|| "symbol table out of bounds".to_string())?;
+
+            for symbol in symbols.chunks_exact(ELF_SYMBOL_SIZE) {
+                let name_offset = read_u32(symbol, 0)? as usize;
+                let address = read_u32(symbol, 4)?;
+                let size = read_u32(symbol, 8)?;
+                let symbol_type = symbol[12] & 0xf;
+                let section_index = read_u16(symbol, 14)?;
+
+                // only untyped, object and function symbols that are actually defined somewhere
+                if symbol_type > 2 || section_index == 0 {
+                    continue;
+                }
+
+                let name = read_c_string(bytes, strings_offset.saturating_add(name_offset));
+
+                if is_symbol_name(&name) {
+                    self.add(&name, address, Some(size));
+                }
+            }
+        }
+
+        Ok(self.len() - previous_len)
+    }
+
+    // linker maps list symbols as "<address> <name>", which covers both psylink and GNU ld maps
+    pub fn load_map(&mut self, text: &str) -> usize {
+        let previous_len = self.len();
+
+        for line in text.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            if let [address, name] = tokens[..]
+                && let Some(address) = parse_address(address)
+                && is_symbol_name(name)
+            {
+                self.add(name, address, None);
+            }
+        }
+
+        self.len() - previous_len
+    }
+
+    // nm output is "<address> <type> <name>", undefined symbols don't have an address
+    pub fn load_nm(&mut self, text: &str) -> usize {
+        let previous_len = self.len();
+
+        for line in text.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            if let [address, symbol_type, name] = tokens[..] {
+                if symbol_type.len() != 1 || symbol_type.eq_ignore_ascii_case("u") {
+                    continue;
+                }
+
+                if let Some(address) = parse_address(address)
+                    && is_symbol_name(name)
+                {
+                    self.add(name, address, None);
+                }
+            }
+        }
+
+        self.len() - previous_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sym_string(bytes: &mut Vec<u8>, text: &str) {
+        bytes.push(text.len() as u8);
+        bytes.extend_from_slice(text.as_bytes());
+    }
+
+    // a header, one section header table with a symbol table and its string table
+    fn elf(symbols: &[(&str, u32, u32, u8)]) -> Vec<u8> {
+        let mut strings = vec![0];
+        let mut table = Vec::new();
+
+        for &(name, address, size, info) in symbols {
+            table.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+            table.extend_from_slice(&address.to_le_bytes());
+            table.extend_from_slice(&size.to_le_bytes());
+            table.extend_from_slice(&[info, 0, 1, 0]);
+
+            strings.extend_from_slice(name.as_bytes());
+            strings.push(0);
+        }
+
+        let sections_offset = 0x34;
+        let table_offset = sections_offset + 3 * 0x28;
+        let strings_offset = table_offset + table.len();
+
+        let mut bytes = vec![0; table_offset];
+
+        bytes[..6].copy_from_slice(b"\x7fELF\x01\x01");
+        bytes[0x20..0x24].copy_from_slice(&(sections_offset as u32).to_le_bytes());
+        bytes[0x2e..0x30].copy_from_slice(&0x28u16.to_le_bytes());
+        bytes[0x30..0x32].copy_from_slice(&3u16.to_le_bytes());
+
+        // section 1 is the symbol table, linked to the strings in section 2
+        let symtab = sections_offset + 0x28;
+
+        for (offset, value) in [
+            (symtab + 0x4, ELF_SECTION_SYMTAB),
+            (symtab + 0x10, table_offset as u32),
+            (symtab + 0x14, table.len() as u32),
+            (symtab + 0x18, 2),
+            (symtab + 0x28 + 0x4, 3),
+            (symtab + 0x28 + 0x10, strings_offset as u32),
+        ] {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&table);
+        bytes.extend_from_slice(&strings);
+
+        bytes
+    }
+
+    #[test]
+    fn looks_up_addresses() {
+        let mut symbols = SymbolTable::new();
+
+        symbols.add("main", 0x8001_0000, Some(0x20));
+        symbols.add("later_name", 0x8001_0000, None);
+        symbols.add("data", 0x8002_0000, None);
+
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.find("main"), Some(0x8001_0000));
+        assert_eq!(symbols.describe(0x8001_0000).as_deref(), Some("main"));
+        assert_eq!(symbols.describe(0x8001_0010).as_deref(), Some("main+0x10"));
+        assert_eq!(symbols.describe(0x8001_0020), None);
+        assert_eq!(symbols.describe(0x8000_0000), None);
+        assert_eq!(
+            symbols.describe(0x8002_ffff).as_deref(),
+            Some("data+0xffff")
+        );
+        assert_eq!(symbols.describe(0x8003_0000), None);
+    }
+
+    #[test]
+    fn loads_maps_and_nm_output() {
+        let mut symbols = SymbolTable::new();
+
+        let map = " 80010000 main\n 0x0000000080010100 _start\n 80010200 not a symbol\n";
+
+        assert_eq!(symbols.load_map(map), 2);
+        assert_eq!(symbols.find("_start"), Some(0x8001_0100));
+
+        let nm = "80020000 T func\n         U undefined\n80020100 D data.1\n";
+
+        assert_eq!(symbols.load_nm(nm), 2);
+        assert_eq!(symbols.find("data.1"), Some(0x8002_0100));
+        assert_eq!(symbols.find("undefined"), None);
+
+        assert!(SymbolTable::new().load(b"nothing useful").is_err());
+    }
+
+    #[test]
+    fn loads_sym_files() {
+        let mut bytes = b"MND\x01\x00\x00\x00\x00".to_vec();
+
+        bytes.extend_from_slice(&0x8001_0000u32.to_le_bytes());
+        bytes.push(0x1);
+        sym_string(&mut bytes, "main");
+
+        // line 10 of main.c starts at main, the next instruction is line 11
+        bytes.extend_from_slice(&0x8001_0000u32.to_le_bytes());
+        bytes.push(0x88);
+        bytes.extend_from_slice(&10u32.to_le_bytes());
+        sym_string(&mut bytes, "main.c");
+
+        bytes.extend_from_slice(&0x8001_0004u32.to_le_bytes());
+        bytes.push(0x80);
+
+        bytes.extend_from_slice(&0x8001_0008u32.to_le_bytes());
+        bytes.push(0x8a);
+
+        let mut symbols = SymbolTable::new();
+
+        assert_eq!(symbols.load(&bytes), Ok(1));
+        assert_eq!(symbols.find("main"), Some(0x8001_0000));
+        assert_eq!(symbols.line_at(0x8001_0000), Some(("main.c", 10)));
+        assert_eq!(symbols.line_at(0x8001_0004), Some(("main.c", 11)));
+        assert_eq!(symbols.line_at(0x8001_0008), None);
+
+        bytes.extend_from_slice(&0x8001_000cu32.to_le_bytes());
+        bytes.push(0x7f);
+
+        assert!(SymbolTable::new().load_sym(&bytes).is_err());
+
+        // cut off in the middle of the name
+        assert!(SymbolTable::new().load_sym(&bytes[..0x10]).is_err());
+    }
+
+    #[test]
+    fn loads_elf_symbols() {
+        let bytes = elf(&[
+            ("main", 0x8001_0000, 0x20, 2),
+            ("counter", 0x8002_0000, 4, 1),
+            ("file.c", 0, 0, 4),
+        ]);
+
+        let mut symbols = SymbolTable::new();
+
+        assert_eq!(symbols.load(&bytes), Ok(2));
+        assert_eq!(symbols.describe(0x8001_001c).as_deref(), Some("main+0x1c"));
+        assert_eq!(symbols.find("counter"), Some(0x8002_0000));
+        assert_eq!(symbols.find("file.c"), None);
+    }
+
+    #[test]
+    fn rejects_truncated_elf_tables() {
+        let bytes = elf(&[("main", 0x8001_0000, 0x20, 2)]);
+
+        for len in [0x30, 0x34 + 0x28 + 0x10, 0x34 + 3 * 0x28 + 8] {
+            assert!(SymbolTable::new().load_elf(&bytes[..len]).is_err(), "{len}");
+        }
+    }
+}
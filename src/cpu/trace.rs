@@ -0,0 +1,533 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use super::{
+    CPU,
+    disassembler::{disassemble, disassemble_with_symbols},
+    symbols::SymbolTable,
+};
+use crate::log;
+
+const TRACE_MAGIC: &[u8; 8] = b"RSXTRACE";
+const TRACE_VERSION: u16 = 1;
+
+// register numbers used in trace records, 0-31 are the GPRs
+pub const REGISTER_HI: u8 = 32;
+pub const REGISTER_LO: u8 = 33;
+pub const GTE_DATA_BASE: u8 = 64;
+pub const GTE_CONTROL_BASE: u8 = 96;
+
+const MEMORY_WRITE: u8 = 0x80;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RegisterWrite {
+    pub register: u8,
+    pub value: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MemoryAccess {
+    pub address: u32,
+    pub value: u32,
+    pub size: u8,
+    pub is_write: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TraceRecord {
+    pub pc: u32,
+    pub opcode: u32,
+    pub register_writes: Vec<RegisterWrite>,
+    pub memory_accesses: Vec<MemoryAccess>,
+}
+
+fn register_name(register: u8) -> String {
+    match register {
+        0..=31 => format!("r{register}"),
+        REGISTER_HI => "hi".to_string(),
+        REGISTER_LO => "lo".to_string(),
+        GTE_DATA_BASE..GTE_CONTROL_BASE => format!("gd{}", register - GTE_DATA_BASE),
+        _ => format!("gc{}", register - GTE_CONTROL_BASE),
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/*
+  Records are stored as:
+  pc (u32), opcode (u32), register write count (u8), memory access count (u8),
+  register writes: register (u8), value (u32)
+  memory accesses: size | 0x80 if it's a write (u8), address (u32), value (u32)
+*/
+impl TraceRecord {
+    pub fn disassembly(&self) -> String {
+        disassemble(self.opcode, self.pc.wrapping_add(4))
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(
+            10 + self.register_writes.len() * 5 + self.memory_accesses.len() * 9,
+        );
+
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.extend_from_slice(&self.opcode.to_le_bytes());
+        bytes.push(self.register_writes.len() as u8);
+        bytes.push(self.memory_accesses.len() as u8);
+
+        for write in &self.register_writes {
+            bytes.push(write.register);
+            bytes.extend_from_slice(&write.value.to_le_bytes());
+        }
+
+        for access in &self.memory_accesses {
+            bytes.push(access.size | if access.is_write { MEMORY_WRITE } else { 0 });
+            bytes.extend_from_slice(&access.address.to_le_bytes());
+            bytes.extend_from_slice(&access.value.to_le_bytes());
+        }
+
+        writer.write_all(&bytes)
+    }
+
+    // returns None once the end of the trace has been reached
+    fn read_from(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut bytes = [0; 4];
+
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let pc = u32::from_le_bytes(bytes);
+        let opcode = read_u32(reader)?;
+        let num_register_writes = read_u8(reader)?;
+        let num_memory_accesses = read_u8(reader)?;
+
+        let register_writes = (0..num_register_writes)
+            .map(|_| {
+                Ok(RegisterWrite {
+                    register: read_u8(reader)?,
+                    value: read_u32(reader)?,
+                })
+            })
+            .collect::<io::Result<Vec<RegisterWrite>>>()?;
+
+        let memory_accesses = (0..num_memory_accesses)
+            .map(|_| {
+                let flags = read_u8(reader)?;
+
+                Ok(MemoryAccess {
+                    size: flags & !MEMORY_WRITE,
+                    is_write: flags & MEMORY_WRITE != 0,
+                    address: read_u32(reader)?,
+                    value: read_u32(reader)?,
+                })
+            })
+            .collect::<io::Result<Vec<MemoryAccess>>>()?;
+
+        Ok(Some(Self {
+            pc,
+            opcode,
+            register_writes,
+            memory_accesses,
+        }))
+    }
+}
+
+impl TraceRecord {
+    // same as the Display output, with symbol names next to code and memory addresses
+    pub fn display_with_symbols(&self, symbols: &SymbolTable) -> String {
+        let mut text = String::new();
+
+        self.write(&mut text, symbols).unwrap();
+
+        text
+    }
+
+    fn write(&self, f: &mut impl fmt::Write, symbols: &SymbolTable) -> fmt::Result {
+        let location = match symbols.describe(self.pc) {
+            Some(name) => format!("{:08x} <{name}>", self.pc),
+            None => format!("{:08x}", self.pc),
+        };
+
+        write!(
+            f,
+            "{location}: {:08x} {:<32}",
+            self.opcode,
+            disassemble_with_symbols(self.opcode, self.pc.wrapping_add(4), symbols, None)
+        )?;
+
+        for write in &self.register_writes {
+            write!(f, " {}=0x{:x}", register_name(write.register), write.value)?;
+        }
+
+        for access in &self.memory_accesses {
+            let direction = if access.is_write { "w" } else { "r" };
+
+            let name = symbols
+                .describe(access.address)
+                .map(|name| format!(" <{name}>"))
+                .unwrap_or_default();
+
+            write!(
+                f,
+                " [{direction}{} 0x{:08x}{name} = 0x{:x}]",
+                access.size * 8,
+                access.address,
+                access.value
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &SymbolTable::new())
+    }
+}
+
+pub struct TraceRecorder {
+    writer: Box<dyn Write>,
+    current: TraceRecord,
+    registers: [u32; 34],
+    gte_registers: Option<[u32; 64]>,
+    pub instructions: u64,
+}
+
+impl TraceRecorder {
+    pub fn new(mut writer: impl Write + 'static) -> io::Result<Self> {
+        writer.write_all(TRACE_MAGIC)?;
+        writer.write_all(&TRACE_VERSION.to_le_bytes())?;
+
+        Ok(Self {
+            writer: Box::new(writer),
+            current: TraceRecord::default(),
+            registers: [0; 34],
+            gte_registers: None,
+            instructions: 0,
+        })
+    }
+
+    pub fn record_access(&mut self, address: u32, value: u32, size: u8, is_write: bool) {
+        self.current.memory_accesses.push(MemoryAccess {
+            address,
+            value,
+            size,
+            is_write,
+        });
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct TraceReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> TraceReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+
+        if &magic != TRACE_MAGIC || u16::from_le_bytes(version) != TRACE_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not an rsx-redux trace file",
+            ));
+        }
+
+        Ok(Self { reader })
+    }
+}
+
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = io::Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        TraceRecord::read_from(&mut self.reader).transpose()
+    }
+}
+
+pub struct Divergence {
+    // index of the first instruction that differs
+    pub index: u64,
+    // None if that trace ended first
+    pub expected: Option<TraceRecord>,
+    pub actual: Option<TraceRecord>,
+    // the instructions leading up to the divergence, which both traces agree on
+    pub context: Vec<TraceRecord>,
+}
+
+// walks both traces in lockstep and stops at the first record that differs
+pub fn diff_traces(
+    mut expected: impl Iterator<Item = io::Result<TraceRecord>>,
+    mut actual: impl Iterator<Item = io::Result<TraceRecord>>,
+    context_len: usize,
+) -> io::Result<Option<Divergence>> {
+    let mut context = VecDeque::with_capacity(context_len + 1);
+    let mut index = 0;
+
+    loop {
+        let expected_record = expected.next().transpose()?;
+        let actual_record = actual.next().transpose()?;
+
+        if expected_record.is_none() && actual_record.is_none() {
+            return Ok(None);
+        }
+
+        if expected_record != actual_record {
+            return Ok(Some(Divergence {
+                index,
+                expected: expected_record,
+                actual: actual_record,
+                context: context.into(),
+            }));
+        }
+
+        if context_len > 0 {
+            if context.len() == context_len {
+                context.pop_front();
+            }
+
+            context.push_back(expected_record.unwrap());
+        }
+
+        index += 1;
+    }
+}
+
+fn is_gte_instruction(opcode: u32) -> bool {
+    matches!(opcode >> 26, 0x12 | 0x32 | 0x3a)
+}
+
+impl CPU {
+    pub fn start_trace(&mut self, writer: impl Write + 'static) -> io::Result<()> {
+        self.trace = Some(TraceRecorder::new(writer)?);
+
+        Ok(())
+    }
+
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(mut trace) => trace.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn trace_registers(&self) -> [u32; 34] {
+        let mut registers = [0; 34];
+
+        registers[..32].copy_from_slice(&self.r.0);
+        registers[REGISTER_HI as usize] = self.hi;
+        registers[REGISTER_LO as usize] = self.lo;
+
+        registers
+    }
+
+    fn trace_gte_registers(&mut self) -> [u32; 64] {
+        let mut registers = [0; 64];
+
+        for i in 0..32 {
+            registers[i] = self.gte.read_data(i);
+            registers[i + 32] = self.gte.read_control(i);
+        }
+
+        registers
+    }
+
+    pub(crate) fn trace_begin(&mut self, opcode: u32) {
+        let registers = self.trace_registers();
+
+        // GTE registers are only compared for the instructions that can change them
+        let gte_registers = is_gte_instruction(opcode).then(|| self.trace_gte_registers());
+
+        if let Some(trace) = &mut self.trace {
+            trace.current = TraceRecord {
+                pc: self.previous_pc,
+                opcode,
+                register_writes: Vec::new(),
+                memory_accesses: Vec::new(),
+            };
+            trace.registers = registers;
+            trace.gte_registers = gte_registers;
+        }
+    }
+
+    // register writes are whatever changed over the step, so the result of a
+    // delayed load shows up on the instruction after the load, same as on hardware
+    pub(crate) fn trace_end(&mut self) {
+        let registers = self.trace_registers();
+
+        let gte_registers = match &self.trace {
+            Some(trace) if trace.gte_registers.is_some() => Some(self.trace_gte_registers()),
+            _ => None,
+        };
+
+        let Some(trace) = &mut self.trace else {
+            return;
+        };
+
+        for (i, value) in registers.iter().enumerate() {
+            if trace.registers[i] != *value {
+                trace.current.register_writes.push(RegisterWrite {
+                    register: i as u8,
+                    value: *value,
+                });
+            }
+        }
+
+        if let (Some(before), Some(after)) = (trace.gte_registers, gte_registers) {
+            for i in 0..64 {
+                if before[i] != after[i] {
+                    trace.current.register_writes.push(RegisterWrite {
+                        register: GTE_DATA_BASE + i as u8,
+                        value: after[i],
+                    });
+                }
+            }
+        }
+
+        trace.instructions += 1;
+
+        if let Err(e) = trace.current.write_to(&mut trace.writer) {
+            log!(Cpu, Error, "couldn't write to trace, stopping: {e}");
+            self.trace = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(pc: u32) -> TraceRecord {
+        TraceRecord {
+            pc,
+            opcode: 0x2408_0001,
+            register_writes: vec![
+                RegisterWrite {
+                    register: 8,
+                    value: 1,
+                },
+                RegisterWrite {
+                    register: GTE_CONTROL_BASE + 31,
+                    value: 0x8000_0000,
+                },
+            ],
+            memory_accesses: vec![
+                MemoryAccess {
+                    address: 0x8001_0000,
+                    value: 0x12,
+                    size: 1,
+                    is_write: true,
+                },
+                MemoryAccess {
+                    address: 0x1f80_1070,
+                    value: 0x1234_5678,
+                    size: 4,
+                    is_write: false,
+                },
+            ],
+        }
+    }
+
+    fn encode(records: &[TraceRecord]) -> Vec<u8> {
+        let mut bytes = TRACE_MAGIC.to_vec();
+
+        bytes.extend_from_slice(&TRACE_VERSION.to_le_bytes());
+
+        for record in records {
+            record.write_to(&mut bytes).unwrap();
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = [record(0x8001_0000), TraceRecord::default()];
+        let bytes = encode(&records);
+
+        let decoded = TraceReader::new(&bytes[..])
+            .unwrap()
+            .collect::<io::Result<Vec<TraceRecord>>>()
+            .unwrap();
+
+        assert_eq!(decoded, records);
+    }
+
+    #[test]
+    fn rejects_bad_traces() {
+        assert!(TraceReader::new(&b"RSXTRACE\x02\x00"[..]).is_err());
+        assert!(TraceReader::new(&b"RSXTRAC"[..]).is_err());
+
+        // a record that stops in the middle
+        let bytes = encode(&[record(0x8001_0000)]);
+        let mut reader = TraceReader::new(&bytes[..bytes.len() - 1]).unwrap();
+
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn finds_the_first_divergence() {
+        let expected = [record(0), record(4), record(8)];
+        let mut actual = expected.clone();
+
+        actual[2].register_writes[0].value = 2;
+
+        let divergence = diff_traces(
+            expected.iter().cloned().map(Ok),
+            actual.iter().cloned().map(Ok),
+            1,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.expected.as_ref(), Some(&expected[2]));
+        assert_eq!(divergence.actual.as_ref(), Some(&actual[2]));
+        assert_eq!(divergence.context, [expected[1].clone()]);
+
+        // one trace ending early counts as a divergence, identical ones don't
+        let divergence = diff_traces(
+            expected.iter().cloned().map(Ok),
+            expected[..2].iter().cloned().map(Ok),
+            0,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.actual, None);
+
+        assert!(
+            diff_traces(
+                expected.iter().cloned().map(Ok),
+                expected.iter().cloned().map(Ok),
+                4
+            )
+            .unwrap()
+            .is_none()
+        );
+    }
+}
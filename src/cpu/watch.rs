@@ -0,0 +1,444 @@
+use std::collections::VecDeque;
+
+use super::bus::{Bus, dma::dma_channel::DmaWrite, ram_search::ValueType};
+
+const MAX_EVENTS: usize = 0x400;
+
+pub type WatchId = usize;
+
+type WatchCallback = Box<dyn FnMut(&WatchEvent)>;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WatchFormat {
+    Hex,
+    Decimal,
+    Binary,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WriteSource {
+    Cpu { pc: u32 },
+    // a transfer into main RAM, started (or picked up again) by the CPU or the scheduler
+    Dma { channel: usize },
+    // cheats, the debugger and anything else that writes to memory without going through the
+    // CPU or DMA, only noticed once per frame when the watch list gets polled
+    External,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct WatchEvent {
+    pub id: WatchId,
+    pub address: u32,
+    pub old_value: i64,
+    pub new_value: i64,
+    pub source: WriteSource,
+}
+
+#[derive(Clone, Debug)]
+pub struct WatchEntry {
+    pub address: u32,
+    pub value_type: ValueType,
+    pub format: WatchFormat,
+    pub label: String,
+    pub frozen: Option<i64>,
+    pub notify: bool,
+    pub value: i64,
+}
+
+impl WatchEntry {
+    pub fn new(address: u32, value_type: ValueType, format: WatchFormat) -> Self {
+        Self {
+            address: Bus::physical_address(address),
+            value_type,
+            format,
+            label: String::new(),
+            frozen: None,
+            notify: false,
+            value: 0,
+        }
+    }
+
+    pub fn display(&self) -> String {
+        let width = self.value_type.size() * 2;
+        let mask = (1u64 << (self.value_type.size() * 8)) - 1;
+        let raw = self.value as u64 & mask;
+
+        match self.format {
+            WatchFormat::Hex => format!("0x{raw:0width$x}"),
+            WatchFormat::Decimal => format!("{}", self.value),
+            WatchFormat::Binary => format!("0b{:0width$b}", raw, width = width * 4),
+        }
+    }
+
+    fn contains(&self, address: u32, size: usize) -> bool {
+        address < self.address + self.value_type.size() as u32
+            && self.address < address + size as u32
+    }
+
+    fn peek(&self, bus: &Bus) -> Option<i64> {
+        let mut bytes = [0; 4];
+
+        for (i, byte) in bytes.iter_mut().take(self.value_type.size()).enumerate() {
+            *byte = bus.peek8(self.address + i as u32)?;
+        }
+
+        Some(self.value_type.read(&bytes, 0))
+    }
+}
+
+#[derive(Default)]
+pub struct WatchList {
+    entries: Vec<Option<WatchEntry>>,
+    // removed entries leave a hole so ids stay valid, this is what lets stores skip the list
+    active: usize,
+    events: VecDeque<WatchEvent>,
+    callbacks: Vec<WatchCallback>,
+    // values still get tracked, but nothing gets notified
+    muted: bool,
+}
+
+impl WatchList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, mut entry: WatchEntry, bus: &Bus) -> WatchId {
+        entry.value = entry.peek(bus).unwrap_or_default();
+
+        self.entries.push(Some(entry));
+        self.active += 1;
+
+        self.entries.len() - 1
+    }
+
+    pub fn remove(&mut self, id: WatchId) {
+        if let Some(entry) = self.entries.get_mut(id)
+            && entry.take().is_some()
+        {
+            self.active -= 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.events.clear();
+        self.active = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active == 0
+    }
+
+    pub fn get(&self, id: WatchId) -> Option<&WatchEntry> {
+        self.entries.get(id).and_then(|entry| entry.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: WatchId) -> Option<&mut WatchEntry> {
+        self.entries.get_mut(id).and_then(|entry| entry.as_mut())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (WatchId, &WatchEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(id, entry)| entry.as_ref().map(|entry| (id, entry)))
+    }
+
+    // returns false for anything outside of RAM and the scratchpad, which can't be frozen
+    pub fn freeze(&mut self, id: WatchId, value: i64) -> bool {
+        match self.get_mut(id) {
+            Some(entry) if Bus::is_memory(entry.address) => {
+                entry.frozen = Some(value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn unfreeze(&mut self, id: WatchId) {
+        if let Some(entry) = self.get_mut(id) {
+            entry.frozen = None;
+        }
+    }
+
+    pub fn subscribe(&mut self, id: WatchId, notify: bool) {
+        if let Some(entry) = self.get_mut(id) {
+            entry.notify = notify;
+        }
+    }
+
+    // callbacks get run for every change event on entries that are subscribed
+    pub fn on_change(&mut self, callback: impl FnMut(&WatchEvent) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn drain_events(&mut self) -> Vec<WatchEvent> {
+        self.events.drain(..).collect()
+    }
+
+    fn push_event(&mut self, event: WatchEvent) {
+        if self.muted {
+            return;
+        }
+
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(event);
+
+        for callback in self.callbacks.iter_mut() {
+            callback(&event);
+        }
+    }
+
+    // called by the CPU store instructions after the write went through
+    pub fn on_write(&mut self, bus: &Bus, address: u32, size: usize, value: u32, pc: u32) {
+        self.check_write(bus, address, size, Some(value), WriteSource::Cpu { pc });
+    }
+
+    // called by the CPU with the transfers its last store or event started
+    pub fn on_dma_write(&mut self, bus: &Bus, write: DmaWrite) {
+        self.check_write(
+            bus,
+            write.address,
+            write.len as usize,
+            None,
+            WriteSource::Dma {
+                channel: write.channel,
+            },
+        );
+    }
+
+    // value is what got written, for the I/O ports peek can't read back
+    fn check_write(
+        &mut self,
+        bus: &Bus,
+        address: u32,
+        size: usize,
+        value: Option<u32>,
+        source: WriteSource,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+
+        let address = Bus::physical_address(address);
+
+        for id in 0..self.entries.len() {
+            let Some(entry) = &mut self.entries[id] else {
+                continue;
+            };
+
+            if !entry.contains(address, size) {
+                continue;
+            }
+
+            // I/O ports can't be read back safely, so only exact writes to them are tracked
+            let new_value = match (entry.peek(bus), value) {
+                (Some(new_value), _) => new_value,
+                (None, Some(value))
+                    if entry.address == address && entry.value_type.size() == size =>
+                {
+                    entry.value_type.read(&value.to_le_bytes(), 0)
+                }
+                _ => continue,
+            };
+
+            if new_value != entry.value {
+                let event = WatchEvent {
+                    id,
+                    address: entry.address,
+                    old_value: entry.value,
+                    new_value,
+                    source,
+                };
+
+                entry.value = new_value;
+
+                if entry.notify {
+                    self.push_event(event);
+                }
+            }
+        }
+    }
+
+    // called once per frame at the start of vblank
+    pub fn update_frame(&mut self, bus: &mut Bus) {
+        for id in 0..self.entries.len() {
+            let Some(entry) = &mut self.entries[id] else {
+                continue;
+            };
+
+            // only RAM and the scratchpad get written, rewriting an I/O port every frame would
+            // run its side effects each time
+            if let Some(frozen) = entry.frozen {
+                let bytes = (frozen as u32).to_le_bytes();
+
+                for (i, &byte) in bytes.iter().take(entry.value_type.size()).enumerate() {
+                    bus.poke8(entry.address + i as u32, byte);
+                }
+
+                entry.value = frozen;
+
+                continue;
+            }
+
+            let Some(new_value) = entry.peek(bus) else {
+                continue;
+            };
+
+            if new_value != entry.value {
+                let event = WatchEvent {
+                    id,
+                    address: entry.address,
+                    old_value: entry.value,
+                    new_value,
+                    source: WriteSource::External,
+                };
+
+                entry.value = new_value;
+
+                if entry.notify {
+                    self.push_event(event);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    #[test]
+    fn reports_writes_to_subscribed_entries() {
+        let mut bus = Bus::new();
+        let mut watches = WatchList::new();
+
+        let id = watches.add(
+            WatchEntry::new(0x8000_0100, ValueType::U16, WatchFormat::Hex),
+            &bus,
+        );
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let callback_seen = seen.clone();
+
+        watches.on_change(move |event| callback_seen.borrow_mut().push(event.new_value));
+
+        // not subscribed yet, so only the value gets updated
+        bus.poke8(0x100, 1);
+        watches.on_write(&bus, 0x0000_0100, 1, 1, 0x8001_0000);
+
+        assert_eq!(watches.get(id).unwrap().value, 1);
+        assert!(watches.drain_events().is_empty());
+
+        watches.subscribe(id, true);
+
+        // through a mirror, to the upper byte
+        bus.poke8(0x101, 2);
+        watches.on_write(&bus, 0xa000_0101, 1, 2, 0x8001_0004);
+
+        let events = watches.drain_events();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].old_value, events[0].new_value), (1, 0x201));
+        assert_eq!(events[0].source, WriteSource::Cpu { pc: 0x8001_0004 });
+        assert_eq!(*seen.borrow(), [0x201]);
+        assert_eq!(watches.get(id).unwrap().display(), "0x0201");
+
+        // writes next to the entry don't count
+        bus.poke8(0x102, 3);
+        watches.on_write(&bus, 0x102, 1, 3, 0);
+
+        assert!(watches.drain_events().is_empty());
+
+        bus.poke8(0x100, 0);
+        watches.on_dma_write(
+            &bus,
+            DmaWrite {
+                channel: 6,
+                address: 0xf0,
+                len: 0x20,
+            },
+        );
+
+        assert_eq!(
+            watches.drain_events()[0].source,
+            WriteSource::Dma { channel: 6 }
+        );
+    }
+
+    #[test]
+    fn freezes_memory_only() {
+        let mut bus = Bus::new();
+        let mut watches = WatchList::new();
+
+        let ram = watches.add(
+            WatchEntry::new(0x1f80_0010, ValueType::U32, WatchFormat::Decimal),
+            &bus,
+        );
+        let io = watches.add(
+            WatchEntry::new(0x1f80_1070, ValueType::U16, WatchFormat::Hex),
+            &bus,
+        );
+
+        assert!(watches.freeze(ram, 0x1234_5678));
+        assert!(!watches.freeze(io, 0));
+
+        watches.update_frame(&mut bus);
+
+        assert_eq!(bus.scratchpad[0x10..0x14], 0x1234_5678u32.to_le_bytes());
+
+        bus.scratchpad[0x10] = 0;
+        watches.update_frame(&mut bus);
+
+        assert_eq!(bus.scratchpad[0x10], 0x78);
+
+        watches.unfreeze(ram);
+        bus.scratchpad[0x10] = 0;
+        watches.update_frame(&mut bus);
+
+        assert_eq!(bus.scratchpad[0x10], 0);
+    }
+
+    #[test]
+    fn skips_writes_once_every_entry_is_removed() {
+        let bus = Bus::new();
+        let mut watches = WatchList::new();
+
+        let first = watches.add(
+            WatchEntry::new(0x8000_0000, ValueType::U8, WatchFormat::Hex),
+            &bus,
+        );
+        let second = watches.add(
+            WatchEntry::new(0x8000_0004, ValueType::U8, WatchFormat::Hex),
+            &bus,
+        );
+
+        watches.remove(first);
+        watches.remove(first);
+
+        assert!(!watches.is_empty());
+
+        watches.remove(second);
+
+        assert!(watches.is_empty());
+        assert!(watches.get(second).is_none());
+
+        watches.add(
+            WatchEntry::new(0x8000_0000, ValueType::U8, WatchFormat::Hex),
+            &bus,
+        );
+        watches.clear();
+
+        assert!(watches.is_empty());
+        assert_eq!(watches.entries().count(), 0);
+    }
+}
@@ -0,0 +1,74 @@
+use std::{env, fs, fs::File, io::BufWriter, path::Path, process::exit};
+
+use rsx_redux::cpu::bus::{
+    gpu::{
+        GPU,
+        dump::{GpuDump, GpuPlayer},
+        vram_viewer::VramViewer,
+    },
+    scheduler::Scheduler,
+};
+
+const SYNTAX: &str = "syntax: rsx-gpu-replay <dump.gpudump> <output_dir> [--frame <n>]
+plays a GPU dump back with the software renderer and writes VRAM after every frame to
+<output_dir>/frame_<n>.png, or only after the given frame with --frame.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{message}");
+    exit(2);
+}
+
+fn write_frame(gpu: &GPU, dir: &Path, frame: usize) {
+    let path = dir.join(format!("frame_{frame:05}.png"));
+
+    let output = File::create(&path)
+        .unwrap_or_else(|e| fail(format!("couldn't create {}: {e}", path.display())));
+
+    VramViewer::new(&gpu.vram)
+        .and_then(|viewer| viewer.full().write_png(BufWriter::new(output)))
+        .unwrap_or_else(|e| fail(format!("couldn't write {}: {e}", path.display())));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        fail(SYNTAX.to_string());
+    }
+
+    let data =
+        fs::read(&args[1]).unwrap_or_else(|e| fail(format!("couldn't open {}: {e}", args[1])));
+    let dump = GpuDump::from_bytes(&data).unwrap_or_else(|e| fail(format!("{}: {e}", args[1])));
+
+    let only_frame = args.iter().position(|arg| arg == "--frame").map(|index| {
+        args.get(index + 1)
+            .and_then(|frame| frame.parse::<usize>().ok())
+            .unwrap_or_else(|| fail("--frame takes a frame number".to_string()))
+    });
+
+    let dir = Path::new(&args[2]);
+
+    fs::create_dir_all(dir)
+        .unwrap_or_else(|e| fail(format!("couldn't create {}: {e}", dir.display())));
+
+    let mut gpu = GPU::new(&mut Scheduler::new());
+    let mut player = GpuPlayer::new(dump);
+
+    let mut frame = 0;
+    let mut written = 0;
+
+    while player.play_frame(&mut gpu) {
+        if only_frame.is_none_or(|only_frame| only_frame == frame) {
+            write_frame(&gpu, dir, frame);
+            written += 1;
+        }
+
+        frame += 1;
+    }
+
+    // whatever came after the last vblank counts as a frame too
+    println!(
+        "played {frame} frame(s), wrote {written} image(s) to {}",
+        dir.display()
+    );
+}
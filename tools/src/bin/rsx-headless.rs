@@ -0,0 +1,445 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{BufWriter, Read},
+    path::Path,
+    process::exit,
+};
+
+use memmap2::Mmap;
+use rsx_redux::cpu::{
+    CPU,
+    bus::gpu::{GPU, vram_viewer::Image},
+    exe::{self, ExeFormat},
+    logger,
+};
+
+const DEFAULT_FRAMES: usize = 600;
+const SAMPLE_RATE: u32 = 44100;
+
+const SYNTAX: &str = "syntax: rsx-headless <disc-or-exe> [options]
+  --bios <path>          the BIOS to boot (default SCPH1001.bin)
+  --frames <n>           how many frames to run, or the most to wait for with --until-tty (default 600)
+  --until-tty <text>     stop once a line of TTY output contains the text
+  --input <script>       press and release buttons, one change per line, ie `120 start down`
+  --output <dir>         where framebuffer.png, audio.wav, tty.log and state_hash.txt go
+                         (default headless)
+  --golden <dir>         compare those files with the ones in dir and exit with 1 if any differ
+  --log <filters>        log levels like the desktop app takes them, ie `info,cdrom=debug`
+test programs can stop the run early by writing their exit code to 1F802082h, which becomes the
+exit code of rsx-headless unless it's 0 and something else failed.
+buttons are select, l3, r3, start, up, right, down, left, l2, r2, l1, r1, triangle, circle,
+cross and square. lines starting with # are comments.";
+
+const BUTTONS: [&str; 16] = [
+    "select", "l3", "r3", "start", "up", "right", "down", "left", "l2", "r2", "l1", "r1",
+    "triangle", "circle", "cross", "square",
+];
+
+const OUTPUT_FILES: [&str; 4] = ["framebuffer.png", "audio.wav", "tty.log", "state_hash.txt"];
+
+struct InputEvent {
+    frame: usize,
+    button: usize,
+    pressed: bool,
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{message}");
+    exit(2);
+}
+
+// only the low 8 bits of an exit code make it to the shell on Unix, so a failure like 0x100 can't
+// be allowed to look like a pass
+fn process_exit_code(code: i32) -> i32 {
+    if code & 0xff == 0 { 1 } else { code }
+}
+
+fn get_option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .map(|index| {
+            args.get(index + 1)
+                .unwrap_or_else(|| fail(format!("{name} takes a value")))
+        })
+        .map(|value| value.as_str())
+}
+
+fn parse_input_script(script: &str) -> Result<Vec<InputEvent>, String> {
+    let mut events = Vec::new();
+
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = || format!("line {}: expected <frame> <button> <down|up>", i + 1);
+
+        let [frame, button, state] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(error());
+        };
+
+        let frame = frame.parse::<usize>().map_err(|_| error())?;
+
+        let button = BUTTONS
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(button))
+            .ok_or_else(|| format!("line {}: unknown button {button}", i + 1))?;
+
+        let pressed = match state {
+            "down" => true,
+            "up" => false,
+            _ => return Err(error()),
+        };
+
+        events.push(InputEvent {
+            frame,
+            button,
+            pressed,
+        });
+    }
+
+    events.sort_by_key(|event| event.frame);
+
+    Ok(events)
+}
+
+fn boot(path: &str, bios: Vec<u8>) -> CPU {
+    let file_path = Path::new(path);
+
+    let mut header = [0; 8];
+    let header_len = File::open(file_path)
+        .and_then(|mut file| file.read(&mut header))
+        .unwrap_or_else(|e| fail(format!("couldn't open {path}: {e}")));
+
+    let extension = file_path
+        .extension()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or_default();
+
+    let mut cpu = match exe::detect_format(&header[..header_len]) {
+        Some(exe_format) => {
+            let exe_bytes =
+                fs::read(path).unwrap_or_else(|e| fail(format!("couldn't read {path}: {e}")));
+
+            let mut cpu = CPU::new(Some(exe_bytes.clone()), String::new());
+
+            if exe_format == ExeFormat::Elf {
+                let _ = cpu.symbols.load_elf(&exe_bytes);
+            }
+
+            cpu
+        }
+        None => match extension {
+            "bin" | "iso" => {
+                let file = File::open(file_path)
+                    .unwrap_or_else(|e| fail(format!("couldn't open {path}: {e}")));
+                let game_data = unsafe { Mmap::map(&file) }
+                    .unwrap_or_else(|e| fail(format!("couldn't map {path}: {e}")));
+
+                let mut cpu = CPU::new(None, path.to_string());
+
+                cpu.bus.cdrom.load_game_desktop(game_data);
+
+                cpu
+            }
+            "cue" => {
+                let cue_contents = fs::read_to_string(path)
+                    .unwrap_or_else(|e| fail(format!("couldn't read {path}: {e}")));
+                let base_path = file_path.parent().unwrap_or(Path::new("."));
+
+                let mut cpu = CPU::new(None, path.to_string());
+
+                cpu.bus
+                    .cdrom
+                    .parse_cue(base_path.to_path_buf(), cue_contents);
+
+                cpu
+            }
+            _ => fail(format!(
+                "{path} is neither an executable nor a .bin/.iso/.cue disc image"
+            )),
+        },
+    };
+
+    cpu.bus.load_bios(bios);
+
+    cpu
+}
+
+// the displayed part of VRAM, as the frontends would show it
+fn framebuffer(gpu: &mut GPU) -> Image {
+    gpu.update_framebuffer();
+
+    let (width, height) = gpu.get_dimensions();
+
+    let pixels = gpu.picture[..(width * height * 3) as usize]
+        .chunks_exact(3)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 0xff])
+        .collect();
+
+    Image {
+        width: width as usize,
+        height: height as usize,
+        pixels,
+    }
+}
+
+// 16-bit stereo PCM
+fn encode_wav(samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, 2 channels
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    // bytes per second and per frame, then bits per sample
+    wav.extend_from_slice(&(SAMPLE_RATE * 4).to_le_bytes());
+    wav.extend_from_slice(&4u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+fn decode_png(path: &Path) -> Result<(u32, u32, Vec<u8>), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+
+    let mut reader = png::Decoder::new(file)
+        .read_info()
+        .map_err(|e| e.to_string())?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+
+    let info = reader.next_frame(&mut pixels).map_err(|e| e.to_string())?;
+
+    pixels.truncate(info.buffer_size());
+
+    Ok((info.width, info.height, pixels))
+}
+
+// images get compared by their pixels, so a different PNG encoder doesn't count as a mismatch
+fn matches_golden(name: &str, output: &Path, golden: &Path) -> Result<bool, String> {
+    let golden_path = golden.join(name);
+    let output_path = output.join(name);
+
+    if !golden_path.exists() {
+        return Err("no golden file".to_string());
+    }
+
+    if name.ends_with(".png") {
+        return Ok(decode_png(&output_path)? == decode_png(&golden_path)?);
+    }
+
+    let read = |path: &Path| fs::read(path).map_err(|e| e.to_string());
+
+    Ok(read(&output_path)? == read(&golden_path)?)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 || args[1].starts_with("--") {
+        fail(SYNTAX.to_string());
+    }
+
+    let bios_path = get_option(&args, "--bios").unwrap_or("SCPH1001.bin");
+    let bios = fs::read(bios_path)
+        .unwrap_or_else(|e| fail(format!("couldn't read the BIOS from {bios_path}: {e}")));
+
+    let frames = get_option(&args, "--frames")
+        .map(|frames| {
+            frames
+                .parse::<usize>()
+                .unwrap_or_else(|_| fail(format!("invalid number of frames: {frames}")))
+        })
+        .unwrap_or(DEFAULT_FRAMES);
+
+    let until_tty = get_option(&args, "--until-tty");
+
+    let inputs = match get_option(&args, "--input") {
+        Some(script_path) => fs::read_to_string(script_path)
+            .map_err(|e| e.to_string())
+            .and_then(|script| parse_input_script(&script))
+            .unwrap_or_else(|e| fail(format!("couldn't load {script_path}: {e}"))),
+        None => Vec::new(),
+    };
+
+    let output = Path::new(get_option(&args, "--output").unwrap_or("headless"));
+
+    if let Some(filters) = get_option(&args, "--log") {
+        logger::apply_filters(filters)
+            .unwrap_or_else(|e| fail(format!("invalid log filters: {e}")));
+    }
+
+    let mut cpu = boot(&args[1], bios);
+
+    let mut inputs = inputs.iter().peekable();
+    let mut audio: Vec<i16> = Vec::new();
+    let mut tty = String::new();
+    let mut found_tty = false;
+    let mut frame = 0;
+
+    while frame < frames && !found_tty && cpu.bus.exit_code().is_none() {
+        while let Some(event) = inputs.next_if(|event| event.frame <= frame) {
+            cpu.bus
+                .peripherals
+                .controller
+                .update_input(event.button, event.pressed);
+        }
+
+        while !cpu.bus.gpu.frame_finished && cpu.bus.exit_code().is_none() {
+            cpu.step();
+        }
+
+        cpu.bus.gpu.frame_finished = false;
+
+        audio.append(&mut cpu.bus.spu.audio_buffer);
+
+        tty += &cpu.bus.tty.take_output();
+
+        found_tty = until_tty.is_some_and(|text| tty.lines().any(|line| line.contains(text)));
+
+        frame += 1;
+    }
+
+    fs::create_dir_all(output)
+        .unwrap_or_else(|e| fail(format!("couldn't create {}: {e}", output.display())));
+
+    let image = framebuffer(&mut cpu.bus.gpu);
+
+    let write = |name: &str, result: Result<(), String>| {
+        if let Err(e) = result {
+            fail(format!(
+                "couldn't write {}: {e}",
+                output.join(name).display()
+            ));
+        }
+    };
+
+    write(
+        "framebuffer.png",
+        File::create(output.join("framebuffer.png"))
+            .map_err(|e| e.to_string())
+            .and_then(|file| image.write_png(BufWriter::new(file))),
+    );
+    write(
+        "audio.wav",
+        fs::write(output.join("audio.wav"), encode_wav(&audio)).map_err(|e| e.to_string()),
+    );
+    write(
+        "tty.log",
+        fs::write(output.join("tty.log"), &tty).map_err(|e| e.to_string()),
+    );
+    write(
+        "state_hash.txt",
+        fs::write(
+            output.join("state_hash.txt"),
+            format!("{:016x}\n", cpu.state_hash()),
+        )
+        .map_err(|e| e.to_string()),
+    );
+
+    println!(
+        "ran {frame} frame(s), wrote the results to {}",
+        output.display()
+    );
+
+    let mut failed = false;
+
+    if let Some(code) = cpu.bus.exit_code() {
+        println!("the program exited with code {code}");
+    }
+
+    if let Some(text) = until_tty
+        && !found_tty
+    {
+        println!("\"{text}\" never showed up in the TTY output");
+        failed = true;
+    }
+
+    if let Some(golden) = get_option(&args, "--golden") {
+        for name in OUTPUT_FILES {
+            match matches_golden(name, output, Path::new(golden)) {
+                Ok(true) => (),
+                Ok(false) => {
+                    println!("{name} doesn't match the golden file");
+                    failed = true;
+                }
+                Err(e) => {
+                    println!("couldn't compare {name}: {e}");
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    match cpu.bus.exit_code() {
+        Some(code) if code != 0 => exit(process_exit_code(code)),
+        _ if failed => exit(1),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_input_scripts() {
+        let script =
+            "# press start, then cross\n\n120 cross down\n60 START down\n  61 start up  \n";
+
+        let events: Vec<(usize, usize, bool)> = parse_input_script(script)
+            .unwrap()
+            .iter()
+            .map(|event| (event.frame, event.button, event.pressed))
+            .collect();
+
+        assert_eq!(events, [(60, 3, true), (61, 3, false), (120, 14, true)]);
+    }
+
+    #[test]
+    fn rejects_bad_input_scripts() {
+        for (script, error) in [
+            ("60 start", "line 1: expected <frame> <button> <down|up>"),
+            (
+                "60 start down now",
+                "line 1: expected <frame> <button> <down|up>",
+            ),
+            (
+                "# comment\nsoon start down",
+                "line 2: expected <frame> <button> <down|up>",
+            ),
+            (
+                "60 start held",
+                "line 1: expected <frame> <button> <down|up>",
+            ),
+            ("60 turbo down", "line 1: unknown button turbo"),
+        ] {
+            assert_eq!(parse_input_script(script).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn keeps_failures_failing() {
+        assert_eq!(process_exit_code(1), 1);
+        assert_eq!(process_exit_code(0xff), 0xff);
+        assert_eq!(process_exit_code(0x101), 0x101);
+        assert_eq!(process_exit_code(0x100), 1);
+        assert_eq!(process_exit_code(-256), 1);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{env, fs, fs::File, io::BufReader, process::exit};
+
+use rsx_redux::cpu::{
+    symbols::SymbolTable,
+    trace::{TraceReader, diff_traces},
+};
+
+const DEFAULT_CONTEXT: usize = 10;
+
+fn open_trace(path: &str) -> TraceReader<BufReader<File>> {
+    let file = File::open(path).unwrap_or_else(|e| {
+        eprintln!("couldn't open {path}: {e}");
+        exit(2);
+    });
+
+    TraceReader::new(BufReader::new(file)).unwrap_or_else(|e| {
+        eprintln!("couldn't read {path}: {e}");
+        exit(2);
+    })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        eprintln!(
+            "syntax: rsx-trace-diff <expected.trace> <actual.trace> [--context <lines>] [--symbols <file>]"
+        );
+        exit(2);
+    }
+
+    let context = args
+        .iter()
+        .position(|arg| arg == "--context")
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.parse::<usize>().expect("invalid context length"))
+        .unwrap_or(DEFAULT_CONTEXT);
+
+    let mut symbols = SymbolTable::new();
+
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--symbols")
+        .and_then(|index| args.get(index + 1))
+    {
+        let bytes = fs::read(path).unwrap_or_else(|e| {
+            eprintln!("couldn't open {path}: {e}");
+            exit(2);
+        });
+
+        if let Err(e) = symbols.load(&bytes) {
+            eprintln!("couldn't load symbols from {path}: {e}");
+            exit(2);
+        }
+    }
+
+    let expected = open_trace(&args[1]);
+    let actual = open_trace(&args[2]);
+
+    let divergence = diff_traces(expected, actual, context).unwrap_or_else(|e| {
+        eprintln!("error while reading traces: {e}");
+        exit(2);
+    });
+
+    let Some(divergence) = divergence else {
+        println!("traces match");
+        return;
+    };
+
+    println!("traces diverge at instruction {}\n", divergence.index);
+
+    for record in &divergence.context {
+        println!("  {}", record.display_with_symbols(&symbols));
+    }
+
+    match divergence.expected {
+        Some(record) => println!("- {}", record.display_with_symbols(&symbols)),
+        None => println!("- <end of trace>"),
+    }
+
+    match divergence.actual {
+        Some(record) => println!("+ {}", record.display_with_symbols(&symbols)),
+        None => println!("+ <end of trace>"),
+    }
+
+    exit(1);
+}
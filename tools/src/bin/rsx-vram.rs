@@ -0,0 +1,117 @@
+use std::{env, fs, fs::File, io::BufWriter, process::exit};
+
+use rsx_redux::cpu::{
+    CPU,
+    bus::gpu::{
+        GPU, TexturePageColors, VRAM_HEIGHT, VRAM_WIDTH,
+        vram_viewer::{Image, VramViewer},
+    },
+};
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+const SYNTAX: &str = "syntax: rsx-vram <state-or-vram-dump> <output.png> [options]
+  --texture <texpage> <clut>     decode the texture page with the texpage and CLUT attributes
+                                 from a GP0 polygon or rectangle command
+  --region <u> <v> <w> <h>       only decode part of the texture page (default 0 0 256 256)
+  --palette <clut> <4|8>         export the 16 or 256 colors of a CLUT
+  --direct15 <x> <y> <w> <h>     decode a region of VRAM as 15-bit color
+  --direct24 <x> <y> <w> <h>     decode a region of VRAM as 24-bit color, w being in 24-bit pixels
+without any of those the whole of VRAM gets exported.
+the input can be a save state from a software renderer build (compressed or not) or a raw
+dump of VRAM, which the desktop app writes with F12 whichever renderer it was built with.";
+
+fn fail(message: String) -> ! {
+    eprintln!("{message}");
+    exit(2);
+}
+
+fn parse_number(value: &str) -> usize {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed.unwrap_or_else(|_| fail(format!("invalid number: {value}")))
+}
+
+// the numbers following an option, ie --region 0 0 64 64
+fn get_numbers(args: &[String], name: &str, count: usize) -> Option<Vec<usize>> {
+    let index = args.iter().position(|arg| arg == name)?;
+
+    let values = args
+        .get(index + 1..index + 1 + count)
+        .unwrap_or_else(|| fail(format!("{name} takes {count} values")));
+
+    Some(values.iter().map(|value| parse_number(value)).collect())
+}
+
+fn load_vram(path: &str) -> Vec<u8> {
+    let mut data = fs::read(path).unwrap_or_else(|e| fail(format!("couldn't open {path}: {e}")));
+
+    if data.starts_with(&ZSTD_MAGIC) {
+        data = zstd::decode_all(&*data)
+            .unwrap_or_else(|e| fail(format!("couldn't decompress {path}: {e}")));
+    }
+
+    if data.len() == VRAM_WIDTH * VRAM_HEIGHT * 2 {
+        return data;
+    }
+
+    let mut cpu = CPU::new(None, String::new());
+
+    if !cpu.load_save_state(&data) {
+        fail(format!(
+            "{path} is neither a save state from a software renderer build nor a VRAM dump, \
+             press F12 in a hardware renderer build to dump VRAM instead"
+        ));
+    }
+
+    cpu.bus.gpu.vram.to_vec()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 3 {
+        fail(SYNTAX.to_string());
+    }
+
+    let vram = load_vram(&args[1]);
+    let viewer = VramViewer::new(&vram).unwrap_or_else(|e| fail(e));
+
+    let image: Image = if let Some(attributes) = get_numbers(&args, "--texture", 2) {
+        let texpage = GPU::parse_texpage(attributes[0] as u32);
+        let clut = GPU::parse_clut(attributes[1] as u32);
+
+        let region = get_numbers(&args, "--region", 4).unwrap_or(vec![0, 0, 256, 256]);
+
+        viewer.texture(&texpage, clut, (region[0], region[1]), region[2], region[3])
+    } else if let Some(palette) = get_numbers(&args, "--palette", 2) {
+        let colors = match palette[1] {
+            4 => TexturePageColors::Bit4,
+            8 => TexturePageColors::Bit8,
+            depth => fail(format!("CLUTs are either 4 or 8-bit, not {depth}")),
+        };
+
+        viewer.palette(GPU::parse_clut(palette[0] as u32), colors)
+    } else if let Some(region) = get_numbers(&args, "--direct15", 4) {
+        viewer.direct15(region[0], region[1], region[2], region[3])
+    } else if let Some(region) = get_numbers(&args, "--direct24", 4) {
+        viewer.direct24(region[0], region[1], region[2], region[3])
+    } else {
+        viewer.full()
+    };
+
+    let output = File::create(&args[2])
+        .unwrap_or_else(|e| fail(format!("couldn't create {}: {e}", args[2])));
+
+    if let Err(e) = image.write_png(BufWriter::new(output)) {
+        fail(format!("couldn't write {}: {e}", args[2]));
+    }
+
+    println!(
+        "wrote a {}x{} image to {}",
+        image.width, image.height, args[2]
+    );
+}
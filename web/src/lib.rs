@@ -1,7 +1,7 @@
 use std::panic;
 
 use renderer_webgl::renderer::Renderer;
-use rsx_redux::cpu::CPU;
+use rsx_redux::cpu::{CPU, logger};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[wasm_bindgen]
@@ -28,6 +28,8 @@ impl PsxWebEmulator {
     #[wasm_bindgen(constructor)]
     pub fn new(canvas_id: &str) -> Self {
         panic::set_hook(Box::new(console_error_panic_hook::hook));
+        // there's no stdout to echo to, the page reads the log with take_log instead
+        logger::set_echo(false);
 
         Self {
             cpu: CPU::new(None, "".to_string()),
@@ -68,6 +70,18 @@ impl PsxWebEmulator {
         self.cpu.bus.gpu.update_framebuffer();
     }
 
+    pub fn set_log_filters(&mut self, filters: &str) -> Result<(), String> {
+        logger::apply_filters(filters)
+    }
+
+    // the lines logged since the last call
+    pub fn take_log(&mut self) -> String {
+        logger::take_new_entries()
+            .iter()
+            .map(|entry| format!("{entry}\n"))
+            .collect()
+    }
+
     pub fn drain_samples(&mut self) -> Vec<i16> {
         self.cpu.bus.spu.audio_buffer.drain(..).collect()
     }